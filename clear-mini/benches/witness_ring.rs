@@ -0,0 +1,146 @@
+// Concurrent recording throughput of the witness ring versus the previous
+// design: a `Mutex<VecDeque>` ring (digest computed under the lock) behind
+// the daemon's global `Mutex<ClearMini>`. Run with
+// `cargo bench -p clear-mini --bench witness_ring`.
+//
+// `record_with_log` measures what the daemon ships: `ClearMini::record_entry`
+// with a segment log attached, appended by the background writer.
+
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clear_mini::api::ClearMini;
+use clear_mini::config::Config;
+use clear_mini::segment::{SegmentConfig, SegmentLog};
+use clear_mini::witness::{Ring, WitnessRecord};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const CAPACITY: usize = 4096;
+
+/// The ring as it was before the seqlock slots.
+struct MutexRing {
+    cap: usize,
+    state: Mutex<(VecDeque<WitnessRecord>, u64, [u8; 32])>,
+}
+
+impl MutexRing {
+    fn new(cap: usize) -> Self {
+        Self { cap, state: Mutex::new((VecDeque::with_capacity(cap), 0, [0; 32])) }
+    }
+
+    fn push(&self, mut r: WitnessRecord) {
+        let mut st = self.state.lock().unwrap();
+        r.seq = st.1;
+        r.prev = st.2;
+        st.2 = r.digest();
+        st.1 += 1;
+        if st.0.len() == self.cap {
+            st.0.pop_front();
+        }
+        st.0.push_back(r);
+    }
+}
+
+fn record(i: u64) -> WitnessRecord {
+    WitnessRecord { src: i as i32, len: 512, port: 443, ..Default::default() }
+}
+
+/// Run `iters` pushes split across `threads`, returning the wall time.
+fn run(threads: usize, iters: u64, push: Arc<dyn Fn(WitnessRecord) + Send + Sync>) -> Duration {
+    let per_thread = iters.div_ceil(threads as u64);
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let push = push.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                for i in 0..per_thread {
+                    push(black_box(record(i)));
+                }
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    for h in handles {
+        h.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn concurrent_push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("witness_push");
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::new("mutex_vecdeque", threads), &threads, |b, &threads| {
+            let outer = Arc::new(Mutex::new(MutexRing::new(CAPACITY)));
+            b.iter_custom(|iters| {
+                let outer = outer.clone();
+                run(threads, iters, Arc::new(move |r| outer.lock().unwrap().push(r)))
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("seqlock_ring", threads), &threads, |b, &threads| {
+            let ring = Arc::new(Ring::new(CAPACITY));
+            b.iter_custom(|iters| {
+                let ring = ring.clone();
+                run(threads, iters, Arc::new(move |r| {
+                    ring.push(r);
+                }))
+            });
+        });
+    }
+    group.finish();
+}
+
+fn record_with_log(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("clear-mini-bench-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut group = c.benchmark_group("witness_record_with_log");
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            let cm = Arc::new(ClearMini::with_config(Config { witness_capacity: CAPACITY, ..Default::default() }));
+            let segments = SegmentConfig { max_segments: 4, ..Default::default() };
+            let log = SegmentLog::open(dir.join(threads.to_string()), segments).unwrap();
+            cm.attach_log(log).unwrap();
+            b.iter_custom(|iters| {
+                let writer = cm.clone();
+                let elapsed = run(threads, iters, Arc::new(move |r| {
+                    writer.record_entry(r).unwrap();
+                }));
+                // Count the time the writer needs to catch up.
+                let start = Instant::now();
+                cm.flush_log().unwrap();
+                elapsed + start.elapsed()
+            });
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn snapshot_under_load(c: &mut Criterion) {
+    let ring = Arc::new(Ring::new(CAPACITY));
+    for i in 0..CAPACITY as u64 {
+        ring.push(record(i));
+    }
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let writer = {
+        let (ring, stop) = (ring.clone(), stop.clone());
+        thread::spawn(move || {
+            let mut i = 0;
+            while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                ring.push(record(i));
+                i += 1;
+            }
+        })
+    };
+    c.bench_function("witness_snapshot_with_writer", |b| b.iter(|| black_box(ring.snapshot().len())));
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    writer.join().unwrap();
+}
+
+criterion_group!(benches, concurrent_push, record_with_log, snapshot_under_load);
+criterion_main!(benches);
@@ -1,17 +1,85 @@
 use crc32fast::Hasher;
+use ed25519_dalek::VerifyingKey;
+use std::io;
+use std::sync::OnceLock;
 
+use crate::chain::{self, ChainCheckpoint, ChainError, VerifyReport};
+use crate::config::{Config, OverflowPolicy};
+use crate::export::ExportFormat;
+use crate::query::{self, Matcher, MinuteBucket, Page, WitnessQuery};
+use crate::segment::SegmentLog;
+use crate::writer::LogWriter;
 use crate::{kairo_p::PAddressRecord, time, witness::Ring, witness::WitnessRecord};
 
 pub struct ClearMini {
     pub ring: Ring,
+    overflow: OverflowPolicy,
+    log: OnceLock<LogWriter>,
 }
 
 impl ClearMini {
     pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Self {
         time::init_monotonic_base();
         Self {
-            ring: Ring::new(4096),
+            ring: Ring::new(config.witness_capacity.max(1)),
+            overflow: config.overflow,
+            log: OnceLock::new(),
+        }
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    /// Persist recorded witnesses to `log` according to the overflow policy,
+    /// resuming the chain (and refilling the ring) from the records already
+    /// on disk. Only one log can be attached.
+    pub fn attach_log(&self, log: SegmentLog) -> io::Result<()> {
+        if self.log.get().is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "a witness log is already attached"));
+        }
+        if let Some(last) = log.last() {
+            let from = (last.seq + 1).saturating_sub(self.ring.capacity() as u64);
+            self.ring.restore(log.read_from(from)?, Vec::new());
         }
+        // The first record the log will receive: the next one pushed, or
+        // when spilling, the next one evicted.
+        let ring = self.ring.snapshot();
+        let next = match log.last() {
+            Some(last) => last.seq + 1,
+            None if self.overflow == OverflowPolicy::SpillToDisk => ring.first().map_or(0, |r| r.seq),
+            None => ring.last().map_or(0, |r| r.seq + 1),
+        };
+        self.log
+            .set(LogWriter::spawn(log, next)?)
+            .map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "a witness log is already attached"))
+    }
+
+    /// Wait until every record recorded so far has been appended to the
+    /// log by its writer thread.
+    pub fn flush_log(&self) -> io::Result<()> {
+        match self.log.get() {
+            Some(writer) => writer.flush(Vec::new(), false),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the persistent log, if any, to stable storage. When spilling,
+    /// the ring's records not yet on disk are appended first.
+    pub fn sync_log(&self) -> io::Result<()> {
+        let Some(writer) = self.log.get() else {
+            return Ok(());
+        };
+        let unsaved = match self.overflow {
+            // The writer skips the ones already on disk.
+            OverflowPolicy::SpillToDisk => self.ring.snapshot(),
+            OverflowPolicy::DropOldest => Vec::new(),
+        };
+        writer.flush(unsaved, true)
     }
 
     pub fn record(
@@ -22,22 +90,111 @@ impl ClearMini {
         flags: u32,
         ip: [u8; 16],
         port: u16,
-    ) {
-        let mut h = Hasher::new();
-        h.update(&ip);
+    ) -> io::Result<()> {
         let r = WitnessRecord {
-            mono: time::now_monotonic_ns(),
-            utc: time::now_utc_ns(),
             src: src.id,
             dst: dst.id,
             len,
-            hash32: h.finalize(),
             flags,
             port,
             ip,
             ..Default::default()
         };
-        self.ring.push(r);
+        self.record_entry(r).map(|_| ())
+    }
+
+    /// Record a prepared entry (e.g. one carrying an [`Extension`](crate::witness::Extension)).
+    /// Timestamps and `hash32` are filled in here; returns the record as chained.
+    pub fn record_entry(&self, mut r: WitnessRecord) -> io::Result<WitnessRecord> {
+        let mut h = Hasher::new();
+        h.update(&r.ip);
+        r.hash32 = h.finalize();
+        r.mono = time::now_monotonic_ns();
+        r.utc = time::now_utc_ns();
+        let (chained, evicted) = self.ring.push_evicting(r);
+        // The writer thread does the disk I/O and restores chain order.
+        if let Some(writer) = self.log.get() {
+            match self.overflow {
+                OverflowPolicy::DropOldest => writer.append(chained.clone())?,
+                OverflowPolicy::SpillToDisk => {
+                    if let Some(e) = evicted {
+                        writer.append(e)?;
+                    }
+                }
+            }
+        }
+        Ok(chained)
+    }
+
+    /// Re-insert records and chain checkpoints from a previous snapshot,
+    /// oldest first, keeping their chain links intact.
+    pub fn restore_witness(&self, records: Vec<WitnessRecord>, checkpoints: Vec<ChainCheckpoint>) {
+        self.ring.restore(records, checkpoints);
+    }
+
+    /// Check the ring's hash chain and signed checkpoints.
+    pub fn verify_witness(&self, trusted: Option<&VerifyingKey>) -> Result<VerifyReport, ChainError> {
+        chain::verify(&self.ring.snapshot(), &self.ring.checkpoints(), trusted)
+    }
+
+    /// Every witness record still held, oldest first: the on-disk log (if
+    /// attached) followed by ring records newer than its tail.
+    pub fn witness_history(&self) -> io::Result<Vec<WitnessRecord>> {
+        self.with_history(None, None, |records| records.collect())
+    }
+
+    /// Run `consume` over the witness history, with log records in the UTC
+    /// range streamed from disk one at a time rather than loaded up front.
+    /// The log lock is only held to start the scan.
+    fn with_history<T>(
+        &self,
+        since_utc: Option<u128>,
+        until_utc: Option<u128>,
+        consume: impl FnOnce(&mut dyn Iterator<Item = WitnessRecord>) -> T,
+    ) -> io::Result<T> {
+        // Records evicted from the ring must reach the log before the scan.
+        self.flush_log()?;
+        let ring = self.ring.snapshot();
+        let disk = match self.log.get() {
+            Some(writer) => Some(writer.log().scan(since_utc, until_utc)?),
+            None => None,
+        };
+        let tail = disk.as_ref().and_then(|d| d.upto());
+        let mut failed = None;
+        let mut records = disk
+            .into_iter()
+            .flatten()
+            .map_while(|r| r.map_err(|e| failed = Some(e)).ok())
+            .chain(ring.into_iter().filter(|r| tail.is_none_or(|t| r.seq > t)));
+        let out = consume(&mut records);
+        drop(records);
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(out),
+        }
+    }
+
+    /// Filter the witness history and return one page of matches.
+    pub fn query_witness(&self, q: &WitnessQuery) -> io::Result<Page> {
+        self.with_history(q.since_utc, q.until_utc, |records| query::query(records, q))?
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Count matching witness records per destination per UTC minute.
+    pub fn aggregate_witness(&self, q: &WitnessQuery) -> io::Result<Vec<MinuteBucket>> {
+        self.with_history(q.since_utc, q.until_utc, |records| query::per_destination_per_minute(records, q))?
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Write every witness record matching `q` (pagination ignored) to `w`.
+    /// Returns the number of records written.
+    pub fn export_witness<W: io::Write>(&self, q: &WitnessQuery, format: ExportFormat, w: W) -> io::Result<usize> {
+        let matcher = Matcher::new(q).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut written = 0;
+        self.with_history(q.since_utc, q.until_utc, |records| {
+            format.write(w, records.filter(|r| matcher.matches(r)).inspect(|_| written += 1))
+        })??;
+        Ok(written)
     }
 
     /// Return a snapshot of the witness ring for internal control-plane use.
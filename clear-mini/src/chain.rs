@@ -0,0 +1,151 @@
+//! Tamper evidence for the witness ring.
+//!
+//! Records are linked by `WitnessRecord::prev` (SHA-256 of the predecessor)
+//! and the ring periodically signs the chain head. [`verify`] walks a run of
+//! records and the checkpoints covering it.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{time, witness::WitnessRecord};
+
+/// Ed25519 signature over the chain head after record `seq`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainCheckpoint {
+    pub seq: u64,
+    pub head: [u8; 32],
+    pub utc: u128,
+    pub public_key: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+fn signed_bytes(seq: u64, head: &[u8; 32], utc: u128) -> Vec<u8> {
+    let mut msg = b"KAIRO-WITNESS-CP-v1".to_vec();
+    msg.extend_from_slice(&seq.to_le_bytes());
+    msg.extend_from_slice(head);
+    msg.extend_from_slice(&utc.to_le_bytes());
+    msg
+}
+
+impl ChainCheckpoint {
+    pub fn sign(key: &SigningKey, seq: u64, head: [u8; 32]) -> Self {
+        let utc = time::now_utc_ns();
+        let signature = key.sign(&signed_bytes(seq, &head, utc)).to_bytes().to_vec();
+        Self {
+            seq,
+            head,
+            utc,
+            public_key: key.verifying_key().to_bytes(),
+            signature,
+        }
+    }
+
+    /// Check the signature; with `trusted`, also require that key.
+    pub fn verify(&self, trusted: Option<&VerifyingKey>) -> bool {
+        if trusted.is_some_and(|k| k.to_bytes() != self.public_key) {
+            return false;
+        }
+        let Ok(key) = VerifyingKey::from_bytes(&self.public_key) else {
+            return false;
+        };
+        let Ok(sig) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        key.verify(&signed_bytes(self.seq, &self.head, self.utc), &sig).is_ok()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainError {
+    /// `missing` records after `after_seq` are absent.
+    Dropped { after_seq: u64, missing: u64 },
+    /// Record `found_seq` appears where `expected_seq` should be.
+    Reordered { expected_seq: u64, found_seq: u64 },
+    /// Record `seq` no longer matches the hash its successor or a checkpoint committed to.
+    Tampered { seq: u64 },
+    BadSignature { seq: u64 },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dropped { after_seq, missing } => write!(f, "{} record(s) missing after seq {}", missing, after_seq),
+            Self::Reordered { expected_seq, found_seq } => {
+                write!(f, "expected seq {} but found seq {}", expected_seq, found_seq)
+            }
+            Self::Tampered { seq } => write!(f, "record seq {} was modified", seq),
+            Self::BadSignature { seq } => write!(f, "checkpoint at seq {} has an invalid signature", seq),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    pub records: usize,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    pub checkpoints_checked: usize,
+}
+
+/// Verify a contiguous run of records (oldest first) against `checkpoints`.
+///
+/// The first record is trusted as the anchor unless it is seq 0 or covered by
+/// a checkpoint. Edits to records after the newest checkpoint are only
+/// detected once a later record or checkpoint commits to them.
+pub fn verify(
+    records: &[WitnessRecord],
+    checkpoints: &[ChainCheckpoint],
+    trusted: Option<&VerifyingKey>,
+) -> Result<VerifyReport, ChainError> {
+    if let Some(first) = records.first() {
+        if first.seq == 0 && first.prev != [0; 32] {
+            return Err(ChainError::Tampered { seq: 0 });
+        }
+    }
+    for (i, pair) in records.windows(2).enumerate() {
+        let (a, b) = (&pair[0], &pair[1]);
+        let expected_seq = a.seq + 1;
+        if b.seq != expected_seq {
+            let later = records[i + 2..].iter().any(|r| r.seq == expected_seq);
+            if b.seq < expected_seq || later {
+                return Err(ChainError::Reordered { expected_seq, found_seq: b.seq });
+            }
+            return Err(ChainError::Dropped { after_seq: a.seq, missing: b.seq - expected_seq });
+        }
+        if b.prev != a.digest() {
+            return Err(ChainError::Tampered { seq: a.seq });
+        }
+    }
+
+    let first_seq = records.first().map(|r| r.seq);
+    let last_seq = records.last().map(|r| r.seq);
+    let mut checked = 0;
+    for cp in checkpoints {
+        if !cp.verify(trusted) {
+            return Err(ChainError::BadSignature { seq: cp.seq });
+        }
+        let (Some(first), Some(last)) = (first_seq, last_seq) else {
+            continue;
+        };
+        if cp.seq < first {
+            continue;
+        }
+        if cp.seq > last {
+            return Err(ChainError::Dropped { after_seq: last, missing: cp.seq - last });
+        }
+        if records[(cp.seq - first) as usize].digest() != cp.head {
+            return Err(ChainError::Tampered { seq: cp.seq });
+        }
+        checked += 1;
+    }
+
+    Ok(VerifyReport {
+        records: records.len(),
+        first_seq,
+        last_seq,
+        checkpoints_checked: checked,
+    })
+}
@@ -1,12 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens to a witness record pushed out of a full ring.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Discard it. An attached log receives every record as it is written.
+    #[default]
+    DropOldest,
+    /// Append it to the attached log, which otherwise only receives the
+    /// ring's remaining records on `ClearMini::sync_log`.
+    SpillToDisk,
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub witness_capacity: usize,
+    pub overflow: OverflowPolicy,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             witness_capacity: 4096,
+            overflow: OverflowPolicy::DropOldest,
         }
     }
 }
@@ -1,9 +1,21 @@
+//! Sliding-window rate detectors.
+//!
+//! [`Window`] counts hits per key exactly and suits a small, known key set.
+//! [`Detector`] has bounded memory for any number of keys: a count-min sketch
+//! for per-key hit counts, HyperLogLog for distinct members per key (e.g.
+//! sources per destination) and a top-K list of heavy hitters. The window
+//! slides in `buckets` steps, so counts age out at bucket granularity.
+
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
 use std::time::{Duration, Instant};
 
+/// Exact per-key sliding window. Keys idle for a whole window are dropped.
 pub struct Window {
     win: Duration,
     map: HashMap<u64, VecDeque<Instant>>,
+    last_sweep: Option<Instant>,
 }
 
 impl Window {
@@ -11,12 +23,20 @@ impl Window {
         Self {
             win: Duration::from_secs(seconds),
             map: HashMap::new(),
+            last_sweep: None,
         }
     }
 
     pub fn hit(&mut self, key: u64) -> usize {
-        let now = Instant::now();
-        let queue = self.map.entry(key).or_insert_with(VecDeque::new);
+        self.hit_at(key, Instant::now())
+    }
+
+    pub fn hit_at(&mut self, key: u64, now: Instant) -> usize {
+        if self.last_sweep.is_none_or(|t| now.duration_since(t) > self.win) {
+            self.evict_idle(now);
+            self.last_sweep = Some(now);
+        }
+        let queue = self.map.entry(key).or_default();
         queue.push_back(now);
 
         while let Some(&front) = queue.front() {
@@ -29,4 +49,365 @@ impl Window {
 
         queue.len()
     }
+
+    /// Drop keys with no hit inside the window.
+    pub fn evict_idle(&mut self, now: Instant) {
+        let win = self.win;
+        self.map
+            .retain(|_, q| q.back().is_some_and(|&t| now.duration_since(t) <= win));
+    }
+
+    /// Keys currently tracked.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+fn hash64<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut h = DefaultHasher::new();
+    value.hash(&mut h);
+    h.finish()
+}
+
+/// Ring of `n` buckets covering a window; bucket `i` holds epoch `epochs[i]`.
+#[derive(Clone)]
+struct Buckets {
+    origin: Instant,
+    step: Duration,
+    epochs: Vec<u64>,
+}
+
+impl Buckets {
+    fn new(window: Duration, n: usize, origin: Instant) -> Self {
+        let n = n.max(1);
+        Self {
+            origin,
+            step: (window / n as u32).max(Duration::from_millis(1)),
+            epochs: vec![u64::MAX; n],
+        }
+    }
+
+    fn epoch(&self, now: Instant) -> u64 {
+        (now.saturating_duration_since(self.origin).as_nanos() / self.step.as_nanos()) as u64
+    }
+
+    /// Index of the current bucket and whether it must be cleared first.
+    fn current(&mut self, now: Instant) -> (usize, bool) {
+        let epoch = self.epoch(now);
+        let i = (epoch % self.epochs.len() as u64) as usize;
+        let stale = self.epochs[i] != epoch;
+        self.epochs[i] = epoch;
+        (i, stale)
+    }
+
+    /// Buckets still inside the window at `now`.
+    fn live(&self, now: Instant) -> impl Iterator<Item = usize> + '_ {
+        let epoch = self.epoch(now);
+        let n = self.epochs.len() as u64;
+        self.epochs
+            .iter()
+            .enumerate()
+            .filter(move |(_, &e)| e != u64::MAX && e <= epoch && epoch - e < n)
+            .map(|(i, _)| i)
+    }
+}
+
+/// Count-min sketch over a sliding window: estimates never undercount and
+/// overcount by at most `e / width` of the window's total hits with
+/// probability `1 - e^-depth`.
+#[derive(Clone)]
+pub struct CountMinWindow {
+    width: usize,
+    depth: usize,
+    buckets: Buckets,
+    /// `[bucket][row][column]`, flattened.
+    counts: Vec<u32>,
+}
+
+impl CountMinWindow {
+    pub fn new(window: Duration, buckets: usize, width: usize, depth: usize, origin: Instant) -> Self {
+        let buckets = Buckets::new(window, buckets, origin);
+        let (width, depth) = (width.max(1), depth.max(1));
+        Self {
+            counts: vec![0; buckets.epochs.len() * width * depth],
+            width,
+            depth,
+            buckets,
+        }
+    }
+
+    fn columns(&self, hash: u64) -> impl Iterator<Item = usize> + '_ {
+        let (h1, h2) = (hash as u32 as u64, (hash >> 32) | 1);
+        (0..self.depth as u64).map(move |row| (h1.wrapping_add(row.wrapping_mul(h2)) % self.width as u64) as usize)
+    }
+
+    fn cell(&self, bucket: usize, row: usize, col: usize) -> usize {
+        (bucket * self.depth + row) * self.width + col
+    }
+
+    /// Add a hit for `hash` and return its estimated count in the window.
+    pub fn add(&mut self, hash: u64, now: Instant) -> u64 {
+        let (b, stale) = self.buckets.current(now);
+        if stale {
+            let span = self.depth * self.width;
+            self.counts[b * span..(b + 1) * span].fill(0);
+        }
+        let cols: Vec<usize> = self.columns(hash).collect();
+        for (row, col) in cols.into_iter().enumerate() {
+            let cell = self.cell(b, row, col);
+            self.counts[cell] = self.counts[cell].saturating_add(1);
+        }
+        self.estimate(hash, now)
+    }
+
+    pub fn estimate(&self, hash: u64, now: Instant) -> u64 {
+        let live: Vec<usize> = self.buckets.live(now).collect();
+        self.columns(hash)
+            .enumerate()
+            .map(|(row, col)| live.iter().map(|&b| self.counts[self.cell(b, row, col)] as u64).sum::<u64>())
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.counts.len() * std::mem::size_of::<u32>()
+    }
+}
+
+/// HyperLogLog over a sliding window (one register set per bucket, merged
+/// by max). Standard error is about `1.04 / sqrt(2^precision)`.
+#[derive(Clone)]
+pub struct SlidingHll {
+    precision: u8,
+    buckets: Buckets,
+    /// `[bucket][register]`, flattened.
+    registers: Vec<u8>,
+}
+
+impl SlidingHll {
+    pub fn new(window: Duration, buckets: usize, precision: u8, origin: Instant) -> Self {
+        let precision = precision.clamp(4, 16);
+        let buckets = Buckets::new(window, buckets, origin);
+        Self {
+            registers: vec![0; buckets.epochs.len() << precision],
+            precision,
+            buckets,
+        }
+    }
+
+    fn m(&self) -> usize {
+        1 << self.precision
+    }
+
+    pub fn add(&mut self, hash: u64, now: Instant) {
+        let (b, stale) = self.buckets.current(now);
+        let m = self.m();
+        let regs = &mut self.registers[b * m..(b + 1) * m];
+        if stale {
+            regs.fill(0);
+        }
+        let idx = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(64 - self.precision as u32 + 1) as u8;
+        regs[idx] = regs[idx].max(rank);
+    }
+
+    pub fn estimate(&self, now: Instant) -> u64 {
+        let m = self.m();
+        let mut merged = vec![0u8; m];
+        for b in self.buckets.live(now) {
+            for (out, &r) in merged.iter_mut().zip(&self.registers[b * m..(b + 1) * m]) {
+                *out = (*out).max(r);
+            }
+        }
+        let mf = m as f64;
+        let alpha = match m {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / mf),
+        };
+        let sum: f64 = merged.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * mf * mf / sum;
+        let zeros = merged.iter().filter(|&&r| r == 0).count();
+        let est = if raw <= 2.5 * mf && zeros > 0 {
+            // Linear counting for small cardinalities.
+            mf * (mf / zeros as f64).ln()
+        } else {
+            raw
+        };
+        est.round() as u64
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.registers.len()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DetectorConfig {
+    pub window: Duration,
+    /// Steps the window slides in.
+    pub buckets: usize,
+    /// Count-min columns and rows.
+    pub width: usize,
+    pub depth: usize,
+    /// HyperLogLog precision (registers = 2^precision per bucket).
+    pub hll_precision: u8,
+    /// Keys with distinct-member tracking; least recently seen go first.
+    pub max_distinct_keys: usize,
+    pub top_k: usize,
+}
+
+impl DetectorConfig {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            buckets: 6,
+            width: 1024,
+            depth: 4,
+            hll_precision: 8,
+            max_distinct_keys: 1024,
+            top_k: 10,
+        }
+    }
+}
+
+struct DistinctEntry {
+    hll: SlidingHll,
+    last_seen: Instant,
+}
+
+/// Bounded multi-key detector; see the module docs.
+pub struct Detector {
+    config: DetectorConfig,
+    origin: Instant,
+    counts: CountMinWindow,
+    distinct: HashMap<String, DistinctEntry>,
+    top: HashMap<String, u64>,
+}
+
+impl Detector {
+    pub fn new(config: DetectorConfig) -> Self {
+        let origin = Instant::now();
+        let counts = CountMinWindow::new(config.window, config.buckets, config.width, config.depth, origin);
+        Self {
+            config,
+            origin,
+            counts,
+            distinct: HashMap::new(),
+            top: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    /// Count a hit for `key`; returns its estimated hits in the window.
+    pub fn hit(&mut self, key: &str, now: Instant) -> u64 {
+        let count = self.counts.add(hash64(key), now);
+        self.track_top(key, count, now);
+        count
+    }
+
+    /// Estimated hits for `key` in the window, without counting one.
+    pub fn count(&self, key: &str, now: Instant) -> u64 {
+        self.counts.estimate(hash64(key), now)
+    }
+
+    /// Record `member` (e.g. a source) under `key`; returns the estimated
+    /// number of distinct members seen for `key` in the window.
+    pub fn hit_distinct(&mut self, key: &str, member: &str, now: Instant) -> u64 {
+        if !self.distinct.contains_key(key) && self.distinct.len() >= self.config.max_distinct_keys.max(1) {
+            self.evict_idle(now);
+            if self.distinct.len() >= self.config.max_distinct_keys.max(1) {
+                let lru = self.distinct.iter().min_by_key(|(_, e)| e.last_seen).map(|(k, _)| k.clone());
+                if let Some(k) = lru {
+                    self.distinct.remove(&k);
+                }
+            }
+        }
+        let (window, buckets, precision, origin) =
+            (self.config.window, self.config.buckets, self.config.hll_precision, self.origin);
+        let entry = self.distinct.entry(key.to_string()).or_insert_with(|| DistinctEntry {
+            hll: SlidingHll::new(window, buckets, precision, origin),
+            last_seen: now,
+        });
+        entry.last_seen = now;
+        entry.hll.add(hash64(member), now);
+        let distinct = entry.hll.estimate(now);
+        self.track_top(key, distinct, now);
+        distinct
+    }
+
+    fn track_top(&mut self, key: &str, value: u64, now: Instant) {
+        let k = self.config.top_k;
+        if k == 0 {
+            return;
+        }
+        if let Some(v) = self.top.get_mut(key) {
+            *v = value;
+            return;
+        }
+        if self.top.len() >= k {
+            self.refresh_top(now);
+        }
+        if self.top.len() < k {
+            self.top.insert(key.to_string(), value);
+            return;
+        }
+        let min = self.top.iter().min_by_key(|(_, &v)| v).map(|(k, &v)| (k.clone(), v));
+        if let Some((min_key, _)) = min.filter(|&(_, v)| value > v) {
+            self.top.remove(&min_key);
+            self.top.insert(key.to_string(), value);
+        }
+    }
+
+    /// Current value of a top-K entry: distinct members if tracked, else hits.
+    fn current(&self, key: &str, now: Instant) -> u64 {
+        match self.distinct.get(key) {
+            Some(e) => e.hll.estimate(now),
+            None => self.count(key, now),
+        }
+    }
+
+    fn refresh_top(&mut self, now: Instant) {
+        let fresh: Vec<(String, u64)> =
+            self.top.keys().map(|k| (k.clone(), self.current(k, now))).collect();
+        self.top = fresh.into_iter().filter(|(_, v)| *v > 0).collect();
+    }
+
+    /// Heaviest keys in the window, largest first.
+    pub fn top_k(&self, now: Instant) -> Vec<(String, u64)> {
+        let mut out: Vec<(String, u64)> = self
+            .top
+            .keys()
+            .map(|k| (k.clone(), self.current(k, now)))
+            .filter(|(_, v)| *v > 0)
+            .collect();
+        out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /// Drop distinct-member state for keys not seen for a whole window.
+    pub fn evict_idle(&mut self, now: Instant) {
+        let window = self.config.window;
+        self.distinct.retain(|_, e| now.saturating_duration_since(e.last_seen) <= window);
+        self.refresh_top(now);
+    }
+
+    /// Keys with distinct-member state.
+    pub fn tracked_keys(&self) -> usize {
+        self.distinct.len()
+    }
+
+    /// Approximate heap use of the sketches.
+    pub fn memory_bytes(&self) -> usize {
+        self.counts.memory_bytes() + self.distinct.values().map(|e| e.hll.memory_bytes()).sum::<usize>()
+    }
 }
@@ -0,0 +1,219 @@
+//! Witness exporters for offline investigation.
+//!
+//! JSONL writes one serialized [`WitnessRecord`] per line. pcapng writes one
+//! synthesized IP/UDP frame per record (link type RAW, like `kairof`
+//! captures): the source is the record's remote `ip:port`, the destination is
+//! loopback on [`WITNESS_UDP_PORT`], and the UDP payload is the record in its
+//! [`WitnessRecord::to_bytes`] layout. Each packet carries a comment with the
+//! decoded fields so Wireshark shows them without a dissector.
+
+use serde::Deserialize;
+use std::borrow::Borrow;
+use std::io::{self, Write};
+
+use crate::witness::{WitnessRecord, RECORD_SIZE};
+
+/// UDP destination port of synthesized witness frames.
+pub const WITNESS_UDP_PORT: u16 = 47_400;
+
+const LINKTYPE_RAW: u16 = 101;
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Jsonl,
+    Pcapng,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Jsonl => "application/x-ndjson",
+            Self::Pcapng => "application/x-pcapng",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Pcapng => "pcapng",
+        }
+    }
+
+    pub fn write<R: Borrow<WitnessRecord>, W: Write>(self, w: W, records: impl IntoIterator<Item = R>) -> io::Result<()> {
+        match self {
+            Self::Jsonl => write_jsonl(w, records),
+            Self::Pcapng => write_pcapng(w, records),
+        }
+    }
+}
+
+/// Write `records` as JSON lines.
+pub fn write_jsonl<R: Borrow<WitnessRecord>, W: Write>(mut w: W, records: impl IntoIterator<Item = R>) -> io::Result<()> {
+    for r in records {
+        serde_json::to_writer(&mut w, r.borrow())?;
+        w.write_all(b"\n")?;
+    }
+    w.flush()
+}
+
+/// Write `records` as a single-interface pcapng capture.
+pub fn write_pcapng<R: Borrow<WitnessRecord>, W: Write>(w: W, records: impl IntoIterator<Item = R>) -> io::Result<()> {
+    let mut out = PcapngWriter::new(w)?;
+    for r in records {
+        out.write(r.borrow())?;
+    }
+    out.into_inner().flush()
+}
+
+/// Streaming pcapng writer; the section and interface headers are written on
+/// construction.
+pub struct PcapngWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(mut w: W) -> io::Result<Self> {
+        let mut shb = Vec::new();
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        push_option(&mut shb, SHB_USERAPPL, b"clear-mini");
+        push_option(&mut shb, OPT_END, &[]);
+        write_block(&mut w, BLOCK_SHB, &shb)?;
+
+        let mut idb = Vec::new();
+        idb.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&0u32.to_le_bytes());
+        push_option(&mut idb, IF_NAME, b"kairo-witness");
+        // Timestamps are in nanoseconds.
+        push_option(&mut idb, IF_TSRESOL, &[9]);
+        push_option(&mut idb, OPT_END, &[]);
+        write_block(&mut w, BLOCK_IDB, &idb)?;
+        Ok(Self { w })
+    }
+
+    pub fn write(&mut self, r: &WitnessRecord) -> io::Result<()> {
+        let frame = frame(r);
+        let ts = r.utc as u64;
+        let mut epb = Vec::with_capacity(32 + frame.len() + 128);
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(ts as u32).to_le_bytes());
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&frame);
+        pad4(&mut epb);
+        let mut comment = format!(
+            "seq={} src={} dst={} len={} flags={:#x} hash32={:#010x} mono={}",
+            r.seq, r.src, r.dst, r.len, r.flags, r.hash32, r.mono
+        );
+        if let Some(ext) = r.extension() {
+            comment.push_str(&format!(" verdict={} scope={}", ext.verdict, ext.scope));
+        }
+        push_option(&mut epb, OPT_COMMENT, comment.as_bytes());
+        push_option(&mut epb, OPT_END, &[]);
+        write_block(&mut self.w, BLOCK_EPB, &epb)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+fn write_block<W: Write>(w: &mut W, kind: u32, body: &[u8]) -> io::Result<()> {
+    let total = (12 + body.len()) as u32;
+    w.write_all(&kind.to_le_bytes())?;
+    w.write_all(&total.to_le_bytes())?;
+    w.write_all(body)?;
+    w.write_all(&total.to_le_bytes())
+}
+
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    pad4(buf);
+}
+
+fn pad4(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    for chunk in chunks {
+        for pair in chunk.chunks(2) {
+            let hi = pair[0] as u32;
+            let lo = pair.get(1).copied().unwrap_or(0) as u32;
+            sum += (hi << 8) | lo;
+        }
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Synthesize an IPv4 or IPv6 UDP datagram carrying the record.
+fn frame(r: &WitnessRecord) -> Vec<u8> {
+    let payload = r.to_bytes();
+    let udp_len = (8 + RECORD_SIZE) as u16;
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend_from_slice(&r.port.to_be_bytes());
+    udp.extend_from_slice(&WITNESS_UDP_PORT.to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    // Checksum: zero ("not computed") is allowed over IPv4; IPv6 fills it in below.
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(&payload);
+
+    let mut out = Vec::with_capacity(40 + udp.len());
+    if r.ip[4..].iter().all(|&b| b == 0) {
+        let src = &r.ip[..4];
+        let dst = [127, 0, 0, 1];
+        let mut ip = [0u8; 20];
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&(20 + udp_len).to_be_bytes());
+        ip[4..6].copy_from_slice(&(r.seq as u16).to_be_bytes());
+        ip[8] = 64;
+        ip[9] = 17;
+        ip[12..16].copy_from_slice(src);
+        ip[16..20].copy_from_slice(&dst);
+        let sum = checksum(&[&ip]);
+        ip[10..12].copy_from_slice(&sum.to_be_bytes());
+        out.extend_from_slice(&ip);
+    } else {
+        let mut dst = [0u8; 16];
+        dst[15] = 1;
+        let mut ip = [0u8; 40];
+        ip[0] = 0x60;
+        ip[4..6].copy_from_slice(&udp_len.to_be_bytes());
+        ip[6] = 17;
+        ip[7] = 64;
+        ip[8..24].copy_from_slice(&r.ip);
+        ip[24..40].copy_from_slice(&dst);
+        let mut pseudo = [0u8; 8];
+        pseudo[2..4].copy_from_slice(&udp_len.to_be_bytes());
+        pseudo[7] = 17;
+        let sum = match checksum(&[&r.ip, &dst, &pseudo, &udp]) {
+            0 => 0xFFFF,
+            s => s,
+        };
+        udp[6..8].copy_from_slice(&sum.to_be_bytes());
+        out.extend_from_slice(&ip);
+    }
+    out.extend_from_slice(&udp);
+    out
+}
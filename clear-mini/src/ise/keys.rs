@@ -0,0 +1,152 @@
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+/// Retired keys kept for verifying stamps issued before a rotation.
+pub const DEFAULT_MAX_RETIRED: usize = 3;
+
+#[derive(Clone)]
+pub struct IseKey {
+    pub id: u32,
+    secret: [u8; 32],
+}
+
+impl IseKey {
+    pub fn new(id: u32, secret: [u8; 32]) -> Self {
+        Self { id, secret }
+    }
+
+    pub fn generate(id: u32) -> Self {
+        let mut secret = [0; 32];
+        OsRng.fill_bytes(&mut secret);
+        Self { id, secret }
+    }
+
+    pub(super) fn secret(&self) -> &[u8; 32] {
+        &self.secret
+    }
+}
+
+impl std::fmt::Debug for IseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IseKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    active: u32,
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyEntry {
+    id: u32,
+    secret_hex: String,
+}
+
+/// The active signing key plus recently retired ones, newest last.
+#[derive(Clone, Debug)]
+pub struct Keyring {
+    keys: Vec<IseKey>,
+    max_retired: usize,
+}
+
+impl Keyring {
+    pub fn new(active: IseKey) -> Self {
+        Self { keys: vec![active], max_retired: DEFAULT_MAX_RETIRED }
+    }
+
+    pub fn generate() -> Self {
+        Self::new(IseKey::generate(1))
+    }
+
+    pub fn with_max_retired(mut self, max_retired: usize) -> Self {
+        self.max_retired = max_retired;
+        self.prune();
+        self
+    }
+
+    pub fn active(&self) -> &IseKey {
+        self.keys.last().expect("keyring always holds its active key")
+    }
+
+    pub fn get(&self, id: u32) -> Option<&IseKey> {
+        self.keys.iter().find(|k| k.id == id)
+    }
+
+    pub fn ids(&self) -> Vec<u32> {
+        self.keys.iter().map(|k| k.id).collect()
+    }
+
+    /// Make a fresh key active, keeping the previous one for verification.
+    pub fn rotate(&mut self) -> &IseKey {
+        let id = self.keys.iter().map(|k| k.id).max().unwrap_or(0) + 1;
+        self.keys.push(IseKey::generate(id));
+        self.prune();
+        self.active()
+    }
+
+    fn prune(&mut self) {
+        let excess = self.keys.len().saturating_sub(self.max_retired + 1);
+        self.keys.drain(..excess);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let file: KeyFile = serde_json::from_slice(&std::fs::read(path)?).map_err(|e| invalid(e.to_string()))?;
+        let mut keys = Vec::new();
+        for entry in file.keys {
+            let secret = hex::decode(&entry.secret_hex)
+                .ok()
+                .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                .ok_or_else(|| invalid(format!("ISE key {} is not 32 hex bytes", entry.id)))?;
+            keys.push(IseKey::new(entry.id, secret));
+        }
+        // The active key goes last.
+        let pos = keys
+            .iter()
+            .position(|k| k.id == file.active)
+            .ok_or_else(|| invalid(format!("active ISE key {} is missing", file.active)))?;
+        let active = keys.remove(pos);
+        keys.push(active);
+        Ok(Self { keys, max_retired: DEFAULT_MAX_RETIRED })
+    }
+
+    /// Write the keyring readable by the owner only.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = KeyFile {
+            active: self.active().id,
+            keys: self
+                .keys
+                .iter()
+                .map(|k| KeyEntry { id: k.id, secret_hex: hex::encode(k.secret) })
+                .collect(),
+        };
+        let tmp = path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut out = options.open(&tmp)?;
+        io::Write::write_all(&mut out, &serde_json::to_vec_pretty(&file)?)?;
+        out.sync_all()?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Load the keyring at `path`, creating one with a fresh key if absent.
+    pub fn load_or_create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::load(path);
+        }
+        let ring = Self::generate();
+        ring.save(path)?;
+        Ok(ring)
+    }
+}
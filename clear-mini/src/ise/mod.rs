@@ -1,21 +1,123 @@
+//! Integrity stamps (ISE): HMAC-SHA256 over a payload with a node key.
+//!
+//! A [`Sig`] binds the key id, issue time and a random nonce to the payload.
+//! [`ise_verify`] checks the MAC against a [`Keyring`] and rejects stale or
+//! replayed stamps through a [`ReplayGuard`].
+
+mod keys;
+mod replay;
+
+pub use keys::{IseKey, Keyring};
+pub use replay::ReplayGuard;
+
+use hmac::{Hmac, Mac};
 use rand::{rngs::OsRng, RngCore};
-use sha2::{Digest, Sha256};
+use sha2::Sha256;
+use std::fmt;
+
+use crate::time;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Encoded size of a [`Sig`].
+pub const SIG_SIZE: usize = 4 + 8 + 16 + 32;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Sig {
+    pub key_id: u32,
+    /// Unix seconds at signing.
+    pub issued: u64,
     pub nonce: [u8; 16],
     pub sig: [u8; 32],
 }
 
-pub fn ise_sign(payload: &[u8]) -> Sig {
+impl Sig {
+    pub fn to_bytes(&self) -> [u8; SIG_SIZE] {
+        let mut b = [0u8; SIG_SIZE];
+        b[..4].copy_from_slice(&self.key_id.to_le_bytes());
+        b[4..12].copy_from_slice(&self.issued.to_le_bytes());
+        b[12..28].copy_from_slice(&self.nonce);
+        b[28..].copy_from_slice(&self.sig);
+        b
+    }
+
+    pub fn from_bytes(b: &[u8; SIG_SIZE]) -> Self {
+        Self {
+            key_id: u32::from_le_bytes(b[..4].try_into().unwrap()),
+            issued: u64::from_le_bytes(b[4..12].try_into().unwrap()),
+            nonce: b[12..28].try_into().unwrap(),
+            sig: b[28..].try_into().unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IseError {
+    UnknownKey(u32),
+    BadMac,
+    /// Issued outside the replay window (too old, or too far in the future).
+    Stale { issued: u64, now: u64 },
+    Replayed,
+    /// The replay cache is full; stamps are refused rather than risk a replay.
+    ReplayCacheFull,
+}
+
+impl fmt::Display for IseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey(id) => write!(f, "unknown ISE key id {}", id),
+            Self::BadMac => write!(f, "ISE signature does not match"),
+            Self::Stale { issued, now } => write!(f, "ISE stamp issued at {} is outside the window at {}", issued, now),
+            Self::Replayed => write!(f, "ISE nonce was already used"),
+            Self::ReplayCacheFull => write!(f, "ISE replay cache is full"),
+        }
+    }
+}
+
+impl std::error::Error for IseError {}
+
+fn mac(key: &IseKey, issued: u64, nonce: &[u8; 16], payload: &[u8]) -> HmacSha256 {
+    let mut m = HmacSha256::new_from_slice(key.secret()).expect("HMAC accepts any key length");
+    m.update(b"KAIRO-ISE-v1");
+    m.update(&key.id.to_le_bytes());
+    m.update(&issued.to_le_bytes());
+    m.update(nonce);
+    m.update(payload);
+    m
+}
+
+pub fn now_secs() -> u64 {
+    (time::now_utc_ns() / 1_000_000_000) as u64
+}
+
+/// Stamp `payload` with `key`.
+pub fn ise_sign(key: &IseKey, payload: &[u8]) -> Sig {
+    ise_sign_at(key, payload, now_secs())
+}
+
+pub fn ise_sign_at(key: &IseKey, payload: &[u8], issued: u64) -> Sig {
     let mut nonce = [0; 16];
     OsRng.fill_bytes(&mut nonce);
+    let sig = mac(key, issued, &nonce, payload).finalize().into_bytes().into();
+    Sig { key_id: key.id, issued, nonce, sig }
+}
 
-    let mut hasher = Sha256::new();
-    hasher.update(&nonce);
-    hasher.update(payload);
-
-    let mut sig = [0u8; 32];
-    sig.copy_from_slice(&hasher.finalize());
+/// Check `sig` over `payload` and record its nonce in `replay`.
+pub fn ise_verify(keys: &Keyring, sig: &Sig, payload: &[u8], replay: &mut ReplayGuard) -> Result<(), IseError> {
+    ise_verify_at(keys, sig, payload, replay, now_secs())
+}
 
-    Sig { nonce, sig }
+pub fn ise_verify_at(
+    keys: &Keyring,
+    sig: &Sig,
+    payload: &[u8],
+    replay: &mut ReplayGuard,
+    now: u64,
+) -> Result<(), IseError> {
+    let key = keys.get(sig.key_id).ok_or(IseError::UnknownKey(sig.key_id))?;
+    // Constant-time comparison.
+    mac(key, sig.issued, &sig.nonce, payload)
+        .verify_slice(&sig.sig)
+        .map_err(|_| IseError::BadMac)?;
+    replay.check(sig, now)
 }
@@ -0,0 +1,62 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use super::{IseError, Sig};
+
+/// Remembers nonces of accepted stamps for as long as their issue time is
+/// inside the window; older stamps are rejected outright, so the cache stays
+/// bounded by the stamp rate times the window.
+pub struct ReplayGuard {
+    window: u64,
+    max_entries: usize,
+    seen: HashSet<(u32, [u8; 16])>,
+    /// Insertion order, for expiry.
+    order: VecDeque<(u64, u32, [u8; 16])>,
+}
+
+impl ReplayGuard {
+    pub fn new(window: Duration, max_entries: usize) -> Self {
+        Self {
+            window: window.as_secs().max(1),
+            max_entries,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    fn expire(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(self.window);
+        while let Some(&(issued, key_id, nonce)) = self.order.front() {
+            if issued >= cutoff {
+                break;
+            }
+            self.order.pop_front();
+            self.seen.remove(&(key_id, nonce));
+        }
+    }
+
+    /// Accept `sig` once if it was issued within the window around `now`.
+    pub fn check(&mut self, sig: &Sig, now: u64) -> Result<(), IseError> {
+        if sig.issued + self.window < now || sig.issued > now + self.window {
+            return Err(IseError::Stale { issued: sig.issued, now });
+        }
+        self.expire(now);
+        if self.seen.contains(&(sig.key_id, sig.nonce)) {
+            return Err(IseError::Replayed);
+        }
+        if self.seen.len() >= self.max_entries {
+            return Err(IseError::ReplayCacheFull);
+        }
+        self.seen.insert((sig.key_id, sig.nonce));
+        self.order.push_back((sig.issued, sig.key_id, sig.nonce));
+        Ok(())
+    }
+}
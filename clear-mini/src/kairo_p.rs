@@ -1,3 +1,5 @@
+use sha2::{Digest, Sha256};
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct PAddressRecord {
@@ -30,4 +32,31 @@ impl PAddressRecord {
         record.id = id;
         record
     }
+
+    /// Record for the agent at `p_address`, identified by its Ed25519
+    /// `public_key` (hex) through [`agent_id`]. Id 0 when the key is unknown.
+    pub fn for_agent(p_address: &str, public_key: Option<&str>) -> Self {
+        Self::new(public_key.map_or(0, agent_id), p_address)
+    }
+}
+
+/// Stable witness id for an agent: the first four bytes of
+/// SHA-256(`"KAIRO-AGENT-v2"` ‖ public key) as a big-endian `u32`,
+/// reinterpreted as `i32` so all 32 bits are kept.
+///
+/// The key, not the P address, is hashed: addresses are leased and reassigned,
+/// keys stay with the agent. Every node derives the same id, so `src`/`dst` in
+/// witness logs join across restarts and nodes. 0 stays reserved for "unknown".
+pub fn agent_id(public_key: &str) -> i32 {
+    let Ok(key) = hex::decode(public_key.trim()) else {
+        return 0;
+    };
+    if key.is_empty() {
+        return 0;
+    }
+    let digest = Sha256::new().chain_update(b"KAIRO-AGENT-v2").chain_update(&key).finalize();
+    match u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) {
+        0 => 1,
+        id => id as i32,
+    }
 }
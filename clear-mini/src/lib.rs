@@ -1,7 +1,12 @@
 pub mod api;
+pub mod chain;
 pub mod config;
 pub mod detector;
+pub mod export;
 pub mod ise;
 pub mod kairo_p;
+pub mod query;
+pub mod segment;
 pub mod time;
 pub mod witness;
+mod writer;
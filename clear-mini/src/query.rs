@@ -0,0 +1,198 @@
+//! Filtering, pagination and aggregation over witness records.
+//!
+//! IPv4 addresses are expected in the first four bytes of `WitnessRecord::ip`
+//! with the rest zeroed, which is how the daemon records them.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::kairo_p::agent_id;
+use crate::witness::WitnessRecord;
+
+pub const DEFAULT_LIMIT: usize = 1000;
+const NS_PER_MINUTE: u128 = 60_000_000_000;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct WitnessQuery {
+    pub src: Option<i32>,
+    pub dst: Option<i32>,
+    /// Agent public keys (hex), matched through [`agent_id`].
+    pub src_key: Option<String>,
+    pub dst_key: Option<String>,
+    pub port: Option<u16>,
+    /// `10.0.0.0/8`, `fd00::/8`, or a single address.
+    pub ip: Option<String>,
+    /// Match records having any of these flag bits.
+    pub flags: Option<u32>,
+    /// Match records having all of these flag bits.
+    pub flags_all: Option<u32>,
+    /// Inclusive UTC range in nanoseconds.
+    #[serde(default, deserialize_with = "opt_u128")]
+    pub since_utc: Option<u128>,
+    #[serde(default, deserialize_with = "opt_u128")]
+    pub until_utc: Option<u128>,
+    /// Inclusive monotonic range in nanoseconds.
+    #[serde(default, deserialize_with = "opt_u128")]
+    pub since_mono: Option<u128>,
+    #[serde(default, deserialize_with = "opt_u128")]
+    pub until_mono: Option<u128>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// Accept `u128` from JSON numbers or from strings, since query-string
+/// deserializers only parse integers up to 64 bits.
+fn opt_u128<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u128>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Num(u128),
+        Str(String),
+    }
+    match Option::<Raw>::deserialize(d)? {
+        None => Ok(None),
+        Some(Raw::Num(n)) => Ok(Some(n)),
+        Some(Raw::Str(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    v4: bool,
+    prefix: [u8; 16],
+    bits: u8,
+}
+
+impl IpRange {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (addr, bits) = match s.split_once('/') {
+            Some((a, b)) => (a, Some(b.parse::<u8>().map_err(|_| format!("bad prefix length in {:?}", s))?)),
+            None => (s, None),
+        };
+        let ip: IpAddr = addr.parse().map_err(|_| format!("bad IP address {:?}", s))?;
+        let (v4, prefix, max) = match ip {
+            IpAddr::V4(v4) => {
+                let mut p = [0u8; 16];
+                p[..4].copy_from_slice(&v4.octets());
+                (true, p, 32)
+            }
+            IpAddr::V6(v6) => (false, v6.octets(), 128),
+        };
+        let bits = bits.unwrap_or(max);
+        if bits > max {
+            return Err(format!("prefix length out of range in {:?}", s));
+        }
+        Ok(Self { v4, prefix, bits })
+    }
+
+    pub fn contains(&self, ip: &[u8; 16]) -> bool {
+        if self.v4 && ip[4..].iter().any(|&b| b != 0) {
+            return false;
+        }
+        let full = (self.bits / 8) as usize;
+        if ip[..full] != self.prefix[..full] {
+            return false;
+        }
+        let rest = self.bits % 8;
+        rest == 0 || {
+            let mask = 0xFFu8 << (8 - rest);
+            ip[full] & mask == self.prefix[full] & mask
+        }
+    }
+}
+
+/// A query with its IP range parsed once.
+pub struct Matcher<'a> {
+    q: &'a WitnessQuery,
+    ip: Option<IpRange>,
+    src_key: Option<i32>,
+    dst_key: Option<i32>,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(q: &'a WitnessQuery) -> Result<Self, String> {
+        let ip = q.ip.as_deref().map(IpRange::parse).transpose()?;
+        Ok(Self {
+            q,
+            ip,
+            src_key: q.src_key.as_deref().map(agent_id),
+            dst_key: q.dst_key.as_deref().map(agent_id),
+        })
+    }
+
+    pub fn matches(&self, r: &WitnessRecord) -> bool {
+        let q = self.q;
+        q.src.is_none_or(|v| r.src == v)
+            && q.dst.is_none_or(|v| r.dst == v)
+            && self.src_key.is_none_or(|v| r.src == v)
+            && self.dst_key.is_none_or(|v| r.dst == v)
+            && q.port.is_none_or(|v| r.port == v)
+            && self.ip.as_ref().is_none_or(|range| range.contains(&r.ip))
+            && q.flags.is_none_or(|v| r.flags & v != 0)
+            && q.flags_all.is_none_or(|v| r.flags & v == v)
+            && q.since_utc.is_none_or(|v| r.utc >= v)
+            && q.until_utc.is_none_or(|v| r.utc <= v)
+            && q.since_mono.is_none_or(|v| r.mono >= v)
+            && q.until_mono.is_none_or(|v| r.mono <= v)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Page {
+    pub records: Vec<WitnessRecord>,
+    /// Matching records before pagination.
+    pub total: usize,
+    pub offset: usize,
+    pub next_offset: Option<usize>,
+}
+
+/// Filter `records` (oldest first) and return one page.
+pub fn query<R: Borrow<WitnessRecord>>(records: impl IntoIterator<Item = R>, q: &WitnessQuery) -> Result<Page, String> {
+    let matcher = Matcher::new(q)?;
+    let offset = q.offset.unwrap_or(0);
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT);
+    let mut total = 0;
+    let mut page = Vec::new();
+    for r in records.into_iter().filter(|r| matcher.matches(r.borrow())) {
+        if total >= offset && page.len() < limit {
+            page.push(r.borrow().clone());
+        }
+        total += 1;
+    }
+    let next_offset = (offset + page.len() < total).then_some(offset + page.len());
+    Ok(Page { records: page, total, offset, next_offset })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MinuteBucket {
+    pub dst: i32,
+    /// Start of the minute, Unix seconds.
+    pub minute_utc: u64,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Count matching records per destination per UTC minute (pagination ignored).
+pub fn per_destination_per_minute<R: Borrow<WitnessRecord>>(
+    records: impl IntoIterator<Item = R>,
+    q: &WitnessQuery,
+) -> Result<Vec<MinuteBucket>, String> {
+    let matcher = Matcher::new(q)?;
+    let mut buckets: BTreeMap<(i32, u64), (u64, u64)> = BTreeMap::new();
+    for r in records.into_iter() {
+        let r = r.borrow();
+        if !matcher.matches(r) {
+            continue;
+        }
+        let minute = (r.utc / NS_PER_MINUTE) as u64 * 60;
+        let entry = buckets.entry((r.dst, minute)).or_default();
+        entry.0 += 1;
+        entry.1 += r.len as u64;
+    }
+    Ok(buckets
+        .into_iter()
+        .map(|((dst, minute_utc), (count, bytes))| MinuteBucket { dst, minute_utc, count, bytes })
+        .collect())
+}
@@ -0,0 +1,369 @@
+//! Append-only on-disk witness log.
+//!
+//! Records are stored in segment files `witness-<first seq>.seg`, each a
+//! 32-byte header followed by fixed-size records in the
+//! [`WitnessRecord::to_bytes`] layout. The newest segment is the only one
+//! written to; on open, a torn trailing record, or one whose chain link does
+//! not match its predecessor, is truncated away.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::witness::{WitnessRecord, RECORD_SIZE};
+
+const MAGIC: &[u8; 8] = b"KWSEG\0\0\x01";
+const FORMAT_VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct SegmentConfig {
+    /// Start a new segment once the current one reaches this size.
+    pub max_segment_bytes: u64,
+    /// Start a new segment once the current one is this old.
+    pub max_segment_age: Duration,
+    /// Closed segments kept; 0 keeps all.
+    pub max_segments: usize,
+    /// Closed segments older than this are deleted.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self {
+            max_segment_bytes: 16 * 1024 * 1024,
+            max_segment_age: Duration::from_secs(24 * 3600),
+            max_segments: 64,
+            max_age: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SegmentInfo {
+    pub path: PathBuf,
+    pub first_seq: u64,
+    pub records: u64,
+    pub created_utc_ns: u128,
+}
+
+struct Active {
+    file: File,
+    info: SegmentInfo,
+    created: SystemTime,
+}
+
+pub struct SegmentLog {
+    dir: PathBuf,
+    config: SegmentConfig,
+    active: Option<Active>,
+    last: Option<WitnessRecord>,
+}
+
+fn segment_path(dir: &Path, first_seq: u64) -> PathBuf {
+    dir.join(format!("witness-{:020}.seg", first_seq))
+}
+
+fn parse_first_seq(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_prefix("witness-")?.strip_suffix(".seg")?.parse().ok()
+}
+
+fn read_header(file: &mut File) -> io::Result<u128> {
+    let mut h = [0u8; HEADER_SIZE];
+    file.read_exact(&mut h)?;
+    let record_size = u32::from_le_bytes(h[8..12].try_into().unwrap()) as usize;
+    let version = u32::from_le_bytes(h[12..16].try_into().unwrap());
+    if &h[..8] != MAGIC || record_size != RECORD_SIZE || version != FORMAT_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a witness segment"));
+    }
+    Ok(u128::from_le_bytes(h[16..32].try_into().unwrap()))
+}
+
+fn header(created_utc_ns: u128) -> [u8; HEADER_SIZE] {
+    let mut h = [0u8; HEADER_SIZE];
+    h[..8].copy_from_slice(MAGIC);
+    h[8..12].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+    h[12..16].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    h[16..32].copy_from_slice(&created_utc_ns.to_le_bytes());
+    h
+}
+
+fn read_records(path: &Path) -> io::Result<Vec<WitnessRecord>> {
+    let mut file = File::open(path)?;
+    read_header(&mut file)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data
+        .chunks_exact(RECORD_SIZE)
+        .map(|c| WitnessRecord::from_bytes(c.try_into().unwrap()))
+        .collect())
+}
+
+/// Records of a log, read one at a time from disk and oldest first; see
+/// [`SegmentLog::scan`]. Holds no reference to the log.
+pub struct Records {
+    paths: std::vec::IntoIter<PathBuf>,
+    current: Option<BufReader<File>>,
+    upto: Option<u64>,
+    since_utc: Option<u128>,
+    until_utc: Option<u128>,
+}
+
+impl Records {
+    /// Seq of the newest record the scan can return.
+    pub fn upto(&self) -> Option<u64> {
+        self.upto
+    }
+}
+
+impl Iterator for Records {
+    type Item = io::Result<WitnessRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0u8; RECORD_SIZE];
+        loop {
+            let reader = match &mut self.current {
+                Some(reader) => reader,
+                None => {
+                    let path = self.paths.next()?;
+                    let mut file = match File::open(&path) {
+                        Ok(file) => file,
+                        // Removed by retention since the scan started.
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    if let Err(e) = read_header(&mut file) {
+                        return Some(Err(e));
+                    }
+                    self.current.insert(BufReader::new(file))
+                }
+            };
+            match reader.read_exact(&mut buf) {
+                Ok(()) => {
+                    let r = WitnessRecord::from_bytes(&buf);
+                    if self.upto.is_none_or(|u| r.seq > u) {
+                        // Appended after the scan started.
+                        self.paths = Vec::new().into_iter();
+                        self.current = None;
+                        return None;
+                    }
+                    if self.since_utc.is_none_or(|v| r.utc >= v) && self.until_utc.is_none_or(|v| r.utc <= v) {
+                        return Some(Ok(r));
+                    }
+                }
+                // End of the segment, or a record still being written.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => self.current = None,
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl SegmentLog {
+    /// Open (or create) the log in `dir`, recovering the newest segment.
+    pub fn open(dir: impl AsRef<Path>, config: SegmentConfig) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut log = Self { dir, config, active: None, last: None };
+        if let Some(path) = log.segment_paths()?.pop() {
+            log.recover(&path)?;
+        }
+        Ok(log)
+    }
+
+    /// Segment files, oldest first.
+    fn segment_paths(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths: Vec<_> = std::fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| parse_first_seq(p).is_some())
+            .collect();
+        paths.sort_by_key(|p| parse_first_seq(p));
+        Ok(paths)
+    }
+
+    /// Truncate a torn or mislinked tail and reopen the segment for appends.
+    fn recover(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let created_utc_ns = match read_header(&mut file) {
+            Ok(created) => created,
+            Err(_) => {
+                // A crash while creating the segment: nothing was committed to it.
+                drop(file);
+                std::fs::remove_file(path)?;
+                return match self.segment_paths()?.pop() {
+                    Some(prev) => self.recover(&prev),
+                    None => Ok(()),
+                };
+            }
+        };
+
+        let records = read_records(path)?;
+        let mut valid = records.len();
+        for i in 1..records.len() {
+            if records[i].prev != records[i - 1].digest() || records[i].seq != records[i - 1].seq + 1 {
+                valid = i;
+                break;
+            }
+        }
+        let len = (HEADER_SIZE + valid * RECORD_SIZE) as u64;
+        if file.metadata()?.len() != len {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        self.last = match records[..valid].last() {
+            Some(last) => Some(last.clone()),
+            // Crashed right after rotating: the chain continues from the
+            // previous segment's tail.
+            None => match self.segment_paths()?.into_iter().rev().find(|p| p.as_path() != path) {
+                Some(prev) => read_records(&prev)?.pop(),
+                None => None,
+            },
+        };
+        let first_seq = parse_first_seq(path).unwrap_or(0);
+        self.active = Some(Active {
+            file,
+            info: SegmentInfo { path: path.to_path_buf(), first_seq, records: valid as u64, created_utc_ns },
+            created: SystemTime::UNIX_EPOCH + Duration::from_nanos(created_utc_ns as u64),
+        });
+        Ok(())
+    }
+
+    fn needs_rotation(&self) -> bool {
+        match &self.active {
+            None => true,
+            Some(a) => {
+                let size = HEADER_SIZE as u64 + a.info.records * RECORD_SIZE as u64;
+                let age = a.created.elapsed().unwrap_or_default();
+                a.info.records > 0 && (size >= self.config.max_segment_bytes || age >= self.config.max_segment_age)
+            }
+        }
+    }
+
+    fn rotate(&mut self, first_seq: u64) -> io::Result<()> {
+        if let Some(a) = self.active.take() {
+            a.file.sync_all()?;
+        }
+        let path = segment_path(&self.dir, first_seq);
+        let created_utc_ns = crate::time::now_utc_ns();
+        let mut file = OpenOptions::new().create(true).truncate(true).write(true).read(true).open(&path)?;
+        file.write_all(&header(created_utc_ns))?;
+        file.sync_all()?;
+        self.active = Some(Active {
+            file,
+            info: SegmentInfo { path, first_seq, records: 0, created_utc_ns },
+            created: SystemTime::now(),
+        });
+        self.apply_retention()
+    }
+
+    /// Append one chained record, rotating first if the segment is full or old.
+    pub fn append(&mut self, record: &WitnessRecord) -> io::Result<()> {
+        if self.needs_rotation() {
+            self.rotate(record.seq)?;
+        }
+        let active = self.active.as_mut().expect("active segment after rotation");
+        active.file.write_all(&record.to_bytes())?;
+        active.info.records += 1;
+        self.last = Some(record.clone());
+        Ok(())
+    }
+
+    /// Flush the active segment to stable storage.
+    pub fn sync(&mut self) -> io::Result<()> {
+        match &self.active {
+            Some(a) => a.file.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    /// Delete closed segments beyond `max_segments` or older than `max_age`.
+    pub fn apply_retention(&mut self) -> io::Result<()> {
+        let active = self.active.as_ref().map(|a| a.info.path.clone());
+        let closed: Vec<_> = self.segment_paths()?.into_iter().filter(|p| Some(p) != active.as_ref()).collect();
+        let excess = match self.config.max_segments {
+            0 => 0,
+            max => closed.len().saturating_sub(max),
+        };
+        for (i, path) in closed.iter().enumerate() {
+            let expired = self.config.max_age.is_some_and(|max| {
+                std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .is_some_and(|age| age > max)
+            });
+            if i < excess || expired {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The newest record on disk, used to resume the chain after a restart.
+    pub fn last(&self) -> Option<&WitnessRecord> {
+        self.last.as_ref()
+    }
+
+    pub fn segments(&self) -> io::Result<Vec<SegmentInfo>> {
+        let mut out = Vec::new();
+        for path in self.segment_paths()? {
+            let mut file = File::open(&path)?;
+            let created_utc_ns = read_header(&mut file)?;
+            let records = (file.metadata()?.len().saturating_sub(HEADER_SIZE as u64)) / RECORD_SIZE as u64;
+            let first_seq = parse_first_seq(&path).unwrap_or(0);
+            out.push(SegmentInfo { path, first_seq, records, created_utc_ns });
+        }
+        Ok(out)
+    }
+
+    /// Every record still on disk, oldest first.
+    pub fn read_all(&self) -> io::Result<Vec<WitnessRecord>> {
+        self.scan(None, None)?.collect()
+    }
+
+    /// Stream the records on disk now whose `utc` lies in the inclusive
+    /// range. A segment's records were all stamped before the next segment
+    /// was created, so segments whose successor's header predates
+    /// `since_utc` are skipped without being opened.
+    pub fn scan(&self, since_utc: Option<u128>, until_utc: Option<u128>) -> io::Result<Records> {
+        let mut paths = self.segment_paths()?;
+        if let Some(since) = since_utc {
+            let mut created = Vec::with_capacity(paths.len());
+            for path in &paths {
+                created.push(File::open(path).and_then(|mut f| read_header(&mut f)).ok());
+            }
+            let first = (0..paths.len())
+                .find(|&i| created.get(i + 1).is_none_or(|next| next.is_none_or(|c| c >= since)))
+                .unwrap_or(0);
+            paths.drain(..first);
+        }
+        Ok(Records {
+            paths: paths.into_iter(),
+            current: None,
+            upto: self.last.as_ref().map(|r| r.seq),
+            since_utc,
+            until_utc,
+        })
+    }
+
+    /// Records with `seq >= from`, skipping segments that end before it.
+    pub fn read_from(&self, from: u64) -> io::Result<Vec<WitnessRecord>> {
+        let paths = self.segment_paths()?;
+        let mut out = Vec::new();
+        for (i, path) in paths.iter().enumerate() {
+            let next_first = paths.get(i + 1).and_then(|p| parse_first_seq(p));
+            if next_first.is_some_and(|n| n <= from) {
+                continue;
+            }
+            out.extend(read_records(path)?.into_iter().filter(|r| r.seq >= from));
+        }
+        Ok(out)
+    }
+}
@@ -1,7 +1,59 @@
+use ed25519_dalek::SigningKey;
 use serde::ser::SerializeStruct;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::chain::ChainCheckpoint;
+
+/// Bits used in `WitnessRecord::flags`.
+pub mod flags {
+    /// Delivered to the GPT subsystem.
+    pub const ROUTE_GPT: u32 = 1;
+    /// Queued in a destination inbox.
+    pub const ROUTE_INBOX: u32 = 1 << 1;
+    /// A burst rule fired with the given policy.
+    pub const BURST_LOG: u32 = 1 << 8;
+    pub const BURST_THROTTLE: u32 = 1 << 9;
+    pub const BURST_QUARANTINE: u32 = 1 << 10;
+    pub const BURST_ALERT: u32 = 1 << 11;
+    /// Dropped because the source is quarantined.
+    pub const QUARANTINED: u32 = 1 << 12;
+    /// Content inspection flagged / dropped the packet.
+    pub const INSPECT_FLAGGED: u32 = 1 << 13;
+    pub const INSPECT_DROPPED: u32 = 1 << 14;
+}
+
+/// `Extension::verdict` codes.
+pub mod verdict {
+    pub const PASS: u8 = 0;
+    pub const FLAG: u8 = 1;
+    pub const DROP: u8 = 2;
+}
+
+/// `Extension::scope` codes: the class of the remote endpoint.
+pub mod scope {
+    pub const UNKNOWN: u8 = 0;
+    pub const LOCAL: u8 = 1;
+    pub const KNOWN_TEST: u8 = 2;
+    pub const KNOWN_PEER: u8 = 3;
+    pub const SUSPICIOUS: u8 = 4;
+}
+
+/// Layout version written to `pad[0]` when an extension is present.
+pub const EXTENSION_VERSION: u8 = 1;
+
+/// Versioned extension area stored in `WitnessRecord::pad`:
+/// `[version, verdict, scope, 0, payload_hash[32], 0; 12]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extension {
+    /// SHA-256 of the packet payload.
+    pub payload_hash: [u8; 32],
+    pub verdict: u8,
+    pub scope: u8,
+}
 
 #[repr(C)]
 #[derive(Clone)]
@@ -15,7 +67,12 @@ pub struct WitnessRecord {
     pub flags: u32,
     pub port: u16,
     pub ip: [u8; 16],
+    /// Extension area; see [`Extension`]. All zero when unused.
     pub pad: [u8; 48],
+    /// Position in the chain, starting at 0.
+    pub seq: u64,
+    /// `digest()` of the previous record; zero for the first one.
+    pub prev: [u8; 32],
 }
 
 impl Default for WitnessRecord {
@@ -31,16 +88,112 @@ impl Default for WitnessRecord {
             port: 0,
             ip: [0; 16],
             pad: [0; 48],
+            seq: 0,
+            prev: [0; 32],
         }
     }
 }
 
+/// Size of the `#[repr(C)]` layout, and of one on-disk record.
+pub const RECORD_SIZE: usize = std::mem::size_of::<WitnessRecord>();
+
+macro_rules! field_range {
+    ($field:ident, $len:expr) => {{
+        let start = std::mem::offset_of!(WitnessRecord, $field);
+        start..start + $len
+    }};
+}
+
+impl WitnessRecord {
+    /// Encode at the `#[repr(C)]` field offsets, little-endian, with
+    /// alignment padding zeroed.
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut b = [0u8; RECORD_SIZE];
+        b[field_range!(mono, 16)].copy_from_slice(&self.mono.to_le_bytes());
+        b[field_range!(utc, 16)].copy_from_slice(&self.utc.to_le_bytes());
+        b[field_range!(src, 4)].copy_from_slice(&self.src.to_le_bytes());
+        b[field_range!(dst, 4)].copy_from_slice(&self.dst.to_le_bytes());
+        b[field_range!(len, 4)].copy_from_slice(&self.len.to_le_bytes());
+        b[field_range!(hash32, 4)].copy_from_slice(&self.hash32.to_le_bytes());
+        b[field_range!(flags, 4)].copy_from_slice(&self.flags.to_le_bytes());
+        b[field_range!(port, 2)].copy_from_slice(&self.port.to_le_bytes());
+        b[field_range!(ip, 16)].copy_from_slice(&self.ip);
+        b[field_range!(pad, 48)].copy_from_slice(&self.pad);
+        b[field_range!(seq, 8)].copy_from_slice(&self.seq.to_le_bytes());
+        b[field_range!(prev, 32)].copy_from_slice(&self.prev);
+        b
+    }
+
+    pub fn from_bytes(b: &[u8; RECORD_SIZE]) -> Self {
+        fn arr<const N: usize>(s: &[u8]) -> [u8; N] {
+            s.try_into().unwrap()
+        }
+        Self {
+            mono: u128::from_le_bytes(arr(&b[field_range!(mono, 16)])),
+            utc: u128::from_le_bytes(arr(&b[field_range!(utc, 16)])),
+            src: i32::from_le_bytes(arr(&b[field_range!(src, 4)])),
+            dst: i32::from_le_bytes(arr(&b[field_range!(dst, 4)])),
+            len: u32::from_le_bytes(arr(&b[field_range!(len, 4)])),
+            hash32: u32::from_le_bytes(arr(&b[field_range!(hash32, 4)])),
+            flags: u32::from_le_bytes(arr(&b[field_range!(flags, 4)])),
+            port: u16::from_le_bytes(arr(&b[field_range!(port, 2)])),
+            ip: arr(&b[field_range!(ip, 16)]),
+            pad: arr(&b[field_range!(pad, 48)]),
+            seq: u64::from_le_bytes(arr(&b[field_range!(seq, 8)])),
+            prev: arr(&b[field_range!(prev, 32)]),
+        }
+    }
+
+    /// The extension area, if one with a known version was written.
+    pub fn extension(&self) -> Option<Extension> {
+        if self.pad[0] != EXTENSION_VERSION {
+            return None;
+        }
+        Some(Extension {
+            verdict: self.pad[1],
+            scope: self.pad[2],
+            payload_hash: self.pad[4..36].try_into().unwrap(),
+        })
+    }
+
+    pub fn set_extension(&mut self, ext: &Extension) {
+        self.pad = [0; 48];
+        self.pad[0] = EXTENSION_VERSION;
+        self.pad[1] = ext.verdict;
+        self.pad[2] = ext.scope;
+        self.pad[4..36].copy_from_slice(&ext.payload_hash);
+    }
+
+    /// SHA-256 over every field in little-endian order (struct padding excluded).
+    pub fn digest(&self) -> [u8; 32] {
+        finish_digest(self.digest_prefix(), self.seq, &self.prev)
+    }
+
+    /// Hash state over every field before `seq`, so the chain link only
+    /// has to finish the last block.
+    fn digest_prefix(&self) -> Sha256 {
+        let mut h = Sha256::new();
+        h.update(b"KAIRO-WITNESS-v1");
+        h.update(self.mono.to_le_bytes());
+        h.update(self.utc.to_le_bytes());
+        h.update(self.src.to_le_bytes());
+        h.update(self.dst.to_le_bytes());
+        h.update(self.len.to_le_bytes());
+        h.update(self.hash32.to_le_bytes());
+        h.update(self.flags.to_le_bytes());
+        h.update(self.port.to_le_bytes());
+        h.update(self.ip);
+        h.update(self.pad);
+        h
+    }
+}
+
 impl Serialize for WitnessRecord {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("WitnessRecord", 10)?;
+        let mut state = serializer.serialize_struct("WitnessRecord", 13)?;
         state.serialize_field("mono", &self.mono)?;
         state.serialize_field("utc", &self.utc)?;
         state.serialize_field("src", &self.src)?;
@@ -51,32 +204,262 @@ impl Serialize for WitnessRecord {
         state.serialize_field("port", &self.port)?;
         state.serialize_field("ip", &self.ip)?;
         state.serialize_field("pad", &&self.pad[..])?;
+        state.serialize_field("seq", &self.seq)?;
+        state.serialize_field("prev", &self.prev)?;
+        // Decoded view of `pad`; ignored on deserialize.
+        state.serialize_field("ext", &self.extension())?;
         state.end()
     }
 }
 
+/// Mirror of the serialized form; `pad` travels as a plain byte list.
+#[derive(Deserialize)]
+struct WitnessRecordRepr {
+    mono: u128,
+    utc: u128,
+    src: i32,
+    dst: i32,
+    len: u32,
+    hash32: u32,
+    flags: u32,
+    port: u16,
+    ip: [u8; 16],
+    pad: Vec<u8>,
+    #[serde(default)]
+    seq: u64,
+    #[serde(default)]
+    prev: [u8; 32],
+}
+
+impl<'de> Deserialize<'de> for WitnessRecord {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = WitnessRecordRepr::deserialize(deserializer)?;
+        let pad: [u8; 48] = r
+            .pad
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("pad must be 48 bytes"))?;
+        Ok(Self {
+            mono: r.mono,
+            utc: r.utc,
+            src: r.src,
+            dst: r.dst,
+            len: r.len,
+            hash32: r.hash32,
+            flags: r.flags,
+            port: r.port,
+            ip: r.ip,
+            pad,
+            seq: r.seq,
+            prev: r.prev,
+        })
+    }
+}
+
+fn finish_digest(mut prefix: Sha256, seq: u64, prev: &[u8; 32]) -> [u8; 32] {
+    prefix.update(seq.to_le_bytes());
+    prefix.update(prev);
+    prefix.finalize().into()
+}
+
+/// Default number of records between signed checkpoints.
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 256;
+/// Signed checkpoints kept in memory.
+const CHECKPOINT_HISTORY: usize = 64;
+
+const WORDS: usize = RECORD_SIZE / 8;
+const _: () = assert!(RECORD_SIZE.is_multiple_of(8));
+
+/// One ring entry guarded by a seqlock: `stamp` is 0 when empty, odd while
+/// the record for seq `(stamp - 1) / 2` is being written and `2 * seq + 2`
+/// once it is complete. The record lives in atomic words so readers never
+/// need a lock or `unsafe`.
+struct Slot {
+    stamp: AtomicU64,
+    words: [AtomicU64; WORDS],
+}
+
+impl Slot {
+    fn new() -> Self {
+        Self {
+            stamp: AtomicU64::new(0),
+            words: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    /// Only called by the holder of the sequencer, so writers never race.
+    fn store(&self, r: &WitnessRecord) {
+        let bytes = r.to_bytes();
+        self.stamp.store(2 * r.seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (word, chunk) in self.words.iter().zip(bytes.chunks_exact(8)) {
+            word.store(u64::from_le_bytes(chunk.try_into().unwrap()), Ordering::Relaxed);
+        }
+        self.stamp.store(2 * r.seq + 2, Ordering::Release);
+    }
+
+    fn load(&self) -> Option<WitnessRecord> {
+        loop {
+            let before = self.stamp.load(Ordering::Acquire);
+            if before == 0 {
+                return None;
+            }
+            if before & 1 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let mut bytes = [0u8; RECORD_SIZE];
+            for (word, chunk) in self.words.iter().zip(bytes.chunks_exact_mut(8)) {
+                chunk.copy_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+            }
+            fence(Ordering::Acquire);
+            if self.stamp.load(Ordering::Relaxed) == before {
+                return Some(WitnessRecord::from_bytes(&bytes));
+            }
+        }
+    }
+}
+
+/// Chain state; held only to link a record to its predecessor.
+struct Sequencer {
+    next_seq: u64,
+    head: [u8; 32],
+    signer: Option<(SigningKey, u64)>,
+}
+
+/// Bounded witness ring. Each pushed record is chained to its predecessor and,
+/// once a signer is set, every `interval` records produce a signed checkpoint.
+///
+/// The chain needs a total order, so writers take the sequencer for the link
+/// itself: the last SHA-256 block of the digest (the rest is hashed
+/// beforehand) and the slot store. Readers (`snapshot`, `len`) never lock
+/// and never block writers.
 pub struct Ring {
-    cap: usize,
-    buf: Arc<Mutex<VecDeque<WitnessRecord>>>,
+    slots: Box<[Slot]>,
+    sequencer: Mutex<Sequencer>,
+    /// `next_seq` as visible to readers.
+    published: AtomicU64,
+    /// First seq of the current contiguous run (moves when `restore` skips ahead).
+    run_start: AtomicU64,
+    checkpoints: Mutex<VecDeque<ChainCheckpoint>>,
 }
 
 impl Ring {
     pub fn new(capacity: usize) -> Self {
         Self {
-            cap: capacity,
-            buf: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            slots: (0..capacity.max(1)).map(|_| Slot::new()).collect(),
+            sequencer: Mutex::new(Sequencer {
+                next_seq: 0,
+                head: [0; 32],
+                signer: None,
+            }),
+            published: AtomicU64::new(0),
+            run_start: AtomicU64::new(0),
+            checkpoints: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sign a checkpoint of the chain head every `interval` records.
+    pub fn set_signer(&self, key: SigningKey, interval: u64) {
+        self.sequencer.lock().unwrap().signer = Some((key, interval.max(1)));
+    }
+
+    fn slot(&self, seq: u64) -> &Slot {
+        &self.slots[(seq % self.slots.len() as u64) as usize]
+    }
+
+    fn push_checkpoint(&self, cp: ChainCheckpoint) {
+        let mut cps = self.checkpoints.lock().unwrap();
+        if cps.contains(&cp) {
+            return;
+        }
+        if cps.len() == CHECKPOINT_HISTORY {
+            cps.pop_front();
+        }
+        cps.push_back(cp);
+    }
+
+    /// Assign `seq`/`prev`, append, and checkpoint if due. Returns the
+    /// record as chained.
+    pub fn push(&self, record: WitnessRecord) -> WitnessRecord {
+        self.push_evicting(record).0
+    }
+
+    /// Like [`Ring::push`], also returning the record evicted to make room.
+    pub fn push_evicting(&self, mut record: WitnessRecord) -> (WitnessRecord, Option<WitnessRecord>) {
+        let prefix = record.digest_prefix();
+        let mut seq = self.sequencer.lock().unwrap();
+        record.seq = seq.next_seq;
+        record.prev = seq.head;
+        seq.head = finish_digest(prefix, record.seq, &record.prev);
+        seq.next_seq += 1;
+
+        let slot = self.slot(record.seq);
+        let evicted = slot.load();
+        slot.store(&record);
+        self.published.store(seq.next_seq, Ordering::Release);
+
+        if let Some((key, interval)) = &seq.signer {
+            if seq.next_seq.is_multiple_of(*interval) {
+                self.push_checkpoint(ChainCheckpoint::sign(key, record.seq, seq.head));
+            }
         }
+        (record, evicted)
     }
 
-    pub fn push(&self, record: WitnessRecord) {
-        let mut guard = self.buf.lock().unwrap();
-        if guard.len() == self.cap {
-            guard.pop_front();
+    /// Re-insert previously chained records unchanged and continue the chain
+    /// from the last one. Records the ring already holds are skipped; a
+    /// record past the current head starts a new run, hiding older ones.
+    pub fn restore(&self, records: Vec<WitnessRecord>, checkpoints: Vec<ChainCheckpoint>) {
+        let mut seq = self.sequencer.lock().unwrap();
+        let known = seq.next_seq;
+        for r in records.into_iter().filter(|r| r.seq >= known) {
+            if r.seq != seq.next_seq {
+                self.run_start.store(r.seq, Ordering::Relaxed);
+            }
+            seq.next_seq = r.seq + 1;
+            seq.head = r.digest();
+            self.slot(r.seq).store(&r);
+            self.published.store(seq.next_seq, Ordering::Release);
         }
-        guard.push_back(record);
+        drop(seq);
+        for cp in checkpoints {
+            self.push_checkpoint(cp);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let hi = self.published.load(Ordering::Acquire);
+        let run = hi.saturating_sub(self.run_start.load(Ordering::Relaxed));
+        run.min(self.capacity() as u64) as usize
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The newest contiguous run of records, oldest first. Concurrent pushes
+    /// may evict the oldest entries mid-read; those are left out rather than
+    /// returning a run with a gap.
     pub fn snapshot(&self) -> Vec<WitnessRecord> {
-        self.buf.lock().unwrap().iter().cloned().collect()
+        let hi = self.published.load(Ordering::Acquire);
+        let lo = hi.saturating_sub(self.capacity() as u64).max(self.run_start.load(Ordering::Relaxed));
+        let mut out: Vec<WitnessRecord> = (lo..hi)
+            .filter_map(|seq| self.slot(seq).load().filter(|r| r.seq == seq))
+            .collect();
+        if let Some(gap) = out.windows(2).rposition(|w| w[1].seq != w[0].seq + 1) {
+            out.drain(..=gap);
+        }
+        out
+    }
+
+    pub fn checkpoints(&self) -> Vec<ChainCheckpoint> {
+        self.checkpoints.lock().unwrap().iter().cloned().collect()
     }
 }
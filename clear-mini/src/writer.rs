@@ -0,0 +1,167 @@
+//! Background appender for the witness log.
+//!
+//! Recording only publishes to the ring and queues the record here; a
+//! dedicated thread does the disk I/O. Pushers queue records after releasing
+//! the ring's sequencer, so they can arrive out of order: the writer buffers
+//! them and appends strictly by `seq`. A gap still open after [`GAP_WAIT`]
+//! (records restored past the log's tail, say) is skipped.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::segment::SegmentLog;
+use crate::witness::WitnessRecord;
+
+/// Records queued before pushers start to wait for the disk.
+const QUEUE_DEPTH: usize = 8192;
+const GAP_WAIT: Duration = Duration::from_millis(50);
+
+enum Msg {
+    Record(WitnessRecord),
+    /// Reply once everything queued so far and `extra` is appended (and
+    /// synced to disk if `sync`).
+    Flush {
+        extra: Vec<WitnessRecord>,
+        sync: bool,
+        done: mpsc::Sender<io::Result<()>>,
+    },
+}
+
+pub(crate) struct LogWriter {
+    log: Arc<Mutex<SegmentLog>>,
+    tx: Option<SyncSender<Msg>>,
+    handle: Option<JoinHandle<()>>,
+    /// First append error not yet reported to a caller.
+    failed: Arc<Mutex<Option<io::Error>>>,
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "witness log writer stopped")
+}
+
+impl LogWriter {
+    /// Start the writer thread; records below `next` are taken as already on disk.
+    pub(crate) fn spawn(log: SegmentLog, next: u64) -> io::Result<Self> {
+        let log = Arc::new(Mutex::new(log));
+        let failed = Arc::new(Mutex::new(None));
+        let (tx, rx) = mpsc::sync_channel(QUEUE_DEPTH);
+        let state = State { log: log.clone(), next, pending: BTreeMap::new() };
+        let handle = thread::Builder::new().name("witness-writer".into()).spawn({
+            let failed = failed.clone();
+            move || run(state, rx, failed)
+        })?;
+        Ok(Self { log, tx: Some(tx), handle: Some(handle), failed })
+    }
+
+    pub(crate) fn log(&self) -> MutexGuard<'_, SegmentLog> {
+        self.log.lock().unwrap()
+    }
+
+    fn send(&self, msg: Msg) -> io::Result<()> {
+        self.tx.as_ref().ok_or_else(stopped)?.send(msg).map_err(|_| stopped())
+    }
+
+    /// Queue `record` for appending. Reports an earlier append that failed.
+    pub(crate) fn append(&self, record: WitnessRecord) -> io::Result<()> {
+        self.send(Msg::Record(record))?;
+        match self.failed.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait until every record queued so far, and `extra`, is appended.
+    pub(crate) fn flush(&self, extra: Vec<WitnessRecord>, sync: bool) -> io::Result<()> {
+        let (done, wait) = mpsc::channel();
+        self.send(Msg::Flush { extra, sync, done })?;
+        wait.recv().map_err(|_| stopped())?
+    }
+}
+
+impl Drop for LogWriter {
+    /// Closing the queue lets the thread append what is left and exit.
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct State {
+    log: Arc<Mutex<SegmentLog>>,
+    /// Seq of the next record to append.
+    next: u64,
+    pending: BTreeMap<u64, WitnessRecord>,
+}
+
+impl State {
+    fn accept(&mut self, record: WitnessRecord) {
+        if record.seq >= self.next {
+            self.pending.insert(record.seq, record);
+        }
+    }
+
+    /// Append the run starting at `next`; with `skip_gaps`, all pending records.
+    fn drain(&mut self, skip_gaps: bool) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut log = self.log.lock().unwrap();
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() != self.next && !skip_gaps {
+                break;
+            }
+            let record = entry.remove();
+            self.next = record.seq + 1;
+            log.append(&record)?;
+        }
+        Ok(())
+    }
+}
+
+fn run(mut state: State, rx: Receiver<Msg>, failed: Arc<Mutex<Option<io::Error>>>) {
+    let mut waiting = Vec::new();
+    loop {
+        let msg = if state.pending.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(GAP_WAIT)
+        };
+        let closed = matches!(msg, Err(RecvTimeoutError::Disconnected));
+        let result = match msg {
+            Ok(Msg::Record(record)) => {
+                state.accept(record);
+                state.drain(false)
+            }
+            Ok(Msg::Flush { extra, sync, done }) => {
+                for record in extra {
+                    state.accept(record);
+                }
+                waiting.push((sync, done));
+                state.drain(false)
+            }
+            Err(_) => state.drain(true),
+        };
+        if let Err(e) = result {
+            failed.lock().unwrap().get_or_insert(e);
+        }
+        if state.pending.is_empty() {
+            for (sync, done) in waiting.drain(..) {
+                let result = match failed.lock().unwrap().take() {
+                    Some(e) => Err(e),
+                    None if sync => state.log.lock().unwrap().sync(),
+                    None => Ok(()),
+                };
+                let _ = done.send(result);
+            }
+        }
+        if closed {
+            return;
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use clear_mini::detector::{Detector, DetectorConfig, Window};
+
+#[test]
+fn window_drops_idle_keys() {
+    let mut w = Window::new(1);
+    let t0 = Instant::now();
+    for key in 0..100 {
+        assert_eq!(w.hit_at(key, t0), 1);
+    }
+    assert_eq!(w.len(), 100);
+
+    let later = t0 + Duration::from_secs(5);
+    assert_eq!(w.hit_at(7, later), 1);
+    assert_eq!(w.len(), 1);
+}
+
+#[test]
+fn counts_slide_out_of_the_window() {
+    let mut d = Detector::new(DetectorConfig::new(Duration::from_secs(6)));
+    let t0 = Instant::now();
+    for _ in 0..10 {
+        d.hit("a", t0);
+    }
+    assert_eq!(d.hit("a", t0), 11);
+    assert_eq!(d.count("b", t0), 0);
+    assert_eq!(d.count("a", t0 + Duration::from_secs(3)), 11);
+    assert_eq!(d.count("a", t0 + Duration::from_secs(7)), 0);
+}
+
+#[test]
+fn memory_stays_bounded_and_heavy_hitters_surface() {
+    let mut config = DetectorConfig::new(Duration::from_secs(60));
+    config.max_distinct_keys = 64;
+    config.top_k = 5;
+    let mut d = Detector::new(config);
+    let now = Instant::now();
+
+    for i in 0..20_000 {
+        d.hit(&format!("noise-{}", i), now);
+        d.hit_distinct(&format!("dst-{}", i), "src", now);
+    }
+    for i in 0..5 {
+        for _ in 0..(500 * (i + 1)) {
+            d.hit(&format!("heavy-{}", i), now);
+        }
+    }
+    let bytes = d.memory_bytes();
+    assert_eq!(d.tracked_keys(), 64);
+
+    let top = d.top_k(now);
+    let keys: Vec<&str> = top.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, ["heavy-4", "heavy-3", "heavy-2", "heavy-1", "heavy-0"]);
+    // Count-min never undercounts.
+    assert!(top[0].1 >= 2500);
+
+    for i in 0..20_000 {
+        d.hit_distinct(&format!("more-{}", i), "src", now);
+    }
+    assert_eq!(d.memory_bytes(), bytes);
+}
+
+#[test]
+fn distinct_members_are_estimated_and_evicted_when_idle() {
+    let mut d = Detector::new(DetectorConfig::new(Duration::from_secs(10)));
+    let t0 = Instant::now();
+    let mut last = 0;
+    for i in 0..1000 {
+        last = d.hit_distinct("victim", &format!("src-{}", i), t0);
+        d.hit_distinct("quiet", "same-src", t0);
+    }
+    assert!((900..=1100).contains(&last), "estimate {}", last);
+    assert_eq!(d.hit_distinct("quiet", "same-src", t0), 1);
+
+    d.evict_idle(t0 + Duration::from_secs(11));
+    assert_eq!(d.tracked_keys(), 0);
+    assert!(d.top_k(t0 + Duration::from_secs(11)).is_empty());
+}
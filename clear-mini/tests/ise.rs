@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use clear_mini::ise::{ise_sign_at, ise_verify_at, IseError, IseKey, Keyring, ReplayGuard, Sig};
+
+const NOW: u64 = 1_700_000_000;
+
+fn guard() -> ReplayGuard {
+    ReplayGuard::new(Duration::from_secs(300), 1000)
+}
+
+#[test]
+fn stamps_need_the_key_and_verify_once() {
+    let keys = Keyring::new(IseKey::new(1, [5; 32]));
+    let mut replay = guard();
+    let sig = ise_sign_at(keys.active(), b"payload", NOW);
+    let sig = Sig::from_bytes(&sig.to_bytes());
+
+    assert_eq!(ise_verify_at(&keys, &sig, b"payload", &mut replay, NOW + 10), Ok(()));
+    assert_eq!(ise_verify_at(&keys, &sig, b"payload", &mut replay, NOW + 20), Err(IseError::Replayed));
+    assert_eq!(
+        ise_verify_at(&keys, &ise_sign_at(keys.active(), b"payload", NOW), b"tampered", &mut replay, NOW),
+        Err(IseError::BadMac)
+    );
+
+    // Same key id, different secret: the old keyless scheme would have accepted this.
+    let forger = IseKey::new(1, [6; 32]);
+    let forged = ise_sign_at(&forger, b"payload", NOW);
+    assert_eq!(ise_verify_at(&keys, &forged, b"payload", &mut replay, NOW), Err(IseError::BadMac));
+
+    let old = ise_sign_at(keys.active(), b"payload", NOW - 600);
+    assert!(matches!(ise_verify_at(&keys, &old, b"payload", &mut replay, NOW), Err(IseError::Stale { .. })));
+}
+
+#[test]
+fn replay_cache_expires_and_fails_closed_when_full() {
+    let keys = Keyring::new(IseKey::new(1, [5; 32]));
+    let mut replay = ReplayGuard::new(Duration::from_secs(300), 2);
+    for _ in 0..2 {
+        let sig = ise_sign_at(keys.active(), b"p", NOW);
+        ise_verify_at(&keys, &sig, b"p", &mut replay, NOW).unwrap();
+    }
+    let third = ise_sign_at(keys.active(), b"p", NOW);
+    assert_eq!(ise_verify_at(&keys, &third, b"p", &mut replay, NOW), Err(IseError::ReplayCacheFull));
+    // Once the first two leave the window they no longer count.
+    let later = ise_sign_at(keys.active(), b"p", NOW + 400);
+    assert_eq!(ise_verify_at(&keys, &later, b"p", &mut replay, NOW + 400), Ok(()));
+    assert_eq!(replay.len(), 1);
+}
+
+#[test]
+fn rotation_keeps_recent_keys_and_persists() {
+    let dir = std::env::temp_dir().join(format!("clear-mini-ise-{}", std::process::id()));
+    let path = dir.join("ise_keys.json");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut keys = Keyring::load_or_create(&path).unwrap().with_max_retired(1);
+    let first = ise_sign_at(keys.active(), b"p", NOW);
+    keys.rotate();
+    let second = ise_sign_at(keys.active(), b"p", NOW);
+    keys.rotate();
+    assert_eq!(keys.ids(), vec![2, 3]);
+    keys.save(&path).unwrap();
+
+    let loaded = Keyring::load(&path).unwrap();
+    assert_eq!(loaded.active().id, 3);
+    let mut replay = guard();
+    assert_eq!(ise_verify_at(&loaded, &first, b"p", &mut replay, NOW), Err(IseError::UnknownKey(1)));
+    assert_eq!(ise_verify_at(&loaded, &second, b"p", &mut replay, NOW), Ok(()));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,227 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clear_mini::api::ClearMini;
+use clear_mini::config::{Config, OverflowPolicy};
+use clear_mini::kairo_p::PAddressRecord;
+use clear_mini::segment::{SegmentConfig, SegmentLog, HEADER_SIZE};
+use clear_mini::witness::{scope, verdict, Extension, Ring, WitnessRecord, EXTENSION_VERSION, RECORD_SIZE};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("clear-mini-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn small_segments(max_segments: usize) -> SegmentConfig {
+    SegmentConfig {
+        max_segment_bytes: (HEADER_SIZE + 4 * RECORD_SIZE) as u64,
+        max_segment_age: Duration::from_secs(3600),
+        max_segments,
+        max_age: None,
+    }
+}
+
+fn chained(n: u32) -> Vec<WitnessRecord> {
+    let ring = Ring::new(64);
+    (0..n).map(|i| ring.push(WitnessRecord { src: i as i32, port: 80, ..Default::default() })).collect()
+}
+
+#[test]
+fn records_round_trip_the_repr_c_layout() {
+    let r = &chained(2)[1];
+    assert_eq!(RECORD_SIZE, std::mem::size_of::<WitnessRecord>());
+    let back = WitnessRecord::from_bytes(&r.to_bytes());
+    assert_eq!(back.digest(), r.digest());
+}
+
+#[test]
+fn segments_rotate_and_recover_after_torn_writes() {
+    let dir = temp_dir("rotate");
+    let records = chained(10);
+    {
+        let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+        for r in &records {
+            log.append(r).unwrap();
+        }
+        let segments = log.segments().unwrap();
+        assert_eq!(segments.iter().map(|s| s.first_seq).collect::<Vec<_>>(), vec![0, 4, 8]);
+        assert_eq!(segments[2].records, 2);
+    }
+
+    // Half a record, as if the process died mid-write.
+    let newest = dir.join(format!("witness-{:020}.seg", 8));
+    std::fs::OpenOptions::new().append(true).open(&newest).unwrap().write_all(&[1; 50]).unwrap();
+    // A full record that does not link to its predecessor.
+    let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+    let mut forged = records[9].clone();
+    forged.seq = 10;
+    log.append(&forged).unwrap();
+    drop(log);
+
+    let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+    assert_eq!(log.last().unwrap().seq, 9);
+    assert_eq!(std::fs::metadata(&newest).unwrap().len(), (HEADER_SIZE + 2 * RECORD_SIZE) as u64);
+    assert_eq!(log.read_all().unwrap().len(), 10);
+    assert_eq!(log.read_from(5).unwrap().first().unwrap().seq, 5);
+
+    log.append(&chained(11)[10]).unwrap();
+    assert_eq!(log.read_all().unwrap().len(), 11);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn reopening_right_after_rotation_resumes_from_the_previous_segment() {
+    let dir = temp_dir("rotate-crash");
+    let records = chained(5);
+    {
+        let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+        for r in &records[..4] {
+            log.append(r).unwrap();
+        }
+    }
+    // The new segment's header made it to disk, its first record did not.
+    let first = std::fs::read(dir.join(format!("witness-{:020}.seg", 0))).unwrap();
+    std::fs::write(dir.join(format!("witness-{:020}.seg", 4)), &first[..HEADER_SIZE]).unwrap();
+
+    let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+    assert_eq!(log.last().unwrap().seq, 3);
+    log.append(&records[4]).unwrap();
+    let all = log.read_all().unwrap();
+    assert_eq!(all.len(), 5);
+    assert!(clear_mini::chain::verify(&all, &[], None).is_ok());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn scans_skip_segments_closed_before_the_range() {
+    let dir = temp_dir("scan");
+    let ring = Ring::new(64);
+    let mut log = SegmentLog::open(&dir, small_segments(0)).unwrap();
+    for i in 0..5 {
+        log.append(&ring.push(WitnessRecord { utc: i, ..Default::default() })).unwrap();
+    }
+    let since = clear_mini::time::now_utc_ns();
+    for _ in 5..9 {
+        log.append(&ring.push(WitnessRecord { utc: clear_mini::time::now_utc_ns(), ..Default::default() })).unwrap();
+    }
+    // An unreadable first segment only fails scans that have to open it.
+    std::fs::write(dir.join(format!("witness-{:020}.seg", 0)), [0u8; HEADER_SIZE]).unwrap();
+    assert!(log.scan(None, None).unwrap().any(|r| r.is_err()));
+
+    let recent: Vec<_> = log.scan(Some(since), None).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(recent.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![5, 6, 7, 8]);
+    let until = recent[1].utc;
+    let window: Vec<_> = log.scan(Some(since), Some(until)).unwrap().map(|r| r.unwrap().seq).collect();
+    assert_eq!(window, recent.iter().filter(|r| r.utc <= until).map(|r| r.seq).collect::<Vec<_>>());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn retention_keeps_the_newest_closed_segments() {
+    let dir = temp_dir("retention");
+    let mut log = SegmentLog::open(&dir, small_segments(1)).unwrap();
+    for r in &chained(13) {
+        log.append(r).unwrap();
+    }
+    let firsts: Vec<_> = log.segments().unwrap().iter().map(|s| s.first_seq).collect();
+    assert_eq!(firsts, vec![8, 12]);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn clear_mini_resumes_the_chain_from_disk() {
+    let dir = temp_dir("resume");
+    let src = PAddressRecord::new(1, "a");
+    let dst = PAddressRecord::new(2, "b");
+    {
+        let cm = ClearMini::new();
+        cm.attach_log(SegmentLog::open(&dir, small_segments(0)).unwrap()).unwrap();
+        for port in 0..6 {
+            cm.record(&src, &dst, 10, 0, [0; 16], port).unwrap();
+        }
+        cm.sync_log().unwrap();
+    }
+
+    let cm = ClearMini::new();
+    cm.attach_log(SegmentLog::open(&dir, small_segments(0)).unwrap()).unwrap();
+    assert_eq!(cm.ring.len(), 6);
+    cm.record(&src, &dst, 10, 0, [0; 16], 6).unwrap();
+    let records = cm.dump_witness_snapshot();
+    assert_eq!(records.last().unwrap().seq, 6);
+    assert!(cm.verify_witness(None).is_ok());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn concurrent_recording_reaches_the_log_in_chain_order() {
+    let dir = temp_dir("concurrent");
+    let cm = std::sync::Arc::new(ClearMini::with_config(Config { witness_capacity: 16, ..Default::default() }));
+    cm.attach_log(SegmentLog::open(&dir, small_segments(0)).unwrap()).unwrap();
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let cm = cm.clone();
+            std::thread::spawn(move || {
+                for port in 0..50 {
+                    cm.record_entry(WitnessRecord { src: t, port, ..Default::default() }).unwrap();
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+    cm.sync_log().unwrap();
+
+    let on_disk = SegmentLog::open(&dir, small_segments(0)).unwrap().read_all().unwrap();
+    assert_eq!(on_disk.iter().map(|r| r.seq).collect::<Vec<_>>(), (0..200).collect::<Vec<_>>());
+    assert!(clear_mini::chain::verify(&on_disk, &[], None).is_ok());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn spill_to_disk_writes_only_evicted_records_until_sync() {
+    let dir = temp_dir("spill");
+    let src = PAddressRecord::new(1, "a");
+    let dst = PAddressRecord::new(2, "b");
+    let config = Config { witness_capacity: 3, overflow: OverflowPolicy::SpillToDisk };
+    {
+        let cm = ClearMini::with_config(config.clone());
+        cm.attach_log(SegmentLog::open(&dir, small_segments(0)).unwrap()).unwrap();
+        for port in 0..5 {
+            cm.record(&src, &dst, 10, 0, [0; 16], port).unwrap();
+        }
+        assert_eq!(cm.ring.len(), 3);
+        cm.flush_log().unwrap();
+        let on_disk = SegmentLog::open(&dir, small_segments(0)).unwrap().read_all().unwrap();
+        assert_eq!(on_disk.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(cm.witness_history().unwrap().len(), 5);
+        cm.sync_log().unwrap();
+    }
+
+    let cm = ClearMini::with_config(config);
+    cm.attach_log(SegmentLog::open(&dir, small_segments(0)).unwrap()).unwrap();
+    for port in 5..8 {
+        cm.record(&src, &dst, 10, 0, [0; 16], port).unwrap();
+    }
+    cm.sync_log().unwrap();
+    let history = cm.witness_history().unwrap();
+    assert_eq!(history.iter().map(|r| r.seq).collect::<Vec<_>>(), (0..8).collect::<Vec<_>>());
+    assert!(clear_mini::chain::verify(&history, &[], None).is_ok());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn extension_area_survives_the_on_disk_layout() {
+    let ext = Extension { payload_hash: [0xab; 32], verdict: verdict::FLAG, scope: scope::KNOWN_PEER };
+    let mut r = WitnessRecord::default();
+    assert_eq!(r.extension(), None);
+    r.set_extension(&ext);
+    let cm = ClearMini::with_config(Config { witness_capacity: 2, ..Default::default() });
+    let chained = cm.record_entry(r).unwrap();
+    let back = WitnessRecord::from_bytes(&chained.to_bytes());
+    assert_eq!(back.extension(), Some(ext));
+    assert_eq!(back.pad[0], EXTENSION_VERSION);
+    assert!(serde_json::to_string(&back).unwrap().contains("\"ext\":{"));
+}
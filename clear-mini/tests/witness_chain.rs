@@ -0,0 +1,97 @@
+use clear_mini::chain::{verify, ChainError};
+use clear_mini::witness::{Ring, WitnessRecord};
+use ed25519_dalek::SigningKey;
+
+fn filled_ring(n: u32) -> (Ring, SigningKey) {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let ring = Ring::new(64);
+    ring.set_signer(key.clone(), 4);
+    for i in 0..n {
+        ring.push(WitnessRecord { src: i as i32, len: i, ..Default::default() });
+    }
+    (ring, key)
+}
+
+#[test]
+fn intact_chain_verifies_with_checkpoints() {
+    let (ring, key) = filled_ring(10);
+    let report = verify(&ring.snapshot(), &ring.checkpoints(), Some(&key.verifying_key())).unwrap();
+    assert_eq!(report.records, 10);
+    assert_eq!(report.last_seq, Some(9));
+    assert_eq!(report.checkpoints_checked, 2);
+}
+
+#[test]
+fn dropped_reordered_and_edited_records_are_detected() {
+    let (ring, key) = filled_ring(10);
+    let trusted = key.verifying_key();
+    let records = ring.snapshot();
+    let cps = ring.checkpoints();
+
+    let mut dropped = records.clone();
+    dropped.remove(5);
+    assert_eq!(verify(&dropped, &cps, None), Err(ChainError::Dropped { after_seq: 4, missing: 1 }));
+
+    let mut reordered = records.clone();
+    reordered.swap(2, 3);
+    assert_eq!(verify(&reordered, &cps, None), Err(ChainError::Reordered { expected_seq: 2, found_seq: 3 }));
+
+    let mut edited = records.clone();
+    edited[6].len = 999;
+    assert_eq!(verify(&edited, &cps, None), Err(ChainError::Tampered { seq: 6 }));
+
+    // Editing the record a checkpoint covers, with no successor to catch it.
+    let truncated = &records[..8];
+    let mut last_edited = truncated.to_vec();
+    last_edited[7].flags = 1;
+    assert_eq!(verify(&last_edited, &cps, None), Err(ChainError::Tampered { seq: 7 }));
+
+    // Tail truncation is caught by a checkpoint beyond the last record.
+    assert_eq!(verify(&records[..6], &cps, None), Err(ChainError::Dropped { after_seq: 5, missing: 2 }));
+
+    let forger = SigningKey::from_bytes(&[9; 32]);
+    let other = Ring::new(8);
+    other.set_signer(forger, 1);
+    other.push(WitnessRecord::default());
+    assert_eq!(
+        verify(&[], &other.checkpoints(), Some(&trusted)),
+        Err(ChainError::BadSignature { seq: 0 })
+    );
+}
+
+#[test]
+fn restore_keeps_links_and_continues_the_chain() {
+    let (ring, _) = filled_ring(5);
+    let restored = Ring::new(64);
+    restored.restore(ring.snapshot(), ring.checkpoints());
+    restored.push(WitnessRecord::default());
+    let records = restored.snapshot();
+    assert_eq!(records.last().unwrap().seq, 5);
+    assert!(verify(&records, &restored.checkpoints(), None).is_ok());
+}
+
+#[test]
+fn concurrent_pushes_form_one_chain_and_snapshots_stay_consistent() {
+    let ring = std::sync::Arc::new(Ring::new(128));
+    let writers: Vec<_> = (0..4)
+        .map(|t| {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for i in 0..500 {
+                    ring.push(WitnessRecord { src: t, len: i, ..Default::default() });
+                }
+            })
+        })
+        .collect();
+    while writers.iter().any(|w| !w.is_finished()) {
+        let snap = ring.snapshot();
+        assert!(verify(&snap, &[], None).is_ok());
+    }
+    for w in writers {
+        w.join().unwrap();
+    }
+    let snap = ring.snapshot();
+    assert_eq!(snap.len(), 128);
+    assert_eq!(snap.last().unwrap().seq, 1999);
+    assert!(verify(&snap, &[], None).is_ok());
+}
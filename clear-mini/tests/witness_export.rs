@@ -0,0 +1,74 @@
+use clear_mini::export::{write_jsonl, write_pcapng, WITNESS_UDP_PORT};
+use clear_mini::witness::{Ring, WitnessRecord, RECORD_SIZE};
+
+fn u32_at(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn records() -> Vec<WitnessRecord> {
+    let ring = Ring::new(8);
+    let mut v6 = [0u8; 16];
+    v6[0] = 0xfd;
+    v6[15] = 7;
+    vec![
+        ring.push(WitnessRecord { utc: 1_700_000_000_123_456_789, src: 1, dst: 2, port: 5000, ip: [10, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], ..Default::default() }),
+        ring.push(WitnessRecord { utc: 1_700_000_001_000_000_000, src: 3, dst: 4, port: 6000, ip: v6, flags: 0x100, ..Default::default() }),
+    ]
+}
+
+/// Split a pcapng stream into (block type, body) pairs, checking both length fields.
+fn blocks(mut b: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut out = Vec::new();
+    while !b.is_empty() {
+        let kind = u32_at(b, 0);
+        let len = u32_at(b, 4) as usize;
+        assert_eq!(len % 4, 0);
+        assert_eq!(u32_at(b, len - 4) as usize, len);
+        out.push((kind, b[8..len - 4].to_vec()));
+        b = &b[len..];
+    }
+    out
+}
+
+#[test]
+fn jsonl_round_trips_each_record() {
+    let records = records();
+    let mut out = Vec::new();
+    write_jsonl(&mut out, &records).unwrap();
+    let lines: Vec<_> = std::str::from_utf8(&out).unwrap().lines().collect();
+    assert_eq!(lines.len(), 2);
+    let back: WitnessRecord = serde_json::from_str(lines[1]).unwrap();
+    assert_eq!(back.digest(), records[1].digest());
+}
+
+#[test]
+fn pcapng_frames_carry_the_record_as_udp_payload() {
+    let records = records();
+    let mut out = Vec::new();
+    write_pcapng(&mut out, &records).unwrap();
+    let blocks = blocks(&out);
+    assert_eq!(blocks.iter().map(|b| b.0).collect::<Vec<_>>(), vec![0x0A0D0D0A, 1, 6, 6]);
+    assert_eq!(u32_at(&blocks[0].1, 0), 0x1A2B3C4D);
+    assert_eq!(u16::from_le_bytes(blocks[1].1[..2].try_into().unwrap()), 101);
+
+    // IPv4 frame: nanosecond timestamp, source ip:port, record bytes after the UDP header.
+    let epb = &blocks[2].1;
+    let ts = ((u32_at(epb, 4) as u64) << 32) | u32_at(epb, 8) as u64;
+    assert_eq!(ts, 1_700_000_000_123_456_789);
+    let frame = &epb[20..20 + u32_at(epb, 12) as usize];
+    assert_eq!(frame[0], 0x45);
+    assert_eq!(&frame[12..16], &[10, 0, 0, 9]);
+    assert_eq!(u16::from_be_bytes([frame[20], frame[21]]), 5000);
+    assert_eq!(u16::from_be_bytes([frame[22], frame[23]]), WITNESS_UDP_PORT);
+    let payload: &[u8; RECORD_SIZE] = frame[28..].try_into().unwrap();
+    assert_eq!(WitnessRecord::from_bytes(payload).digest(), records[0].digest());
+    let comment = String::from_utf8_lossy(&epb[20 + frame.len().next_multiple_of(4)..]);
+    assert!(comment.contains("seq=0 src=1 dst=2"));
+
+    // IPv6 frame.
+    let epb = &blocks[3].1;
+    let frame = &epb[20..20 + u32_at(epb, 12) as usize];
+    assert_eq!(frame[0] >> 4, 6);
+    assert_eq!(frame[8], 0xfd);
+    assert_eq!(frame.len(), 40 + 8 + RECORD_SIZE);
+}
@@ -0,0 +1,103 @@
+use clear_mini::query::{per_destination_per_minute, query, IpRange, WitnessQuery};
+use clear_mini::witness::WitnessRecord;
+
+const MINUTE: u128 = 60_000_000_000;
+
+fn v4(a: [u8; 4]) -> [u8; 16] {
+    let mut ip = [0; 16];
+    ip[..4].copy_from_slice(&a);
+    ip
+}
+
+fn records() -> Vec<WitnessRecord> {
+    (0..10u32)
+        .map(|i| WitnessRecord {
+            seq: i as u64,
+            mono: i as u128 * 1000,
+            utc: i as u128 * MINUTE / 4,
+            src: 1,
+            dst: (i % 2) as i32 + 10,
+            len: 100,
+            flags: if i % 3 == 0 { 0b11 } else { 0b01 },
+            port: 80,
+            ip: v4([10, 0, (i % 2) as u8, i as u8]),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn filters_combine_and_paginate() {
+    let all = records();
+    let q = WitnessQuery {
+        dst: Some(10),
+        ip: Some("10.0.0.0/24".into()),
+        since_mono: Some(2000),
+        limit: Some(2),
+        ..Default::default()
+    };
+    let page = query(&all, &q).unwrap();
+    assert_eq!(page.total, 4);
+    assert_eq!(page.records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![2, 4]);
+    assert_eq!(page.next_offset, Some(2));
+
+    let rest = query(&all, &WitnessQuery { offset: page.next_offset, ..q }).unwrap();
+    assert_eq!(rest.records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![6, 8]);
+    assert_eq!(rest.next_offset, None);
+
+    let both = WitnessQuery { flags_all: Some(0b11), until_utc: Some(MINUTE), ..Default::default() };
+    assert_eq!(query(&all, &both).unwrap().records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![0, 3]);
+
+    assert!(query(&all, &WitnessQuery { ip: Some("10.0.0.0/33".into()), ..Default::default() }).is_err());
+}
+
+#[test]
+fn ip_ranges_do_not_confuse_families() {
+    let net = IpRange::parse("10.0.0.0/8").unwrap();
+    assert!(net.contains(&v4([10, 9, 9, 9])));
+    assert!(!net.contains(&v4([11, 0, 0, 1])));
+    let mut v6 = v4([10, 0, 0, 1]);
+    v6[15] = 1;
+    assert!(!net.contains(&v6));
+    assert!(IpRange::parse("fd00::/8").unwrap().contains(&"fd12::1".parse::<std::net::Ipv6Addr>().unwrap().octets()));
+}
+
+#[test]
+fn aggregates_per_destination_per_minute() {
+    let buckets = per_destination_per_minute(records(), &WitnessQuery::default()).unwrap();
+    let counts: Vec<_> = buckets.iter().map(|b| (b.dst, b.minute_utc, b.count)).collect();
+    assert_eq!(
+        counts,
+        vec![(10, 0, 2), (10, 60, 2), (10, 120, 1), (11, 0, 2), (11, 60, 2), (11, 120, 1)]
+    );
+    assert_eq!(buckets[0].bytes, 200);
+}
+
+#[test]
+fn agent_ids_are_stable_and_filterable_by_key() {
+    use clear_mini::kairo_p::{agent_id, PAddressRecord};
+
+    let alice = "11".repeat(32);
+    let bob = "22".repeat(32);
+    // Pinned: changing the derivation breaks joins with existing logs.
+    let id = agent_id(&alice);
+    assert_eq!(id as u32, 0xd3f3_ac81);
+    assert!(id < 0, "all 32 bits are kept");
+    assert_eq!(agent_id(&format!(" {} ", alice.to_uppercase())), id);
+    assert_eq!(agent_id(""), 0);
+    assert_eq!(agent_id("not hex"), 0);
+    assert_ne!(agent_id(&bob), id);
+    // The id follows the key, not the address it currently leases.
+    assert_eq!(PAddressRecord::for_agent("10.0.0.1/24", Some(&alice)).id, id);
+    assert_eq!(PAddressRecord::for_agent("10.0.0.7/24", Some(&alice)).id, id);
+    assert_eq!(PAddressRecord::for_agent("gpt://main", None).id, 0);
+
+    let mut all = records();
+    all[3].src = agent_id(&alice);
+    all[4].dst = agent_id(&bob);
+    let q = WitnessQuery { src_key: Some(alice.clone()), ..Default::default() };
+    assert_eq!(query(&all, &q).unwrap().records[0].seq, 3);
+    let q = WitnessQuery { dst_key: Some(bob.clone()), ..Default::default() };
+    let page = query(&all, &q).unwrap();
+    assert_eq!((page.total, page.records[0].seq), (1, 4));
+}
@@ -0,0 +1,79 @@
+// ===========================
+// 📄 rust-core/src/log_recorder.rs
+// ===========================
+
+// --- HMAC-SHA256 ログ記録 ---
+use crate::ai_tcp_packet_generated::aitcp as fb;
+use chrono::{DateTime, Utc};
+use flatbuffers::FlatBufferBuilder;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// ログ1件ごとに HMAC-SHA256 を付与する。鍵は任意のタイミングでローテーション可能。
+pub struct LogRecorder {
+    key: [u8; 32],
+    key_created_at: DateTime<Utc>,
+}
+
+impl Default for LogRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogRecorder {
+    /// ランダムな 32 バイト鍵で初期化
+    pub fn new() -> Self {
+        Self { key: rand::random(), key_created_at: Utc::now() }
+    }
+
+    /// ログ本文の HMAC を計算
+    pub fn hash(&self, entry: &[u8]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(entry);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// HMAC が一致するか検証
+    pub fn verify(&self, entry: &[u8], tag: &[u8]) -> bool {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(entry);
+        mac.verify_slice(tag).is_ok()
+    }
+
+    /// 新しい鍵に切り替える
+    pub fn rotate(&mut self) {
+        self.key = rand::random();
+        self.key_created_at = Utc::now();
+    }
+
+    pub fn key_created_at(&self) -> DateTime<Utc> {
+        self.key_created_at
+    }
+
+    /// 鍵と作成時刻を AITcpPacket (size-prefixed FlatBuffer) に書き出す
+    pub fn export_flatbuffers(&self) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let timestamp = self.key_created_at.timestamp().to_le_bytes();
+        let args = fb::AITcpPacketArgs {
+            version: 1,
+            header: Some(builder.create_vector(&timestamp)),
+            payload: Some(builder.create_vector(&self.key)),
+            ..Default::default()
+        };
+        let root = fb::AITcpPacket::create(&mut builder, &args);
+        fb::finish_size_prefixed_aitcp_packet_buffer(&mut builder, root);
+        builder.finished_data().to_vec()
+    }
+
+    /// `export_flatbuffers` の出力から復元
+    pub fn recover_flatbuffers(buf: &[u8]) -> Option<Self> {
+        let pkt = fb::size_prefixed_root_as_aitcp_packet(buf).ok()?;
+        let key: [u8; 32] = pkt.payload()?.bytes().try_into().ok()?;
+        let timestamp = i64::from_le_bytes(pkt.header()?.bytes().try_into().ok()?);
+        let key_created_at = DateTime::from_timestamp(timestamp, 0)?;
+        Some(Self { key, key_created_at })
+    }
+}
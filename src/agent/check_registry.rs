@@ -7,7 +7,8 @@ fn main() {
         Ok(entries) => {
             let mut names = HashSet::new();
             let mut addrs = HashSet::new();
-            for e in &entries {
+            // Revoked and deleted records are history; only live ones must be unique.
+            for e in entries.iter().filter(|e| !e.deleted) {
                 if !names.insert(&e.name) {
                     eprintln!("Duplicate agent name found: {}", e.name);
                     std::process::exit(1);
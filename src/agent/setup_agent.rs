@@ -1,6 +1,8 @@
 use clap::Parser;
 use ed25519_dalek::SigningKey;
 use kairo_lib::config::{save_agent_config, load_agent_config};
+use kairo_lib::p_allocator::{assign_in_file, Allocator, AllocatorConfig};
+use kairo_lib::registration::{AssignRequest, RegisterRequest, RegistrationChallenge};
 use kairo_lib::registry::{register_agent, RegistryEntry};
 use kairo_lib::AgentConfig;
 // This import is unused and has been removed.
@@ -20,6 +22,11 @@ struct CliArgs {
         help = "エージェント名 (英数字およびアンダースコアのみ。二重引用符は不要です)"
     )]
     name: String,
+    /// Seed node that assigns the P address and registers the new key,
+    /// e.g. `http://10.0.0.254:8000`. Without it the address comes from the
+    /// local lease file `p_leases.json`.
+    #[arg(long)]
+    seed_url: Option<String>,
 }
 
 // This function is unused and has been removed.
@@ -52,6 +59,58 @@ fn sanitize_agent_name(name: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Fetch a one-time nonce from the seed.
+async fn fetch_challenge(client: &reqwest::Client, base: &str) -> Result<RegistrationChallenge, String> {
+    client
+        .post(format!("{}/register/challenge", base))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("challenge request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("bad challenge: {}", e))
+}
+
+/// Lease a P address for `key` from the seed's allocator, proving possession over a fresh nonce.
+async fn assign_p_address(seed_url: &str, key: &SigningKey) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let base = seed_url.trim_end_matches('/');
+    let challenge = fetch_challenge(&client, base).await?;
+    let res = client
+        .post(format!("{}/assign_p_address", base))
+        .json(&AssignRequest::sign(key, None, &challenge))
+        .send()
+        .await
+        .map_err(|e| format!("assign request failed: {}", e))?;
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.map_err(|e| format!("bad response: {}", e))?;
+    match body["p_address"].as_str() {
+        Some(p_address) if status.is_success() => Ok(p_address.to_string()),
+        _ => Err(format!("{}: {}", status, body["message"].as_str().unwrap_or_default())),
+    }
+}
+
+/// Register `agent_id` at the seed, proving possession of `key` over a fresh nonce.
+async fn register_with_seed(seed_url: &str, agent_id: &str, key: &SigningKey) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let base = seed_url.trim_end_matches('/');
+    let challenge = fetch_challenge(&client, base).await?;
+    let res = client
+        .post(format!("{}/register", base))
+        .json(&RegisterRequest::sign(agent_id, key, &challenge))
+        .send()
+        .await
+        .map_err(|e| format!("registration request failed: {}", e))?;
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.map_err(|e| format!("bad response: {}", e))?;
+    if status.is_success() && body["status"] == "success" {
+        Ok(body["message"].as_str().unwrap_or_default().to_string())
+    } else {
+        Err(format!("{}: {}", status, body["message"].as_str().unwrap_or_default()))
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli_args = CliArgs::parse();
@@ -105,15 +164,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "\nStep 2: Registering with a Seed Node..."
         );
 
-        // Try to request P address from local daemon
-        println!(
-            "\nSkipping KAIRO-P address assignment from local daemon (not implemented)."
-        );
-        let p_address = format!("10.0.0.{}/24", rand::random::<u8>()); // ダミーのPアドレスを生成
+        let p_address = match &cli_args.seed_url {
+            Some(url) => assign_p_address(url, &signing_key).await?,
+            None => {
+                let allocator = Allocator::new(AllocatorConfig::default())?;
+                let now = chrono::Utc::now().timestamp();
+                assign_in_file("p_leases.json", &allocator, &public_key_hex, None, now)?.p_address
+            }
+        };
 
-        println!("-> Assigned Dummy P Address: {}", p_address);
+        println!("-> Assigned P Address: {}", p_address);
 
-        println!("-> Skipping registration with seed node (not implemented).");
+        match &cli_args.seed_url {
+            Some(url) => match register_with_seed(url, &cli_args.name, &signing_key).await {
+                Ok(msg) => println!("-> Seed node: {}", msg),
+                Err(e) => eprintln!("-> Seed registration failed: {}", e),
+            },
+            None => println!("-> Skipping registration with seed node (no --seed-url)."),
+        }
 
         config = AgentConfig {
             p_address: p_address.clone(),
@@ -0,0 +1,125 @@
+use clap::Parser;
+use kairo_lib::registry_snapshot::{compare, HeadTracker, InclusionProof, SnapshotError, SnapshotHead};
+use kairo_lib::seed_key;
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+
+/// Check that a registry entry is in the seed's signed snapshot, and that the
+/// seed has not shown this client (or its peers) a conflicting view.
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Args {
+    /// The seed's Ed25519 public key (hex), as printed at seed startup.
+    #[arg(long)]
+    seed_public_key: String,
+    /// Fetch a fresh proof from this seed, e.g. `http://10.0.0.254:8000`.
+    #[arg(long)]
+    seed_url: Option<String>,
+    #[arg(long)]
+    p_address: Option<String>,
+    #[arg(long)]
+    name: Option<String>,
+    /// Verify a saved proof instead of fetching one (works offline).
+    #[arg(long)]
+    proof: Option<String>,
+    /// Keep the verified proof for later offline checks.
+    #[arg(long)]
+    save_proof: Option<String>,
+    /// Newest snapshot head seen by this client.
+    #[arg(long, default_value = ".kairo/registry_head.json")]
+    head_file: String,
+    /// Heads other clients received from the same seed.
+    #[arg(long)]
+    peer_head: Vec<String>,
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn write_json<T: serde::Serialize>(path: &str, value: &T) -> Result<(), String> {
+    if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value).unwrap()).map_err(|e| format!("{}: {}", path, e))
+}
+
+async fn fetch_proof(seed_url: &str, p_address: Option<&str>, name: Option<&str>) -> Result<InclusionProof, String> {
+    let query: Vec<(&str, &str)> = p_address.map(|p| ("p_address", p)).into_iter().chain(name.map(|n| ("name", n))).collect();
+    let res = reqwest::Client::new()
+        .get(format!("{}/registry/proof", seed_url.trim_end_matches('/')))
+        .query(&query)
+        .send()
+        .await
+        .map_err(|e| format!("proof request failed: {}", e))?;
+    let status = res.status();
+    if !status.is_success() {
+        return Err(format!("{}: {}", status, res.text().await.unwrap_or_default()));
+    }
+    res.json().await.map_err(|e| format!("bad proof: {}", e))
+}
+
+fn fail(message: String, split_view: bool) -> ! {
+    eprintln!("❌ {}", message);
+    exit(if split_view { 2 } else { 1 })
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let issuer = seed_key::parse_public_key(&args.seed_public_key).unwrap_or_else(|e| fail(e, false));
+
+    let proof: InclusionProof = match (&args.proof, &args.seed_url) {
+        (Some(path), _) => read_json(path).unwrap_or_else(|e| fail(e, false)),
+        (None, Some(url)) => {
+            if args.p_address.is_none() && args.name.is_none() {
+                fail("--p-address or --name is required with --seed-url".to_string(), false);
+            }
+            fetch_proof(url, args.p_address.as_deref(), args.name.as_deref())
+                .await
+                .unwrap_or_else(|e| fail(e, false))
+        }
+        (None, None) => fail("either --proof or --seed-url is required".to_string(), false),
+    };
+    if let Err(e) = proof.verify(&issuer) {
+        fail(e.to_string(), false);
+    }
+
+    let mut tracker: HeadTracker = if Path::new(&args.head_file).exists() {
+        read_json(&args.head_file).unwrap_or_else(|e| fail(e, false))
+    } else {
+        HeadTracker::new()
+    };
+    match tracker.observe(&proof.head, &issuer) {
+        Ok(()) => {}
+        // An old saved proof is fine to check offline; a seed going back is not.
+        Err(SnapshotError::Rollback { .. }) if args.proof.is_some() => {}
+        Err(e) => fail(e.to_string(), true),
+    }
+    for path in &args.peer_head {
+        let head: SnapshotHead = read_json(path).unwrap_or_else(|e| fail(e, false));
+        if let Err(e) = head.verify(&issuer) {
+            fail(format!("{}: {}", path, e), false);
+        }
+        if let Err(e) = compare(&proof.head, &head).and_then(|_| tracker.latest.as_ref().map_or(Ok(()), |l| compare(l, &head))) {
+            fail(format!("{}: {}", path, e), true);
+        }
+    }
+    write_json(&args.head_file, &tracker).unwrap_or_else(|e| fail(e, false));
+    if let Some(path) = &args.save_proof {
+        write_json(path, &proof).unwrap_or_else(|e| fail(e, false));
+    }
+
+    let leaf = &proof.leaf;
+    println!(
+        "✅ {} ({}) key {} is {:?} in generation {} (root {})",
+        leaf.name,
+        leaf.p_address.as_deref().unwrap_or("-"),
+        leaf.public_key.as_deref().unwrap_or("-"),
+        leaf.status,
+        proof.head.generation,
+        proof.head.root
+    );
+}
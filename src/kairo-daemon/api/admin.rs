@@ -0,0 +1,360 @@
+//! Operator API served on `admin.listen_address`.
+//!
+//! Requests must carry `Authorization: KAIRO-Admin <pubkey_hex>:<unix_secs>:<nonce>:<sig_hex>`
+//! where the signature is Ed25519 over `"<METHOD> <PATH>[?<QUERY>] <unix_secs> <nonce>"`
+//! made by a key listed in `admin.public_keys`. Each nonce is accepted once per key.
+//! With no keys configured, only loopback clients are served.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use axum::extract::{ConnectInfo, Path, Query, Request};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use clear_mini::export::ExportFormat;
+use clear_mini::query::WitnessQuery;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::clear_mini_state::CLEAR_MINI;
+use crate::{config, inbox, peers};
+
+/// Accepted clock skew for admin tokens.
+const TOKEN_MAX_AGE_SECS: i64 = 60;
+/// Longest accepted token nonce.
+const MAX_NONCE_LEN: usize = 64;
+/// Tokens remembered at once; new tokens are refused while the cache is full.
+const MAX_SEEN_TOKENS: usize = 100_000;
+
+static SEEN_TOKENS: Lazy<Mutex<SeenTokens>> = Lazy::new(|| Mutex::new(SeenTokens::default()));
+
+/// `(key, nonce)` pairs of tokens still inside their validity window.
+#[derive(Default)]
+struct SeenTokens(HashMap<(String, String), i64>);
+
+impl SeenTokens {
+    /// Record a token; a token already seen within its window is a replay.
+    fn insert(&mut self, key: &str, nonce: &str, issued: i64, now: i64) -> Result<(), &'static str> {
+        self.0.retain(|_, ts| (now - *ts).abs() <= TOKEN_MAX_AGE_SECS);
+        if self.0.len() >= MAX_SEEN_TOKENS {
+            return Err("too many admin tokens in flight");
+        }
+        match self.0.insert((key.to_ascii_lowercase(), nonce.to_string()), issued) {
+            Some(_) => Err("replayed admin token"),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/admin/peers", get(list_peers))
+        .route("/admin/peers/:id/disconnect", post(disconnect_peer))
+        .route("/admin/inboxes", get(list_inboxes))
+        .route("/admin/inboxes/:p_address", get(inspect_inbox))
+        .route("/admin/inboxes/:p_address", delete(purge_inbox))
+        .route("/admin/reload", post(reload_config))
+        .route("/admin/inspection", get(inspection_stats))
+        .route("/admin/burst/top", get(burst_top))
+        .route("/admin/gpt_log", get(query_gpt_log))
+        .route("/admin/witness", get(query_witness))
+        .route("/admin/witness/aggregate", get(aggregate_witness))
+        .route("/admin/witness/export", get(export_witness))
+        .route("/admin/witness/verify", get(verify_witness))
+        .route("/admin/ise", get(ise_keys))
+        .route("/admin/ise/rotate", post(rotate_ise_key))
+        .route("/admin/revocations", get(revocation_status))
+        .route("/admin/revocations/refresh", post(refresh_revocations))
+        .layer(axum::middleware::from_fn(require_admin))
+}
+
+/// `target` is the request path with its query string, if any.
+fn verify_token(
+    headers: &HeaderMap,
+    method: &str,
+    target: &str,
+    keys: &[String],
+    seen: &mut SeenTokens,
+) -> Result<(), &'static str> {
+    let value = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("KAIRO-Admin "))
+        .ok_or("missing admin token")?;
+    let mut parts = value.splitn(4, ':');
+    let (key_hex, ts, nonce, sig_hex) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(k), Some(t), Some(n), Some(s)) => (k, t, n, s),
+        _ => return Err("malformed admin token"),
+    };
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN || !nonce.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err("malformed admin token");
+    }
+    if !keys.iter().any(|k| k.eq_ignore_ascii_case(key_hex)) {
+        return Err("unknown admin key");
+    }
+    let issued: i64 = ts.parse().map_err(|_| "malformed admin token")?;
+    let now = Utc::now().timestamp();
+    if (now - issued).abs() > TOKEN_MAX_AGE_SECS {
+        return Err("expired admin token");
+    }
+    let key_bytes: [u8; 32] = hex::decode(key_hex)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("malformed admin token")?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| "malformed admin token")?;
+    let sig = hex::decode(sig_hex)
+        .ok()
+        .and_then(|b| Signature::from_slice(&b).ok())
+        .ok_or("malformed admin token")?;
+    let message = format!("{} {} {} {}", method, target, ts, nonce);
+    key.verify(message.as_bytes(), &sig).map_err(|_| "invalid admin signature")?;
+    seen.insert(key_hex, nonce, issued, now)
+}
+
+async fn require_admin(ConnectInfo(remote): ConnectInfo<SocketAddr>, req: Request, next: Next) -> Response {
+    let keys = config::CONFIG.read().unwrap().admin.public_keys.clone();
+    let result = if keys.is_empty() {
+        if remote.ip().is_loopback() {
+            Ok(())
+        } else {
+            Err("admin API is local-only")
+        }
+    } else {
+        let target = req.uri().path_and_query().map_or(req.uri().path(), |p| p.as_str());
+        verify_token(req.headers(), req.method().as_str(), target, &keys, &mut SEEN_TOKENS.lock().unwrap())
+    };
+    match result {
+        Ok(()) => next.run(req).await,
+        Err(reason) => {
+            log::warn!("[ADMIN] Rejected {} {} from {}: {}", req.method(), req.uri().path(), remote, reason);
+            (StatusCode::UNAUTHORIZED, Json(json!({ "status": "error", "message": reason }))).into_response()
+        }
+    }
+}
+
+async fn list_peers() -> impl IntoResponse {
+    Json(peers::list())
+}
+
+async fn disconnect_peer(Path(id): Path<u64>) -> impl IntoResponse {
+    if peers::force_disconnect(id) {
+        log::warn!("[ADMIN] Force-disconnect requested for peer {}", id);
+        (StatusCode::OK, Json(json!({ "status": "success" })))
+    } else {
+        (StatusCode::NOT_FOUND, Json(json!({ "status": "not_found" })))
+    }
+}
+
+async fn list_inboxes() -> impl IntoResponse {
+    let depths: serde_json::Map<String, serde_json::Value> = inbox::depths()
+        .into_iter()
+        .map(|(k, v)| (k, json!(v)))
+        .collect();
+    Json(depths)
+}
+
+async fn inspect_inbox(Path(p_address): Path<String>) -> impl IntoResponse {
+    Json(inbox::peek(&p_address))
+}
+
+async fn purge_inbox(Path(p_address): Path<String>) -> impl IntoResponse {
+    let purged = inbox::purge(&p_address);
+    log::warn!("[ADMIN] Purged {} packets from inbox {}", purged, p_address);
+    Json(json!({ "status": "success", "purged": purged }))
+}
+
+async fn reload_config() -> impl IntoResponse {
+    match config::reload() {
+        Ok(settings) => {
+            crate::ip_classifier::refresh();
+            log::info!("[ADMIN] Reloaded config ({} firewall rules)", settings.firewall.deny_sources.len());
+            (StatusCode::OK, Json(json!({ "status": "success", "config": settings })))
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "status": "error", "message": e }))),
+    }
+}
+
+async fn inspection_stats() -> impl IntoResponse {
+    Json(json!({ "rules": crate::inspect::stats() }))
+}
+
+async fn burst_top() -> impl IntoResponse {
+    Json(json!({ "rules": crate::burst::BURST.lock().unwrap().top() }))
+}
+
+async fn query_gpt_log(Query(q): Query<crate::gpt_log_processor::LogQuery>) -> impl IntoResponse {
+    let dir = config::CONFIG.read().unwrap().gpt_log.dir.clone();
+    Json(crate::gpt_log_processor::query(&dir, &q))
+}
+
+async fn query_witness(Query(q): Query<WitnessQuery>) -> Response {
+    match CLEAR_MINI.query_witness(&q) {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => witness_error(e),
+    }
+}
+
+async fn aggregate_witness(Query(q): Query<WitnessQuery>) -> Response {
+    match CLEAR_MINI.aggregate_witness(&q) {
+        Ok(buckets) => Json(json!({ "buckets": buckets })).into_response(),
+        Err(e) => witness_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+async fn export_witness(Query(q): Query<WitnessQuery>, Query(params): Query<ExportParams>) -> Response {
+    let mut body = Vec::new();
+    if let Err(e) = CLEAR_MINI.export_witness(&q, params.format, &mut body) {
+        return witness_error(e);
+    }
+    let disposition = format!("attachment; filename=\"witness.{}\"", params.format.extension());
+    (
+        [(CONTENT_TYPE, params.format.content_type().to_string()), (CONTENT_DISPOSITION, disposition)],
+        body,
+    )
+        .into_response()
+}
+
+fn witness_error(e: std::io::Error) -> Response {
+    let status = match e.kind() {
+        std::io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "status": "error", "message": e.to_string() }))).into_response()
+}
+
+async fn verify_witness() -> impl IntoResponse {
+    let trusted = crate::clear_mini_state::WITNESS_KEY.get();
+    let public_key = trusted.map(|k| hex::encode(k.to_bytes()));
+    match CLEAR_MINI.verify_witness(trusted) {
+        Ok(report) => (StatusCode::OK, Json(json!({ "status": "ok", "public_key": public_key, "report": report }))),
+        Err(e) => (
+            StatusCode::CONFLICT,
+            Json(json!({ "status": "tampered", "public_key": public_key, "message": e.to_string() })),
+        ),
+    }
+}
+
+async fn ise_keys() -> impl IntoResponse {
+    match crate::p_signature_validator::key_ids() {
+        Some((active, keys)) => (StatusCode::OK, Json(json!({ "active": active, "keys": keys }))),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "error", "message": "ISE keyring not loaded" })),
+        ),
+    }
+}
+
+async fn rotate_ise_key() -> impl IntoResponse {
+    match crate::p_signature_validator::rotate() {
+        Ok((active, keys)) => {
+            log::info!("🔑 Rotated ISE key, now {}", active);
+            (StatusCode::OK, Json(json!({ "status": "success", "active": active, "keys": keys })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e.to_string() })),
+        ),
+    }
+}
+
+async fn revocation_status() -> impl IntoResponse {
+    match crate::revocations::status() {
+        Some(status) => (StatusCode::OK, Json(json!(status))),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "error", "message": "No seed_public_key configured" })),
+        ),
+    }
+}
+
+async fn refresh_revocations() -> impl IntoResponse {
+    let settings = config::CONFIG.read().unwrap().revocations.clone();
+    match crate::revocations::refresh(&settings).await {
+        Ok(added) => (StatusCode::OK, Json(json!({ "status": "success", "added": added, "list": crate::revocations::status() }))),
+        Err(e) => (StatusCode::BAD_GATEWAY, Json(json!({ "status": "error", "message": e }))),
+    }
+}
+
+/// Serve the admin router on its own listener.
+pub async fn serve(addr: &str) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("Admin API listening on {}", addr);
+    axum::serve(listener, router().into_make_service_with_connect_info::<SocketAddr>()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn token(key: &SigningKey, method: &str, target: &str, ts: i64, nonce: &str) -> HeaderMap {
+        let sig = key.sign(format!("{} {} {} {}", method, target, ts, nonce).as_bytes());
+        let value = format!(
+            "KAIRO-Admin {}:{}:{}:{}",
+            hex::encode(key.verifying_key().as_bytes()),
+            ts,
+            nonce,
+            hex::encode(sig.to_bytes())
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(axum::http::header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn admin_token_is_bound_to_key_request_time_and_nonce() {
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let keys = vec![hex::encode(key.verifying_key().as_bytes())];
+        let now = Utc::now().timestamp();
+        let mut seen = SeenTokens::default();
+        let mut check = |headers: HeaderMap, method: &str, target: &str| verify_token(&headers, method, target, &keys, &mut seen);
+
+        assert!(check(token(&key, "GET", "/admin/peers", now, "n1"), "GET", "/admin/peers").is_ok());
+        assert_eq!(check(token(&key, "GET", "/admin/peers", now, "n1"), "GET", "/admin/peers"), Err("replayed admin token"));
+        assert_eq!(
+            check(token(&key, "GET", "/admin/peers", now, "n2"), "POST", "/admin/reload"),
+            Err("invalid admin signature")
+        );
+        assert_eq!(
+            check(token(&key, "GET", "/admin/gpt_log?source=a", now, "n3"), "GET", "/admin/gpt_log?source=b"),
+            Err("invalid admin signature")
+        );
+        assert!(check(token(&key, "GET", "/admin/gpt_log?source=a", now, "n3"), "GET", "/admin/gpt_log?source=a").is_ok());
+        assert_eq!(
+            check(token(&key, "GET", "/admin/peers", now - 600, "n4"), "GET", "/admin/peers"),
+            Err("expired admin token")
+        );
+        let other = SigningKey::from_bytes(&[1u8; 32]);
+        assert_eq!(
+            check(token(&other, "GET", "/admin/peers", now, "n5"), "GET", "/admin/peers"),
+            Err("unknown admin key")
+        );
+        assert_eq!(check(HeaderMap::new(), "GET", "/admin/peers"), Err("missing admin token"));
+    }
+
+    #[test]
+    fn witness_query_parses_from_the_query_string() {
+        let uri: axum::http::Uri = "/admin/witness?dst=7&ip=10.0.0.0/8&since_utc=1760000000000000000000&offset=5"
+            .parse()
+            .unwrap();
+        let Query(q) = Query::<WitnessQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(q.dst, Some(7));
+        assert_eq!(q.ip.as_deref(), Some("10.0.0.0/8"));
+        assert_eq!(q.since_utc, Some(1_760_000_000_000_000_000_000));
+        assert_eq!(q.offset, Some(5));
+    }
+}
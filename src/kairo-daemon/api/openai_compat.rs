@@ -0,0 +1,296 @@
+//! OpenAI-compatible `POST /v1/chat/completions`.
+//!
+//! Clients authenticate with `Authorization: Bearer <token>`, the token being
+//! read from `openai_compat.api_key_env` at startup. Requests are wrapped into
+//! packets signed by the gateway key and routed through `handle_send::deliver`,
+//! so they see the same firewall, inspection and burst policies as native
+//! traffic. The destination is taken from the `X-Kairo-Destination` header,
+//! else from `model` (a configured backend name becomes `gpt://<model>`,
+//! anything with `/` is used as a P address), else
+//! `openai_compat.default_destination`, and must be listed in
+//! `openai_compat.allowed_destinations`. Replies are buffered: with
+//! `"stream": true` the finished reply is sent as a single content chunk.
+
+use axum::extract::Json;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use ed25519_dalek::{Signer, SigningKey};
+use kairo_lib::packet::AiTcpPacket;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::OpenAiCompatSettings;
+
+use crate::gpt_responder::{Message, CHAT_PAYLOAD_TYPE};
+use crate::metrics::METRICS;
+
+pub const ENABLE_ENV: &str = "KAIRO_ENABLE_OPENAI_COMPAT";
+
+struct Gateway {
+    key: SigningKey,
+    /// SHA-256 of the bearer token, compared instead of the token itself.
+    token_digest: [u8; 32],
+}
+
+static GATEWAY: OnceCell<Gateway> = OnceCell::new();
+
+static SEQUENCE: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(chrono::Utc::now().timestamp_millis() as u64));
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// `--enable-openai-compat` or `KAIRO_ENABLE_OPENAI_COMPAT=1|true`.
+pub fn enabled(cli_flag: bool) -> bool {
+    cli_flag || matches!(std::env::var(ENABLE_ENV).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Load the gateway signing key and client token. The daemon refuses to start
+/// the gateway without them: packets signed by a per-run key could not be
+/// verified by peers after a restart.
+pub fn init(settings: &OpenAiCompatSettings) -> Result<(), String> {
+    let path = settings
+        .signing_key_file
+        .as_deref()
+        .ok_or("openai_compat.signing_key_file is not set")?;
+    let key = crate::config::load_signing_key(path)?;
+    let token = std::env::var(&settings.api_key_env)
+        .ok()
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| format!("{} is not set", settings.api_key_env))?;
+    log::info!("[OPENAI] Gateway packets signed by {}", hex::encode(key.verifying_key().to_bytes()));
+    let _ = GATEWAY.set(Gateway { key, token_digest: Sha256::digest(token.as_bytes()).into() });
+    Ok(())
+}
+
+fn authorized(gateway: &Gateway, headers: &HeaderMap) -> bool {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|t| <[u8; 32]>::from(Sha256::digest(t.as_bytes())) == gateway.token_digest)
+}
+
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new().route("/v1/chat/completions", post(chat_completions))
+}
+
+fn resolve_destination(headers: &HeaderMap, model: &str, default: &str) -> String {
+    if let Some(dest) = headers.get("x-kairo-destination").and_then(|v| v.to_str().ok()) {
+        return dest.to_string();
+    }
+    if model.contains('/') {
+        model.to_string()
+    } else if crate::gpt_responder::backend_config(model).is_some() {
+        format!("gpt://{}", model)
+    } else {
+        default.to_string()
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = json!({ "error": { "message": message, "type": "kairo_error", "code": status.as_u16() } });
+    (status, Json(body)).into_response()
+}
+
+fn signed_packet(key: &SigningKey, source: &str, destination: &str, payload: String) -> AiTcpPacket {
+    AiTcpPacket {
+        source: source.to_string(),
+        destination: destination.to_string(),
+        version: 1,
+        source_p_address: source.to_string(),
+        destination_p_address: destination.to_string(),
+        source_public_key: hex::encode(key.verifying_key().to_bytes()),
+        sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        timestamp_utc: chrono::Utc::now().timestamp() as u64,
+        payload_type: CHAT_PAYLOAD_TYPE.to_string(),
+        signature: hex::encode(key.sign(payload.as_bytes()).to_bytes()),
+        payload,
+    }
+}
+
+/// The buffered reply as SSE `chat.completion.chunk` events: the role, the
+/// whole content in one chunk, then the stop chunk.
+fn stream_events(id: &str, created: i64, model: &str, content: &str) -> Vec<Event> {
+    let chunk = |delta: serde_json::Value, finish: Option<&str>| {
+        let data = json!({
+            "id": id, "object": "chat.completion.chunk", "created": created, "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }]
+        });
+        Event::default().data(data.to_string())
+    };
+    let mut events = vec![chunk(json!({ "role": "assistant" }), None)];
+    events.push(chunk(json!({ "content": content }), None));
+    events.push(chunk(json!({}), Some("stop")));
+    events.push(Event::default().data("[DONE]"));
+    events
+}
+
+/// Handle POST /v1/chat/completions
+async fn chat_completions(headers: HeaderMap, Json(req): Json<ChatCompletionRequest>) -> Response {
+    METRICS.packet_received("openai_compat");
+    let Some(gateway) = GATEWAY.get() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "gateway key not loaded");
+    };
+    if !authorized(gateway, &headers) {
+        METRICS.packet_rejected("openai_unauthorized");
+        return error_response(StatusCode::UNAUTHORIZED, "invalid or missing bearer token");
+    }
+    let settings = crate::config::CONFIG.read().unwrap().openai_compat.clone();
+    let destination = resolve_destination(&headers, &req.model, &settings.default_destination);
+    if !settings.allowed_destinations.contains(&destination) {
+        log::warn!("[OPENAI] Refusing destination {} not in allowed_destinations", destination);
+        METRICS.packet_rejected("openai_destination");
+        return error_response(StatusCode::FORBIDDEN, "destination not allowed");
+    }
+
+    let payload = match serde_json::to_string(&req.messages) {
+        Ok(p) => p,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let packet = signed_packet(&gateway.key, &settings.source_p_address, &destination, payload);
+    let sequence = packet.sequence;
+    log::info!("[OPENAI] {} -> {} (seq={}, stream={})", req.model, destination, sequence, req.stream);
+
+    let (status, content) = crate::handle_send::deliver(packet).await;
+    if status != StatusCode::OK {
+        return error_response(status, &content);
+    }
+
+    let id = format!("chatcmpl-{}", sequence);
+    let created = chrono::Utc::now().timestamp();
+    if req.stream {
+        let events = stream_events(&id, created, &req.model, &content);
+        return Sse::new(futures_util::stream::iter(events.into_iter().map(Ok::<_, Infallible>))).into_response();
+    }
+
+    Json(json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": req.model,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop"
+        }],
+        "kairo": { "destination": destination, "sequence": sequence }
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn completions_need_a_token_and_an_allowed_destination() {
+        let dir = std::env::temp_dir().join(format!("kairo-openai-compat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("gateway_key");
+        std::fs::write(&key_file, hex::encode([5u8; 32])).unwrap();
+        let settings = OpenAiCompatSettings {
+            allowed_destinations: vec!["gpt://main".into(), "10.9.9.9/24".into()],
+            signing_key_file: Some(key_file.to_str().unwrap().to_string()),
+            api_key_env: "KAIRO_TEST_OPENAI_COMPAT_TOKEN".into(),
+            ..Default::default()
+        };
+        assert!(init(&settings).unwrap_err().contains("KAIRO_TEST_OPENAI_COMPAT_TOKEN"));
+        std::env::set_var("KAIRO_TEST_OPENAI_COMPAT_TOKEN", "s3cret");
+        init(&settings).unwrap();
+        {
+            let mut config = crate::config::CONFIG.write().unwrap();
+            config.gpt_log.enabled = false;
+            config.openai_compat = settings;
+        }
+        let _ = std::fs::remove_dir_all(dir);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router::<()>()).await.unwrap() });
+        let url = format!("http://{}/v1/chat/completions", addr);
+        let client = reqwest::Client::new();
+        let messages = json!([{ "role": "system", "content": "x" }, { "role": "user", "content": "hello there" }]);
+
+        let resp = client.post(&url).json(&json!({ "model": "main", "messages": messages })).send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = client
+            .post(&url)
+            .bearer_auth("wrong")
+            .json(&json!({ "model": "main", "messages": messages }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let resp: serde_json::Value = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .json(&json!({ "model": "main", "messages": messages }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(resp["choices"][0]["message"]["content"], "[mock:gpt-4o-mini] hello there");
+        assert_eq!(resp["kairo"]["destination"], "gpt://main");
+
+        let body = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .json(&json!({ "model": "main", "messages": messages, "stream": true }))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let content: String = body
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .filter_map(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+            .filter_map(|v| v["choices"][0]["delta"]["content"].as_str().map(String::from))
+            .collect();
+        assert_eq!(content, "[mock:gpt-4o-mini] hello there");
+        assert!(body.contains("data: [DONE]"));
+
+        let resp = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .header("X-Kairo-Destination", "10.9.9.8/24")
+            .json(&json!({ "model": "main", "messages": messages }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403);
+        assert!(crate::inbox::drain("10.9.9.8/24").is_empty());
+
+        let resp = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .header("X-Kairo-Destination", "10.9.9.9/24")
+            .json(&json!({ "model": "main", "messages": messages }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let queued = crate::inbox::drain("10.9.9.9/24");
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].payload_type, CHAT_PAYLOAD_TYPE);
+        assert!(crate::p_signature_validator::validate_packet(&queued[0]));
+        assert_eq!(queued[0].source_public_key, hex::encode(SigningKey::from_bytes(&[5u8; 32]).verifying_key().to_bytes()));
+    }
+}
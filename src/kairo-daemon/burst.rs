@@ -0,0 +1,350 @@
+//! Configurable burst detectors built on `clear_mini::detector::Detector`.
+
+use clear_mini::detector::{Detector, DetectorConfig};
+use clear_mini::witness::flags;
+use kairo_lib::packet::AiTcpPacket;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{BurstAction, BurstKey, BurstMeasure, BurstRule};
+
+pub(crate) static BURST: Lazy<Mutex<BurstEngine>> = Lazy::new(|| Mutex::new(BurstEngine::default()));
+
+/// Quarantined senders and alert cooldowns kept at once; when full, the
+/// entry closest to expiry is dropped.
+const MAX_TRACKED: usize = 10_000;
+
+/// A rule that crossed its threshold for this packet.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub rule: String,
+    pub action: BurstAction,
+    pub key: String,
+    pub count: u64,
+    pub window_secs: u64,
+    /// For `Alert` rules: notify the auditor. Set once per rule and key per window.
+    pub alert: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Decision {
+    pub triggers: Vec<Trigger>,
+    /// Set when the source was already quarantined before this packet.
+    pub quarantined: bool,
+}
+
+impl Decision {
+    /// Whether the packet must be dropped.
+    pub fn blocks(&self) -> bool {
+        self.quarantined
+            || self
+                .triggers
+                .iter()
+                .any(|t| matches!(t.action, BurstAction::Throttle | BurstAction::Quarantine))
+    }
+
+    /// `WitnessRecord::flags` bits describing this decision.
+    pub fn witness_flags(&self) -> u32 {
+        let mut bits = if self.quarantined { flags::QUARANTINED } else { 0 };
+        for t in &self.triggers {
+            bits |= match t.action {
+                BurstAction::Log => flags::BURST_LOG,
+                BurstAction::Throttle => flags::BURST_THROTTLE,
+                BurstAction::Quarantine => flags::BURST_QUARANTINE,
+                BurstAction::Alert => flags::BURST_ALERT,
+            };
+        }
+        bits
+    }
+}
+
+#[derive(Default)]
+pub struct BurstEngine {
+    rules: Vec<BurstRule>,
+    detectors: Vec<Detector>,
+    /// Sender public key -> end of quarantine.
+    quarantine: HashMap<String, Instant>,
+    /// (rule, key) -> end of the alert cooldown.
+    alerted: HashMap<(String, String), Instant>,
+}
+
+/// Heaviest keys of one rule, as served by `GET /admin/burst/top`.
+#[derive(Debug, Serialize)]
+pub struct RuleTop {
+    pub rule: String,
+    pub measure: BurstMeasure,
+    pub window_secs: u64,
+    pub top: Vec<TopKey>,
+    pub memory_bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopKey {
+    pub key: String,
+    pub count: u64,
+}
+
+/// Sources are counted by their verified public key: the claimed source P
+/// address is not bound to the signature.
+fn key_of(rule: &BurstRule, packet: &AiTcpPacket) -> String {
+    match rule.key {
+        BurstKey::Source => packet.source_public_key.to_ascii_lowercase(),
+        BurstKey::Destination => packet.destination_p_address.clone(),
+        BurstKey::PayloadType => packet.payload_type.clone(),
+    }
+}
+
+/// Insert `key` expiring at `until`, dropping expired entries first and the
+/// entry closest to expiry if the map is still full.
+fn insert_bounded<K: std::hash::Hash + Eq + Clone>(map: &mut HashMap<K, Instant>, key: K, until: Instant, now: Instant) {
+    if map.len() >= MAX_TRACKED && !map.contains_key(&key) {
+        map.retain(|_, t| now < *t);
+        if map.len() >= MAX_TRACKED {
+            if let Some(oldest) = map.iter().min_by_key(|(_, t)| **t).map(|(k, _)| k.clone()) {
+                map.remove(&oldest);
+            }
+        }
+    }
+    map.insert(key, until);
+}
+
+impl BurstEngine {
+    /// Rebuild the detectors if the configured rules changed since the last call.
+    fn sync_rules(&mut self, rules: &[BurstRule]) {
+        if self.rules != rules {
+            self.rules = rules.to_vec();
+            self.detectors = rules
+                .iter()
+                .map(|r| Detector::new(DetectorConfig::new(Duration::from_secs(r.window_secs))))
+                .collect();
+        }
+    }
+
+    /// `source_key` is the sender's hex public key.
+    pub fn is_quarantined(&mut self, source_key: &str, now: Instant) -> bool {
+        match self.quarantine.get(source_key) {
+            Some(&until) if now < until => true,
+            Some(_) => {
+                self.quarantine.remove(source_key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Count the packet against every rule and return the rules that fired.
+    pub fn evaluate(&mut self, packet: &AiTcpPacket, rules: &[BurstRule]) -> Decision {
+        self.sync_rules(rules);
+        let now = Instant::now();
+        let source_key = packet.source_public_key.to_ascii_lowercase();
+        if self.is_quarantined(&source_key, now) {
+            return Decision { triggers: Vec::new(), quarantined: true };
+        }
+
+        let mut decision = Decision::default();
+        for (rule, detector) in self.rules.iter().zip(self.detectors.iter_mut()) {
+            let key = key_of(rule, packet);
+            let count = match rule.measure {
+                BurstMeasure::Packets => detector.hit(&key, now),
+                BurstMeasure::DistinctSources => detector.hit_distinct(&key, &source_key, now),
+            };
+            if count < rule.threshold as u64 {
+                continue;
+            }
+            let window = Duration::from_secs(rule.window_secs);
+            let mut alert = false;
+            match rule.action {
+                BurstAction::Quarantine => {
+                    let until = now + Duration::from_secs(rule.quarantine_secs);
+                    insert_bounded(&mut self.quarantine, source_key.clone(), until, now);
+                }
+                BurstAction::Alert => {
+                    let id = (rule.name.clone(), key.clone());
+                    alert = self.alerted.get(&id).is_none_or(|until| now >= *until);
+                    if alert {
+                        insert_bounded(&mut self.alerted, id, now + window, now);
+                    }
+                }
+                BurstAction::Log | BurstAction::Throttle => {}
+            }
+            decision.triggers.push(Trigger {
+                rule: rule.name.clone(),
+                action: rule.action,
+                key,
+                count,
+                window_secs: rule.window_secs,
+                alert,
+            });
+        }
+        decision
+    }
+
+    /// Per-rule heavy hitters in the current windows.
+    pub fn top(&self) -> Vec<RuleTop> {
+        let now = Instant::now();
+        self.rules
+            .iter()
+            .zip(&self.detectors)
+            .map(|(rule, detector)| RuleTop {
+                rule: rule.name.clone(),
+                measure: rule.measure,
+                window_secs: rule.window_secs,
+                top: detector
+                    .top_k(now)
+                    .into_iter()
+                    .map(|(key, count)| TopKey { key, count })
+                    .collect(),
+                memory_bytes: detector.memory_bytes(),
+            })
+            .collect()
+    }
+}
+
+/// Build the notification queued for the auditor when an `Alert` rule fires.
+pub fn alert_packet(trigger: &Trigger, packet: &AiTcpPacket, auditor: &str) -> AiTcpPacket {
+    let body = serde_json::json!({
+        "rule": trigger.rule,
+        "key": trigger.key,
+        "count": trigger.count,
+        "window_secs": trigger.window_secs,
+        "source_p_address": packet.source_p_address,
+        "destination_p_address": packet.destination_p_address,
+        "sequence": packet.sequence,
+    });
+    AiTcpPacket {
+        source: "kairo-daemon".to_string(),
+        destination: auditor.to_string(),
+        version: 1,
+        source_p_address: "kairo-daemon".to_string(),
+        destination_p_address: auditor.to_string(),
+        source_public_key: String::new(),
+        sequence: 0,
+        timestamp_utc: chrono::Utc::now().timestamp() as u64,
+        payload_type: "application/vnd.kairo.burst-alert+json".to_string(),
+        payload: body.to_string(),
+        signature: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet from the sender key `key`, claiming the P address `src`.
+    fn signed_packet(key: &str, src: &str) -> AiTcpPacket {
+        AiTcpPacket {
+            source: src.into(),
+            destination: "10.0.0.2/24".into(),
+            version: 1,
+            source_p_address: src.into(),
+            destination_p_address: "10.0.0.2/24".into(),
+            source_public_key: key.into(),
+            sequence: 1,
+            timestamp_utc: 0,
+            payload_type: "text/plain".into(),
+            payload: "hi".into(),
+            signature: String::new(),
+        }
+    }
+
+    fn packet(src: &str) -> AiTcpPacket {
+        signed_packet(src, src)
+    }
+
+    fn rule(name: &str, key: BurstKey, threshold: usize, action: BurstAction) -> BurstRule {
+        BurstRule {
+            name: name.into(),
+            key,
+            window_secs: 10,
+            threshold,
+            action,
+            quarantine_secs: 60,
+            measure: BurstMeasure::Packets,
+        }
+    }
+
+    #[test]
+    fn rules_fire_per_key_and_quarantine_sticks() {
+        let rules = vec![
+            rule("src", BurstKey::Source, 3, BurstAction::Quarantine),
+            rule("type", BurstKey::PayloadType, 2, BurstAction::Log),
+        ];
+        let mut engine = BurstEngine::default();
+
+        let first = engine.evaluate(&packet("a"), &rules);
+        assert!(first.triggers.is_empty());
+
+        let second = engine.evaluate(&packet("b"), &rules);
+        assert_eq!(second.triggers.len(), 1);
+        assert!(!second.blocks());
+        assert_eq!(second.witness_flags(), flags::BURST_LOG);
+
+        engine.evaluate(&packet("a"), &rules);
+        let third = engine.evaluate(&packet("a"), &rules);
+        assert!(third.blocks());
+        assert_ne!(third.witness_flags() & flags::BURST_QUARANTINE, 0);
+
+        let after = engine.evaluate(&packet("a"), &rules);
+        assert!(after.quarantined);
+        assert_eq!(after.witness_flags(), flags::QUARANTINED);
+        assert!(!engine.evaluate(&packet("b"), &rules).quarantined);
+    }
+
+    #[test]
+    fn quarantine_follows_the_sender_key_and_alerts_once_per_window() {
+        let rules = vec![
+            rule("src", BurstKey::Source, 2, BurstAction::Quarantine),
+            rule("dst", BurstKey::Destination, 2, BurstAction::Alert),
+        ];
+        let mut engine = BurstEngine::default();
+
+        // "mallory" floods while claiming alice's P address.
+        engine.evaluate(&signed_packet("mallory", "alice"), &rules);
+        let fired = engine.evaluate(&signed_packet("mallory", "alice"), &rules);
+        assert!(fired.blocks());
+        let alerts: Vec<_> = fired.triggers.iter().filter(|t| t.alert).collect();
+        assert_eq!(alerts.len(), 1);
+
+        assert!(engine.evaluate(&signed_packet("mallory", "elsewhere"), &rules).quarantined);
+        let alice = engine.evaluate(&signed_packet("alice", "alice"), &rules);
+        assert!(!alice.quarantined);
+        // Still over the destination threshold, but the auditor was already told.
+        assert!(alice.triggers.iter().any(|t| t.action == BurstAction::Alert && !t.alert));
+    }
+
+    #[test]
+    fn tracked_maps_stay_bounded() {
+        let now = Instant::now();
+        let mut map = HashMap::new();
+        for i in 0..MAX_TRACKED + 10 {
+            insert_bounded(&mut map, i, now + Duration::from_secs(60 + i as u64), now);
+        }
+        assert_eq!(map.len(), MAX_TRACKED);
+        assert!(!map.contains_key(&0));
+        assert!(map.contains_key(&(MAX_TRACKED + 9)));
+    }
+
+    #[test]
+    fn distinct_sources_rule_counts_each_source_once_and_reports_top_keys() {
+        let mut spread = rule("spread", BurstKey::Destination, 3, BurstAction::Log);
+        spread.measure = BurstMeasure::DistinctSources;
+        let rules = vec![spread];
+        let mut engine = BurstEngine::default();
+
+        for _ in 0..5 {
+            assert!(engine.evaluate(&packet("a"), &rules).triggers.is_empty());
+        }
+        assert!(engine.evaluate(&packet("b"), &rules).triggers.is_empty());
+        let fired = engine.evaluate(&packet("c"), &rules);
+        assert_eq!(fired.triggers.len(), 1);
+        assert_eq!(fired.triggers[0].count, 3);
+
+        let top = engine.top();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].top[0].key, "10.0.0.2/24");
+        assert_eq!(top[0].top[0].count, 3);
+    }
+}
@@ -0,0 +1,177 @@
+//! Shutdown handling and on-disk state checkpoints.
+//!
+//! On SIGINT/SIGTERM the daemon stops accepting connections, lets in-flight
+//! requests finish, then writes inboxes, queued tasks, the witness ring and
+//! the KAIRO-P replay windows to `state_path`. The next start restores them
+//! and removes the file so the same state is not replayed twice.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clear_mini::chain::ChainCheckpoint;
+use clear_mini::witness::WitnessRecord;
+use kairo_lib::packet::AiTcpPacket;
+use serde::{Deserialize, Serialize};
+
+use crate::clear_mini_state::CLEAR_MINI;
+use crate::kairo_p_listener::SequenceTracker;
+use crate::task_queue::{Task, TaskQueue};
+use crate::{inbox, peers};
+
+const CHECKPOINT_VERSION: u32 = 1;
+/// How long to wait for KAIRO-P connections to close on shutdown.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub saved_at: String,
+    pub inboxes: HashMap<String, Vec<AiTcpPacket>>,
+    pub tasks: Vec<Task>,
+    pub witness: Vec<WitnessRecord>,
+    #[serde(default)]
+    pub witness_checkpoints: Vec<ChainCheckpoint>,
+    /// Highest accepted sequence per hex sender key.
+    pub replay_windows: HashMap<String, u64>,
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    log::warn!("🛑 Shutdown signal received, draining connections...");
+}
+
+/// Close KAIRO-P connections and wait (bounded) for their tasks to exit.
+pub async fn drain_peers() {
+    peers::disconnect_all();
+    let start = Instant::now();
+    while peers::count() > 0 && start.elapsed() < DRAIN_TIMEOUT {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    if peers::count() > 0 {
+        log::warn!("{} KAIRO-P connections still open after drain timeout", peers::count());
+    }
+}
+
+pub fn capture(queue: &Arc<Mutex<TaskQueue>>, tracker: &SequenceTracker) -> Checkpoint {
+    Checkpoint {
+        version: CHECKPOINT_VERSION,
+        saved_at: chrono::Utc::now().to_rfc3339(),
+        inboxes: inbox::snapshot(),
+        tasks: queue.lock().unwrap().tasks().to_vec(),
+        witness: CLEAR_MINI.dump_witness_snapshot(),
+        witness_checkpoints: CLEAR_MINI.ring.checkpoints(),
+        replay_windows: tracker.snapshot(),
+    }
+}
+
+/// Write the checkpoint via a temporary file and rename, so a crash mid-write
+/// never leaves a truncated state file behind.
+pub fn save(path: &str, checkpoint: &Checkpoint) -> std::io::Result<()> {
+    let path = Path::new(path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let json = serde_json::to_vec_pretty(checkpoint)?;
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Load and apply a checkpoint if one exists, then remove it.
+pub fn restore(path: &str, queue: &Arc<Mutex<TaskQueue>>, tracker: &SequenceTracker) -> std::io::Result<bool> {
+    if !Path::new(path).exists() {
+        return Ok(false);
+    }
+    let text = std::fs::read_to_string(path)?;
+    let checkpoint: Checkpoint = serde_json::from_str(&text)?;
+    if checkpoint.version != CHECKPOINT_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unsupported checkpoint version {}", checkpoint.version),
+        ));
+    }
+
+    log::info!(
+        "♻️ Restoring checkpoint from {}: {} inboxes, {} tasks, {} witness records, {} replay windows",
+        checkpoint.saved_at,
+        checkpoint.inboxes.len(),
+        checkpoint.tasks.len(),
+        checkpoint.witness.len(),
+        checkpoint.replay_windows.len()
+    );
+    inbox::restore(checkpoint.inboxes);
+    {
+        let mut q = queue.lock().unwrap();
+        for task in checkpoint.tasks {
+            q.add_task(task);
+        }
+    }
+    CLEAR_MINI.restore_witness(checkpoint.witness, checkpoint.witness_checkpoints);
+    tracker.restore(&checkpoint.replay_windows);
+
+    std::fs::remove_file(path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("kairo-checkpoint-{}", std::process::id()));
+        let path = dir.join("daemon_state.json");
+        let path = path.to_str().unwrap();
+
+        let mut saved = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            ..Default::default()
+        };
+        saved.tasks.push(Task { id: "1".into(), name: "n".into(), command: "c".into() });
+        // Other tests share CLEAR_MINI; a high seq keeps the record ahead of theirs.
+        saved.witness.push(WitnessRecord { src: 7, port: 443, utc: u128::MAX, seq: 1 << 40, ..Default::default() });
+        saved.replay_windows.insert(hex::encode([3u8; 32]), 42);
+        save(path, &saved).unwrap();
+
+        let queue = Arc::new(Mutex::new(TaskQueue::new()));
+        let tracker = SequenceTracker::default();
+        assert!(restore(path, &queue, &tracker).unwrap());
+        assert!(!Path::new(path).exists());
+
+        assert_eq!(queue.lock().unwrap().tasks()[0].command, "c");
+        assert_eq!(tracker.snapshot().get(&hex::encode([3u8; 32])), Some(&42));
+        let witness = CLEAR_MINI.dump_witness_snapshot();
+        assert!(witness.iter().any(|r| r.src == 7 && r.port == 443 && r.utc == u128::MAX));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,5 +1,44 @@
 use clear_mini::api::ClearMini;
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use clear_mini::segment::SegmentLog;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use once_cell::sync::{Lazy, OnceCell};
 
-pub(crate) static CLEAR_MINI: Lazy<Mutex<ClearMini>> = Lazy::new(|| Mutex::new(ClearMini::new()));
+use crate::config::WitnessSettings;
+
+pub(crate) static CLEAR_MINI: Lazy<ClearMini> =
+    Lazy::new(|| ClearMini::with_config(crate::config::CONFIG.read().unwrap().witness.clear_mini_config()));
+
+/// Key that signs this node's witness chain checkpoints.
+pub(crate) static WITNESS_KEY: OnceCell<VerifyingKey> = OnceCell::new();
+
+/// Install the checkpoint signer from `witness.signing_key_file`.
+pub fn init_signer(settings: &WitnessSettings) -> VerifyingKey {
+    let key = match settings.signing_key_file.as_deref().map(|path| (path, kairo_lib::seed_key::load_or_create(path))) {
+        Some((_, Ok(key))) => key,
+        Some((path, Err(e))) => {
+            log::error!("Cannot load witness key {}: {}; signing checkpoints with an ephemeral key", path, e);
+            SigningKey::from_bytes(&rand::random())
+        }
+        None => {
+            log::warn!("No witness.signing_key_file, signing witness checkpoints with an ephemeral key");
+            SigningKey::from_bytes(&rand::random())
+        }
+    };
+    let public = key.verifying_key();
+    CLEAR_MINI.ring.set_signer(key, settings.checkpoint_interval);
+    let _ = WITNESS_KEY.set(public);
+    public
+}
+
+/// Open the on-disk witness log from `witness.log_dir`, if configured.
+pub fn open_log(settings: &WitnessSettings) {
+    let Some(dir) = settings.log_dir.as_deref() else {
+        return;
+    };
+    let result = SegmentLog::open(dir, settings.segment_config())
+        .and_then(|log| CLEAR_MINI.attach_log(log));
+    match result {
+        Ok(()) => log::info!("Witness log at {} ({} records in ring)", dir, CLEAR_MINI.ring.len()),
+        Err(e) => log::error!("Failed to open witness log {}: {}", dir, e),
+    }
+}
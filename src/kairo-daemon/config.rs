@@ -1,3 +1,511 @@
 //! src/kairo_daemon/config.rs
 
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
 
+pub const DEFAULT_CONFIG_PATH: &str = ".kairo/config/daemon_config.json";
+
+/// Daemon settings loaded from `daemon_config.json`.
+/// Missing fields fall back to the built-in defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonSettings {
+    pub listen_address: String,
+    pub listen_port: u16,
+    pub kairo_p_address: String,
+    /// Checkpoint written on shutdown and restored on the next start.
+    pub state_path: String,
+    pub admin: AdminSettings,
+    pub firewall: FirewallRules,
+    pub burst: BurstSettings,
+    pub classifier: ClassifierPolicy,
+    pub inspection: InspectionSettings,
+    /// LLM backends keyed by the `<name>` in `gpt://<name>` destinations.
+    pub gpt_backends: BTreeMap<String, LlmBackendConfig>,
+    pub openai_compat: OpenAiCompatSettings,
+    pub gpt_log: GptLogSettings,
+    pub witness: WitnessSettings,
+    pub ise: IseSettings,
+    pub revocations: RevocationSettings,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        Self {
+            listen_address: "127.0.0.1".to_string(),
+            listen_port: 8080,
+            kairo_p_address: crate::kairo_p_listener::DEFAULT_ADDR.to_string(),
+            state_path: ".kairo/state/daemon_state.json".to_string(),
+            admin: AdminSettings::default(),
+            firewall: FirewallRules::default(),
+            burst: BurstSettings::default(),
+            classifier: ClassifierPolicy::default(),
+            inspection: InspectionSettings::default(),
+            gpt_backends: BTreeMap::from([("main".to_string(), LlmBackendConfig::default())]),
+            openai_compat: OpenAiCompatSettings::default(),
+            gpt_log: GptLogSettings::default(),
+            witness: WitnessSettings::default(),
+            ise: IseSettings::default(),
+            revocations: RevocationSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminSettings {
+    pub listen_address: String,
+    /// Hex Ed25519 keys allowed to sign admin tokens.
+    /// When empty, the admin API only answers loopback clients.
+    pub public_keys: Vec<String>,
+}
+
+impl Default for AdminSettings {
+    fn default() -> Self {
+        Self {
+            listen_address: "127.0.0.1:8079".to_string(),
+            public_keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallRules {
+    /// Source P addresses or hex public keys whose packets are dropped.
+    pub deny_sources: Vec<String>,
+    /// Also drop packets whose source P address has no key in the registry.
+    /// A source registered to another key is always dropped.
+    pub require_registered_source: bool,
+}
+
+impl FirewallRules {
+    pub fn denies(&self, p_address: &str, public_key: &str) -> bool {
+        self.deny_sources
+            .iter()
+            .any(|s| s == p_address || s.eq_ignore_ascii_case(public_key))
+    }
+}
+
+/// What a burst rule counts hits by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BurstKey {
+    Source,
+    Destination,
+    PayloadType,
+}
+
+/// Policy applied while a burst rule is over its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BurstAction {
+    Log,
+    /// Reject packets for the key until the window drains.
+    Throttle,
+    /// Reject every packet from the sender key for `quarantine_secs`.
+    Quarantine,
+    /// Queue an alert packet in the auditor's inbox, once per key per window.
+    Alert,
+}
+
+impl BurstAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Log => "log",
+            Self::Throttle => "throttle",
+            Self::Quarantine => "quarantine",
+            Self::Alert => "alert",
+        }
+    }
+}
+
+/// What a burst rule counts per key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BurstMeasure {
+    #[default]
+    Packets,
+    /// Distinct sender keys (approximate).
+    DistinctSources,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BurstRule {
+    pub name: String,
+    pub key: BurstKey,
+    pub window_secs: u64,
+    pub threshold: usize,
+    pub action: BurstAction,
+    #[serde(default)]
+    pub quarantine_secs: u64,
+    #[serde(default)]
+    pub measure: BurstMeasure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BurstSettings {
+    pub rules: Vec<BurstRule>,
+    /// Inbox that receives `Alert` notifications.
+    pub auditor_p_address: String,
+}
+
+impl Default for BurstSettings {
+    fn default() -> Self {
+        Self {
+            rules: vec![BurstRule {
+                name: "dst_10s".to_string(),
+                key: BurstKey::Destination,
+                window_secs: 10,
+                threshold: 50,
+                action: BurstAction::Log,
+                quarantine_secs: 0,
+                measure: BurstMeasure::Packets,
+            }],
+            auditor_p_address: "auditor".to_string(),
+        }
+    }
+}
+
+/// Endpoint class assigned by `ip_classifier`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    Local,
+    KnownTest,
+    KnownPeer,
+    Suspicious,
+    Unknown,
+}
+
+/// CIDR lists driving `ip_classifier`. Lists are checked in the order
+/// suspicious, known peers, known test, local; anything else is `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassifierPolicy {
+    pub local: Vec<String>,
+    pub known_test: Vec<String>,
+    pub known_peers: Vec<String>,
+    pub suspicious: Vec<String>,
+    /// Agent registry whose live P addresses are added to `known_peers`.
+    pub registry_path: Option<String>,
+    /// How often the registry file is checked for changes.
+    pub refresh_secs: u64,
+    /// Classes whose endpoints the daemon refuses to talk to.
+    pub block: Vec<EndpointClass>,
+}
+
+impl Default for ClassifierPolicy {
+    fn default() -> Self {
+        Self {
+            local: ["127.0.0.0/8", "::1/128", "10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16"]
+                .map(String::from)
+                .to_vec(),
+            known_test: vec!["93.184.216.34/32".to_string()],
+            known_peers: Vec::new(),
+            suspicious: Vec::new(),
+            registry_path: Some("agent_registry.json".to_string()),
+            refresh_secs: 10,
+            block: vec![EndpointClass::Suspicious],
+        }
+    }
+}
+
+/// Outcome of a content inspection rule, ordered by severity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    #[default]
+    Pass,
+    Flag,
+    Drop,
+}
+
+/// A regex and/or keyword rule. Keywords match case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatternRule {
+    pub name: String,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InspectionSettings {
+    /// Packets whose payload exceeds this many bytes are dropped.
+    pub max_payload_bytes: usize,
+    /// Accepted `payload_type`s; empty accepts any.
+    pub allowed_payload_types: Vec<String>,
+    pub patterns: Vec<PatternRule>,
+    /// Verdict for built-in prompt-injection markers; `pass` disables the rule.
+    pub prompt_injection: Verdict,
+}
+
+impl Default for InspectionSettings {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 64 * 1024,
+            allowed_payload_types: Vec::new(),
+            patterns: Vec::new(),
+            prompt_injection: Verdict::Flag,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmBackendKind {
+    /// Any server implementing OpenAI's `POST /chat/completions`.
+    Openai,
+    /// Deterministic offline responder for tests and demos.
+    Mock,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmBackendConfig {
+    pub kind: LlmBackendKind,
+    pub base_url: String,
+    pub model: String,
+    /// Environment variable holding the bearer token, if any.
+    pub api_key_env: Option<String>,
+    pub temperature: f32,
+    pub timeout_secs: u64,
+}
+
+impl Default for LlmBackendConfig {
+    fn default() -> Self {
+        Self {
+            kind: LlmBackendKind::Mock,
+            base_url: "https://api.openai.com/v1".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            temperature: 0.7,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// `/v1/chat/completions` gateway. Enabled with `KAIRO_ENABLE_OPENAI_COMPAT=1`
+/// or `--enable-openai-compat` (phase 2 of the OpenAI compatibility plan).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenAiCompatSettings {
+    /// P address the gateway signs packets as.
+    pub source_p_address: String,
+    /// Used when the request names neither a backend nor a destination.
+    pub default_destination: String,
+    /// The only destinations clients may reach through the gateway.
+    pub allowed_destinations: Vec<String>,
+    /// File holding the hex Ed25519 secret key; required when the gateway is enabled.
+    pub signing_key_file: Option<String>,
+    /// Environment variable holding the bearer token clients must send.
+    pub api_key_env: String,
+}
+
+impl Default for OpenAiCompatSettings {
+    fn default() -> Self {
+        Self {
+            source_p_address: "openai-compat".to_string(),
+            default_destination: "gpt://main".to_string(),
+            allowed_destinations: vec!["gpt://main".to_string()],
+            signing_key_file: None,
+            api_key_env: "KAIRO_OPENAI_COMPAT_API_KEY".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionRule {
+    pub name: String,
+    pub regex: String,
+}
+
+/// Conversation logs for `gpt://` traffic, one JSONL file per source and backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GptLogSettings {
+    pub enabled: bool,
+    pub dir: String,
+    /// Apply the built-in API key / token / email / card number patterns.
+    pub redact_builtin: bool,
+    pub redact: Vec<RedactionRule>,
+    /// Records older than this are pruned; 0 keeps them forever.
+    pub retention_days: u64,
+    /// Oldest files are removed once the log directory exceeds this; 0 = no limit.
+    pub max_total_bytes: u64,
+}
+
+impl Default for GptLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: "logs/gpt".to_string(),
+            redact_builtin: true,
+            redact: Vec::new(),
+            retention_days: 30,
+            max_total_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WitnessSettings {
+    /// File holding the hex Ed25519 secret key that signs witness chain
+    /// checkpoints, created on first start; a random key is used per run when unset.
+    pub signing_key_file: Option<String>,
+    pub checkpoint_interval: u64,
+    /// Records held in memory; changes need a restart.
+    pub capacity: usize,
+    /// `drop_oldest` writes every record to `log_dir`; `spill_to_disk` writes
+    /// records there only once they leave the in-memory ring.
+    pub overflow: clear_mini::config::OverflowPolicy,
+    /// Directory of the append-only witness segment log; unset keeps
+    /// records in memory only.
+    pub log_dir: Option<String>,
+    pub segment_max_bytes: u64,
+    pub segment_max_age_secs: u64,
+    /// Closed segments kept; 0 keeps all.
+    pub max_segments: usize,
+    /// Closed segments older than this are deleted; 0 disables.
+    pub max_age_days: u64,
+}
+
+impl Default for WitnessSettings {
+    fn default() -> Self {
+        Self {
+            signing_key_file: Some(".kairo/config/witness_signing_key".to_string()),
+            checkpoint_interval: clear_mini::witness::DEFAULT_CHECKPOINT_INTERVAL,
+            capacity: clear_mini::config::Config::default().witness_capacity,
+            overflow: clear_mini::config::OverflowPolicy::DropOldest,
+            log_dir: Some(".kairo/witness".to_string()),
+            segment_max_bytes: 16 * 1024 * 1024,
+            segment_max_age_secs: 24 * 3600,
+            max_segments: 64,
+            max_age_days: 0,
+        }
+    }
+}
+
+impl WitnessSettings {
+    pub fn clear_mini_config(&self) -> clear_mini::config::Config {
+        clear_mini::config::Config {
+            witness_capacity: self.capacity,
+            overflow: self.overflow,
+        }
+    }
+
+    pub fn segment_config(&self) -> clear_mini::segment::SegmentConfig {
+        clear_mini::segment::SegmentConfig {
+            max_segment_bytes: self.segment_max_bytes,
+            max_segment_age: std::time::Duration::from_secs(self.segment_max_age_secs),
+            max_segments: self.max_segments,
+            max_age: (self.max_age_days > 0).then(|| std::time::Duration::from_secs(self.max_age_days * 86400)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IseSettings {
+    /// Keyring of HMAC keys for integrity stamps; created on first start.
+    pub key_file: String,
+    /// Retired keys still accepted after `POST /admin/ise/rotate`.
+    pub max_retired_keys: usize,
+    /// Stamps issued further than this from now are rejected.
+    pub replay_window_secs: u64,
+    /// Nonces remembered within the window; stamps are refused once full.
+    pub replay_max_entries: usize,
+    /// Refuse KAIRO-P frames without a stamp in `footer` even on a loopback
+    /// bind; other binds always require one. A stamp that is present is
+    /// always checked.
+    pub require_on_kairo_p: bool,
+}
+
+impl Default for IseSettings {
+    fn default() -> Self {
+        Self {
+            key_file: ".kairo/ise_keys.json".to_string(),
+            max_retired_keys: 3,
+            replay_window_secs: 300,
+            replay_max_entries: 100_000,
+            require_on_kairo_p: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RevocationSettings {
+    /// Seed node serving `GET /revocations`; nothing is fetched when unset.
+    pub seed_url: Option<String>,
+    /// Hex Ed25519 key the seed signs revocation lists with.
+    pub seed_public_key: Option<String>,
+    /// Signed lists kept across restarts.
+    pub cache_file: String,
+    pub refresh_secs: u64,
+    /// Fetch the full list again after this many deltas.
+    pub max_deltas: usize,
+}
+
+impl Default for RevocationSettings {
+    fn default() -> Self {
+        Self {
+            seed_url: None,
+            seed_public_key: None,
+            cache_file: ".kairo/revocations.json".to_string(),
+            refresh_secs: 60,
+            max_deltas: 100,
+        }
+    }
+}
+
+/// Read an Ed25519 secret key stored as 32 hex-encoded bytes.
+pub fn load_signing_key(path: &str) -> Result<ed25519_dalek::SigningKey, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let bytes: [u8; 32] = hex::decode(text.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| format!("{}: expected 32 hex-encoded bytes", path))?;
+    Ok(ed25519_dalek::SigningKey::from_bytes(&bytes))
+}
+
+static CONFIG_PATH: OnceCell<String> = OnceCell::new();
+pub(crate) static CONFIG: Lazy<RwLock<DaemonSettings>> =
+    Lazy::new(|| RwLock::new(DaemonSettings::default()));
+
+fn read_settings(path: &str) -> Result<DaemonSettings, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Load settings at startup. A missing file keeps the defaults.
+pub fn init(path: &str) -> DaemonSettings {
+    let _ = CONFIG_PATH.set(path.to_string());
+    let settings = if std::path::Path::new(path).exists() {
+        read_settings(path).unwrap_or_else(|e| {
+            log::error!("Invalid daemon config, using defaults: {}", e);
+            DaemonSettings::default()
+        })
+    } else {
+        log::warn!("{} not found, using default daemon settings", path);
+        DaemonSettings::default()
+    };
+    *CONFIG.write().unwrap() = settings.clone();
+    settings
+}
+
+/// Re-read the config file. Listen addresses only take effect after a restart.
+pub fn reload() -> Result<DaemonSettings, String> {
+    let path = CONFIG_PATH.get().map(String::as_str).unwrap_or(DEFAULT_CONFIG_PATH);
+    let settings = read_settings(path)?;
+    *CONFIG.write().unwrap() = settings.clone();
+    Ok(settings)
+}
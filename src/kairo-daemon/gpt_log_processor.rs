@@ -1,35 +1,254 @@
+//! Conversation logs for the GPT subsystem.
+//!
+//! Every `gpt://` exchange is appended, after redaction, to
+//! `<dir>/<source>__<backend>.jsonl`. Retention is enforced at startup and
+//! then hourly by `main`.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::Path;
-use chrono::Utc;
-use log::{info, error};
-
-/// GPT応答をログファイルに追記保存する
-pub async fn log_gpt_response(response: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let log_dir = Path::new("logs");
-    let log_file = log_dir.join("gpt_response_log.jsonl");
-
-    // ログディレクトリがなければ作成
-    if !log_dir.exists() {
-        std::fs::create_dir_all(&log_dir)?;
-        info!("📁 Created log directory: {:?}", log_dir);
-    }
-
-    // 書き込みオープン
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_file)?;
-
-    // タイムスタンプ付きで記録
-    let timestamp = Utc::now().to_rfc3339();
-    let log_entry = format!(r#"{{"timestamp": "{}", "response": {}}}"#, timestamp, response);
-
-    if let Err(e) = writeln!(file, "{}", log_entry) {
-        error!("❌ Failed to write log entry: {}", e);
-        Err(Box::new(e))
-    } else {
-        info!("✅ Log written successfully to: {:?}", log_file);
-        Ok(())
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::GptLogSettings;
+use crate::gpt_responder::Message;
+
+const BUILTIN_REDACTIONS: &[(&str, &str)] = &[
+    ("api_key", r"\bsk-[A-Za-z0-9_-]{16,}"),
+    ("aws_key", r"\bAKIA[0-9A-Z]{16}\b"),
+    ("bearer", r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]{8,}"),
+    ("email", r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b"),
+    ("card_number", r"\b(?:\d[ -]?){12,15}\d\b"),
+];
+
+/// Serialises appends and caches the compiled redaction rules.
+static STATE: Lazy<Mutex<Option<(GptLogSettings, Redactor)>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationRecord {
+    pub timestamp: DateTime<Utc>,
+    pub source_p_address: String,
+    pub sequence: u64,
+    pub destination_p_address: String,
+    pub backend: String,
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub response: String,
+    pub remote_addr: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogQuery {
+    pub source: Option<String>,
+    pub backend: Option<String>,
+    pub sequence: Option<u64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+pub struct Redactor {
+    rules: Vec<(String, Regex)>,
+}
+
+impl Redactor {
+    pub fn new(settings: &GptLogSettings) -> Self {
+        let builtin = BUILTIN_REDACTIONS
+            .iter()
+            .filter(|_| settings.redact_builtin)
+            .map(|(name, re)| (name.to_string(), re.to_string()));
+        let custom = settings.redact.iter().map(|r| (r.name.clone(), r.regex.clone()));
+        let rules = builtin
+            .chain(custom)
+            .filter_map(|(name, re)| match Regex::new(&re) {
+                Ok(re) => Some((name, re)),
+                Err(e) => {
+                    error!("[GPT_LOG] Skipping redaction rule {}: {}", name, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |acc, (name, re)| {
+            re.replace_all(&acc, format!("[REDACTED:{}]", name).as_str()).into_owned()
+        })
+    }
+}
+
+/// Keep file names portable: P addresses contain `/` and `:`.
+fn sanitize(part: &str) -> String {
+    part.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+fn conversation_path(dir: &Path, source: &str, backend: &str) -> PathBuf {
+    dir.join(format!("{}__{}.jsonl", sanitize(source), sanitize(backend)))
+}
+
+/// Redact and append one exchange.
+pub fn append(settings: &GptLogSettings, mut record: ConversationRecord) -> std::io::Result<()> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    let mut state = STATE.lock().unwrap();
+    if state.as_ref().map(|(s, _)| s) != Some(settings) {
+        *state = Some((settings.clone(), Redactor::new(settings)));
+    }
+    let redactor = &state.as_ref().unwrap().1;
+    for m in &mut record.messages {
+        m.content = redactor.redact(&m.content);
+    }
+    record.response = redactor.redact(&record.response);
+
+    let dir = Path::new(&settings.dir);
+    std::fs::create_dir_all(dir)?;
+    let path = conversation_path(dir, &record.source_p_address, &record.backend);
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)
+}
+
+fn read_records(path: &Path) -> Vec<ConversationRecord> {
+    std::fs::read_to_string(path)
+        .map(|text| text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+        .unwrap_or_default()
+}
+
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Records matching `q`, oldest first; `limit` keeps the most recent ones.
+pub fn query(dir: &str, q: &LogQuery) -> Vec<ConversationRecord> {
+    let dir = Path::new(dir);
+    let files = match (&q.source, &q.backend) {
+        (Some(source), Some(backend)) => vec![conversation_path(dir, source, backend)],
+        _ => log_files(dir),
+    };
+    let mut records: Vec<_> = files
+        .iter()
+        .flat_map(|p| read_records(p))
+        .filter(|r| q.source.as_ref().is_none_or(|s| &r.source_p_address == s))
+        .filter(|r| q.backend.as_ref().is_none_or(|b| &r.backend == b))
+        .filter(|r| q.sequence.is_none_or(|s| r.sequence == s))
+        .filter(|r| q.since.is_none_or(|t| r.timestamp >= t))
+        .filter(|r| q.until.is_none_or(|t| r.timestamp <= t))
+        .collect();
+    records.sort_by_key(|r| r.timestamp);
+    if let Some(limit) = q.limit {
+        let skip = records.len().saturating_sub(limit);
+        records.drain(..skip);
+    }
+    records
+}
+
+/// Drop records past `retention_days`, then whole files (oldest first) while
+/// the directory is over `max_total_bytes`.
+pub fn enforce_retention(settings: &GptLogSettings, now: DateTime<Utc>) -> std::io::Result<()> {
+    let _guard = STATE.lock().unwrap();
+    let dir = Path::new(&settings.dir);
+    if settings.retention_days > 0 {
+        let cutoff = now - ChronoDuration::days(settings.retention_days as i64);
+        for path in log_files(dir) {
+            let records = read_records(&path);
+            let kept: Vec<_> = records.iter().filter(|r| r.timestamp >= cutoff).collect();
+            if kept.is_empty() {
+                std::fs::remove_file(&path)?;
+            } else if kept.len() < records.len() {
+                let mut text = String::new();
+                for r in kept {
+                    text.push_str(&serde_json::to_string(r)?);
+                    text.push('\n');
+                }
+                std::fs::write(&path, text)?;
+            }
+        }
+    }
+
+    if settings.max_total_bytes > 0 {
+        let mut files: Vec<_> = log_files(dir)
+            .into_iter()
+            .filter_map(|p| {
+                let meta = std::fs::metadata(&p).ok()?;
+                Some((meta.modified().ok()?, meta.len(), p))
+            })
+            .collect();
+        files.sort();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in files {
+            if total <= settings.max_total_bytes {
+                break;
+            }
+            warn!("[GPT_LOG] Removing {:?} to stay under {} bytes", path, settings.max_total_bytes);
+            std::fs::remove_file(&path)?;
+            total -= len;
+        }
+    }
+    info!("[GPT_LOG] Retention applied to {:?}", dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(source: &str, seq: u64, age_days: i64, prompt: &str) -> ConversationRecord {
+        ConversationRecord {
+            timestamp: Utc::now() - ChronoDuration::days(age_days),
+            source_p_address: source.into(),
+            sequence: seq,
+            destination_p_address: "gpt://main".into(),
+            backend: "main".into(),
+            model: "m".into(),
+            messages: vec![Message { role: "user".into(), content: prompt.into() }],
+            response: "ok".into(),
+            remote_addr: "127.0.0.1:0".into(),
+        }
+    }
+
+    #[test]
+    fn logs_are_redacted_queryable_and_pruned() {
+        let dir = std::env::temp_dir().join(format!("kairo-gpt-log-{}", std::process::id()));
+        let settings = GptLogSettings {
+            dir: dir.to_str().unwrap().to_string(),
+            redact: vec![crate::config::RedactionRule { name: "ticket".into(), regex: r"TICKET-\d+".into() }],
+            retention_days: 7,
+            ..Default::default()
+        };
+
+        append(&settings, record("10.0.0.1/24", 1, 10, "old")).unwrap();
+        append(&settings, record("10.0.0.1/24", 2, 0, "key sk-abcdefghijklmnopqrstu for a@b.io, TICKET-42")).unwrap();
+        append(&settings, record("10.0.0.2/24", 1, 0, "hi")).unwrap();
+
+        let q = LogQuery { source: Some("10.0.0.1/24".into()), backend: Some("main".into()), ..Default::default() };
+        let found = query(&settings.dir, &q);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[1].messages[0].content,
+            "key [REDACTED:api_key] for [REDACTED:email], [REDACTED:ticket]"
+        );
+        let by_seq = query(&settings.dir, &LogQuery { sequence: Some(1), ..Default::default() });
+        assert_eq!(by_seq.len(), 2);
+
+        enforce_retention(&settings, Utc::now()).unwrap();
+        let remaining = query(&settings.dir, &LogQuery::default());
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().all(|r| r.messages[0].content != "old"));
+
+        let _ = std::fs::remove_dir_all(dir);
     }
 }
@@ -1,11 +1,16 @@
 use anyhow::{anyhow, Error};
-use kairo_lib::packet::Packet;
+use kairo_lib::packet::AiTcpPacket;
 use log::{error, info, warn};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
 use std::time::Duration;
 
+use crate::config::{LlmBackendConfig, LlmBackendKind};
+use crate::gpt_log_processor::ConversationRecord;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
     pub role: String,
@@ -19,6 +24,7 @@ pub struct GptRequest {
     pub temperature: f32,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct GptResponse {
     pub id: String,
@@ -28,47 +34,234 @@ pub struct GptResponse {
     pub choices: Vec<Choice>,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct Choice {
     pub index: i32,
     pub message: Message,
 }
 
-const DEFAULT_TIMEOUT_SECS: u64 = 10;
-const ZERO_COST_ENDPOINT: &str = "https://example.com";
+/// `payload_type` of packets whose payload is a JSON array of chat [`Message`]s.
+pub const CHAT_PAYLOAD_TYPE: &str = "application/vnd.kairo.chat+json";
+
+/// A finished completion plus the peer that produced it (for the witness log).
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub content: String,
+    pub model: String,
+    pub remote_addr: SocketAddr,
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub trait LlmBackend: Send + Sync {
+    fn complete<'a>(&'a self, request: &'a GptRequest) -> BoxFuture<'a, Result<Completion, Error>>;
+}
+
+pub struct OpenAiBackend {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiBackend {
+    /// `upstream` pins the host of `base_url` to already vetted addresses;
+    /// redirects are not followed, so requests never reach anything else.
+    pub fn new(base_url: &str, api_key: Option<String>, timeout: Duration, upstream: &[SocketAddr]) -> Result<Self, Error> {
+        let mut builder = Client::builder().timeout(timeout).redirect(reqwest::redirect::Policy::none());
+        if let Some(host) = reqwest::Url::parse(base_url)?.domain().filter(|_| !upstream.is_empty()) {
+            builder = builder.resolve_to_addrs(host, upstream);
+        }
+        let client = builder.build()?;
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+}
+
+impl LlmBackend for OpenAiBackend {
+    fn complete<'a>(&'a self, request: &'a GptRequest) -> BoxFuture<'a, Result<Completion, Error>> {
+        Box::pin(async move {
+            let url = format!("{}/chat/completions", self.base_url);
+            let mut builder = self.client.post(&url).json(request);
+            if let Some(key) = &self.api_key {
+                builder = builder.bearer_auth(key);
+            }
+            let response = builder.send().await.map_err(|e| {
+                error!("Failed to reach LLM backend {}: {}", url, e);
+                anyhow!("Failed to reach LLM backend {}: {}", url, e)
+            })?;
+
+            let remote_addr = response.remote_addr().unwrap_or_else(|| {
+                warn!("Could not get remote_addr from response, falling back to 0.0.0.0:0");
+                "0.0.0.0:0".parse().unwrap()
+            });
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow!("LLM backend returned {}: {}", status, body));
+            }
+
+            let parsed: GptResponse = response.json().await?;
+            let content = parsed
+                .choices
+                .into_iter()
+                .next()
+                .map(|c| c.message.content)
+                .ok_or_else(|| anyhow!("LLM backend returned no choices"))?;
+            Ok(Completion { content, model: parsed.model, remote_addr })
+        })
+    }
+}
+
+/// Answers without any network access: the reply is derived only from the request.
+pub struct MockBackend;
+
+impl LlmBackend for MockBackend {
+    fn complete<'a>(&'a self, request: &'a GptRequest) -> BoxFuture<'a, Result<Completion, Error>> {
+        Box::pin(async move {
+            let prompt = request
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.role == "user")
+                .map(|m| m.content.as_str())
+                .unwrap_or("");
+            Ok(Completion {
+                content: format!("[mock:{}] {}", request.model, prompt),
+                model: request.model.clone(),
+                remote_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            })
+        })
+    }
+}
+
+pub fn build_backend(config: &LlmBackendConfig, upstream: &[SocketAddr]) -> Result<Box<dyn LlmBackend>, Error> {
+    match config.kind {
+        LlmBackendKind::Mock => Ok(Box::new(MockBackend)),
+        LlmBackendKind::Openai => {
+            let api_key = config.api_key_env.as_deref().and_then(|var| std::env::var(var).ok());
+            let timeout = Duration::from_secs(config.timeout_secs);
+            Ok(Box::new(OpenAiBackend::new(&config.base_url, api_key, timeout, upstream)?))
+        }
+    }
+}
+
+/// Addresses a backend would talk to, resolved before anything is sent so
+/// the endpoint policy can refuse them. The mock backend stays in-process.
+pub async fn resolve_upstream(config: &LlmBackendConfig) -> Result<Vec<SocketAddr>, Error> {
+    if config.kind == LlmBackendKind::Mock {
+        return Ok(vec![SocketAddr::from(([127, 0, 0, 1], 0))]);
+    }
+    let url = reqwest::Url::parse(&config.base_url)?;
+    let host = url.host_str().ok_or_else(|| anyhow!("{} has no host", config.base_url))?;
+    let port = url.port_or_known_default().ok_or_else(|| anyhow!("{} has no port", config.base_url))?;
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("{} did not resolve", host));
+    }
+    Ok(addrs)
+}
+
+/// Look up the backend for `name` in `gpt://<name>`.
+pub fn backend_config(name: &str) -> Option<LlmBackendConfig> {
+    crate::config::CONFIG.read().unwrap().gpt_backends.get(name).cloned()
+}
 
-pub async fn gpt_log_and_respond(packet: &Packet) -> Result<(String, SocketAddr), Error> {
+pub async fn complete_with(name: &str, messages: Vec<Message>, upstream: &[SocketAddr]) -> Result<Completion, Error> {
+    let config = backend_config(name).ok_or_else(|| anyhow!("no LLM backend named {:?}", name))?;
+    let request = GptRequest {
+        model: config.model.clone(),
+        messages,
+        temperature: config.temperature,
+    };
+    build_backend(&config, upstream)?.complete(&request).await
+}
+
+/// `upstream` are the vetted addresses from [`resolve_upstream`].
+pub async fn gpt_log_and_respond(packet: &AiTcpPacket, upstream: &[SocketAddr]) -> Result<(String, SocketAddr), Error> {
+    let name = packet.destination_p_address.strip_prefix("gpt://").unwrap_or("main");
     info!(
-        "  [GPT_Subsystem] Processing packet seq={} (Zero-Cost Mode)",
-        packet.sequence
+        "  [GPT_Subsystem] Processing packet seq={} via backend {:?}",
+        packet.sequence, name
     );
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
-        .build()?;
-
-    let response = client
-        .get(ZERO_COST_ENDPOINT)
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Failed to connect for remote_addr test: {}", e);
-            anyhow!("Failed to connect for remote_addr test: {}", e)
-        })?;
-
-    let remote_addr = response.remote_addr().unwrap_or_else(|| {
-        warn!("Could not get remote_addr from response, falling back to 0.0.0.0:0");
-        "0.0.0.0:0".parse().unwrap()
-    });
-    info!("  [GPT_Subsystem] Actual remote addr: {}", remote_addr);
-
-    if !response.status().is_success() {
-        let status = response.status();
-        warn!(
-            "Test connection to example.com failed ({}). Still got remote_addr.",
-            status
-        );
+    let messages = if packet.payload_type == CHAT_PAYLOAD_TYPE {
+        serde_json::from_str(&packet.payload).map_err(|e| anyhow!("invalid chat payload: {}", e))?
+    } else {
+        vec![Message { role: "user".to_string(), content: packet.payload.clone() }]
+    };
+    let completion = complete_with(name, messages.clone(), upstream).await?;
+    info!(
+        "  [GPT_Subsystem] {} answered from {}",
+        completion.model, completion.remote_addr
+    );
+
+    let record = ConversationRecord {
+        timestamp: chrono::Utc::now(),
+        source_p_address: packet.source_p_address.clone(),
+        sequence: packet.sequence,
+        destination_p_address: packet.destination_p_address.clone(),
+        backend: name.to_string(),
+        model: completion.model.clone(),
+        messages,
+        response: completion.content.clone(),
+        remote_addr: completion.remote_addr.to_string(),
+    };
+    let settings = crate::config::CONFIG.read().unwrap().gpt_log.clone();
+    if let Err(e) = crate::gpt_log_processor::append(&settings, record) {
+        error!("❌ Failed to write GPT conversation log: {}", e);
     }
+    Ok((completion.content, completion.remote_addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
 
-    Ok(("OK (Zero-Cost Response)".to_string(), remote_addr))
+    fn request(prompt: &str) -> GptRequest {
+        GptRequest {
+            model: "test-model".into(),
+            messages: vec![
+                Message { role: "system".into(), content: "be brief".into() },
+                Message { role: "user".into(), content: prompt.into() },
+            ],
+            temperature: 0.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_is_deterministic_and_openai_backend_parses_choices() {
+        let a = MockBackend.complete(&request("ping")).await.unwrap();
+        let b = MockBackend.complete(&request("ping")).await.unwrap();
+        assert_eq!(a.content, "[mock:test-model] ping");
+        assert_eq!(a.content, b.content);
+
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                let prompt = body["messages"][1]["content"].as_str().unwrap_or("").to_uppercase();
+                Json(serde_json::json!({
+                    "id": "cmpl-1", "object": "chat.completion", "created": 0, "model": body["model"],
+                    "choices": [{ "index": 0, "message": { "role": "assistant", "content": prompt } }]
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let backend = OpenAiBackend::new(&format!("http://{}/v1/", addr), None, Duration::from_secs(5), &[]).unwrap();
+        let completion = backend.complete(&request("ping")).await.unwrap();
+        assert_eq!(completion.content, "PING");
+        assert_eq!(completion.model, "test-model");
+        assert_eq!(completion.remote_addr, addr);
+    }
 }
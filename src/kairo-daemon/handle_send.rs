@@ -1,43 +1,58 @@
-use clear_mini::detector::Window;
+use axum::http::StatusCode;
+use axum::Json;
 use clear_mini::kairo_p::PAddressRecord;
-use kairo_lib::packet::Packet;
+use clear_mini::witness::{flags, scope, verdict, Extension, WitnessRecord};
+use kairo_lib::packet::AiTcpPacket;
 use log::{error, info, warn};
-use once_cell::sync::Lazy;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
-use std::sync::Mutex;
-use crate::ip_classifier::{classify_ip, EndpointClass};
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use crate::burst::{Decision, BURST};
+use crate::config::{BurstAction, Verdict};
+use crate::ip_classifier::{classify_ip, is_blocked, witness_scope, EndpointClass};
 use crate::clear_mini_state::CLEAR_MINI;
-
-static DET_DST_10S: Lazy<Mutex<Window>> = Lazy::new(|| Mutex::new(Window::new(10)));
+use crate::metrics::METRICS;
 
 struct SendRequest {
-    src_id: i32,
-    src_nick: String,
+    src: PAddressRecord,
+    dst: PAddressRecord,
     dst_ip: [u8; 16],
     dst_port: u16,
     route_flags: u32,
     payload_len: u32,
+    ext: Extension,
 }
 
 impl SendRequest {
-    fn new(packet: &Packet, dst_ip: [u8; 16], dst_port: u16, route_flags: u32) -> Self {
+    fn new(packet: &AiTcpPacket, dst_ip: [u8; 16], dst_port: u16, route_flags: u32) -> Self {
+        let verdict = if route_flags & flags::INSPECT_DROPPED != 0 {
+            verdict::DROP
+        } else if route_flags & flags::INSPECT_FLAGGED != 0 {
+            verdict::FLAG
+        } else {
+            verdict::PASS
+        };
         Self {
-            src_id: derive_agent_id(&packet.source_p_address),
-            src_nick: packet.source_p_address.clone(),
+            src: PAddressRecord::for_agent(&packet.source_p_address, Some(&packet.source_public_key)),
+            dst: PAddressRecord::for_agent(
+                &packet.destination_p_address,
+                crate::ip_classifier::registered_key(&packet.destination_p_address).as_deref(),
+            ),
             dst_ip,
             dst_port,
             route_flags,
-            payload_len: packet.payload.as_bytes().len() as u32,
+            payload_len: packet.payload.len() as u32,
+            ext: Extension {
+                payload_hash: Sha256::digest(packet.payload.as_bytes()).into(),
+                verdict,
+                scope: scope::UNKNOWN,
+            },
         }
     }
-}
 
-fn derive_agent_id(p_address: &str) -> i32 {
-    let mut hasher = DefaultHasher::new();
-    p_address.hash(&mut hasher);
-    (hasher.finish() & 0x7FFF_FFFF) as i32
+    fn with_class(mut self, class: EndpointClass) -> Self {
+        self.ext.scope = witness_scope(class);
+        self
+    }
 }
 
 fn parse_destination_endpoint(p_address: &str) -> ([u8; 16], u16) {
@@ -51,6 +66,16 @@ fn parse_destination_endpoint(p_address: &str) -> ([u8; 16], u16) {
     }
 }
 
+/// IP part of a destination P address such as `10.0.0.2/24` or `kairo://10.0.0.2:9000`.
+fn destination_ip(p_address: &str) -> Option<IpAddr> {
+    let stripped = p_address.split("://").last().unwrap_or(p_address);
+    let host = stripped.split('/').next().unwrap_or(stripped);
+    host.parse::<SocketAddr>()
+        .map(|s| s.ip())
+        .or_else(|_| host.parse::<IpAddr>())
+        .ok()
+}
+
 fn encode_ip(ip: IpAddr) -> [u8; 16] {
     match ip {
         IpAddr::V4(v4) => {
@@ -62,59 +87,182 @@ fn encode_ip(ip: IpAddr) -> [u8; 16] {
 }
 
 fn record_witness(req: &SendRequest) {
-    let mut cm = CLEAR_MINI.lock().unwrap();
-    let src = PAddressRecord::new(req.src_id, &req.src_nick);
-    let dst = PAddressRecord::new(0, "");
-    cm.record(&src, &dst, req.payload_len, req.route_flags, req.dst_ip, req.dst_port);
+    let mut r = WitnessRecord {
+        src: req.src.id,
+        dst: req.dst.id,
+        len: req.payload_len,
+        flags: req.route_flags,
+        port: req.dst_port,
+        ip: req.dst_ip,
+        ..Default::default()
+    };
+    r.set_extension(&req.ext);
+    if let Err(e) = CLEAR_MINI.record_entry(r) {
+        error!("❌ Failed to persist witness record: {}", e);
+    }
 }
 
-fn detect_burst(dst_ip: [u8; 16], dst_port: u16) {
-    let mut hasher = DefaultHasher::new();
-    dst_ip.hash(&mut hasher);
-    dst_port.hash(&mut hasher);
-    let key = hasher.finish();
-
-    let count = DET_DST_10S.lock().unwrap().hit(key);
-    if count >= 50 {
-        warn!(
-            "AI/Burst PUT detected: {} hits/10s to {}:{}",
-            count,
-            format_ip(dst_ip),
-            dst_port
-        );
+/// Whether the sender is revoked: by the key it signs with, by the key the
+/// registry binds to its P address, or by the registry record of that address.
+/// A revoked agent cannot get around the list by signing with a new key.
+fn is_revoked_sender(packet: &AiTcpPacket) -> bool {
+    crate::revocations::is_revoked(&packet.source_public_key)
+        || crate::ip_classifier::registered_key(&packet.source_p_address).is_some_and(|k| crate::revocations::is_revoked(&k))
+        || crate::ip_classifier::is_revoked_address(&packet.source_p_address)
+}
+
+/// Why the source P address may not be used with the packet's key: the
+/// registry lists it for another key, or for none while
+/// `firewall.require_registered_source` is set.
+fn source_binding_error(packet: &AiTcpPacket) -> Option<&'static str> {
+    match crate::ip_classifier::registered_key(&packet.source_p_address) {
+        Some(key) if !key.eq_ignore_ascii_case(&packet.source_public_key) => Some("source_key_mismatch"),
+        Some(_) => None,
+        None if crate::config::CONFIG.read().unwrap().firewall.require_registered_source => Some("unregistered_source"),
+        None => None,
     }
 }
 
-fn format_ip(ip: [u8; 16]) -> String {
-    if ip[4..].iter().any(|b| *b != 0) {
-        Ipv6Addr::from(ip).to_string()
-    } else {
-        Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string()
+/// Run the configured burst rules and apply their non-blocking policies.
+fn apply_burst_policies(packet: &AiTcpPacket) -> Decision {
+    let burst = crate::config::CONFIG.read().unwrap().burst.clone();
+    let decision = BURST.lock().unwrap().evaluate(packet, &burst.rules);
+
+    for t in &decision.triggers {
+        METRICS.burst_detected(&t.rule, t.action);
+        match t.action {
+            BurstAction::Log => warn!(
+                "AI/Burst PUT detected [{}]: {} hits/{}s for {}",
+                t.rule, t.count, t.window_secs, t.key
+            ),
+            BurstAction::Throttle => warn!(
+                "⏳ [BURST:{}] Throttling {} ({} hits/{}s)",
+                t.rule, t.key, t.count, t.window_secs
+            ),
+            BurstAction::Quarantine => error!(
+                "🚧 [BURST:{}] Quarantined key {} of {} ({} hits/{}s for {})",
+                t.rule, packet.source_public_key, packet.source_p_address, t.count, t.window_secs, t.key
+            ),
+            BurstAction::Alert if !t.alert => {}
+            BurstAction::Alert => {
+                error!(
+                    "🚨 [BURST:{}] Alerting auditor {}: {} hits/{}s for {}",
+                    t.rule, burst.auditor_p_address, t.count, t.window_secs, t.key
+                );
+                crate::inbox::enqueue(crate::burst::alert_packet(t, packet, &burst.auditor_p_address));
+            }
+        }
     }
+    decision
 }
 
-/// Handle POST /send_packet
-pub async fn handle_send(packet: Packet) -> Result<impl warp::Reply, warp::Rejection> {
+/// Handle POST /send
+pub async fn handle_send(Json(packet): Json<AiTcpPacket>) -> (StatusCode, String) {
     info!(
         "🔵 [SEND] Received POST: from_public_key={}, to={}",
         packet.source_p_address, packet.destination_p_address
     );
-    info!(
-        "DEBUG: packet.destination_p_address = {:?}",
-        packet.destination_p_address
-    );
+    METRICS.packet_received("http");
 
-    let valid = crate::p_signature_validator::validate(&packet);
+    let valid = crate::p_signature_validator::validate_packet(&packet);
     if !valid {
         error!("❌ Invalid signature from {}", packet.source_p_address);
-        return Ok(warp::reply::with_status(
-            "Forbidden",
-            warp::http::StatusCode::FORBIDDEN,
-        ));
+        METRICS.packet_rejected("bad_signature");
+        METRICS.signature_failure("http");
+        return (StatusCode::FORBIDDEN, "Forbidden".to_string());
+    }
+
+    deliver(packet).await
+}
+
+/// Route an already authenticated packet: `gpt://<name>` goes to the
+/// configured LLM backend, everything else is queued in the destination inbox.
+/// Every transport ends up here, so keys on the seed's revocation list and
+/// sources not bound to the signing key are refused here and nowhere else.
+pub async fn deliver(packet: AiTcpPacket) -> (StatusCode, String) {
+    if is_revoked_sender(&packet) {
+        error!("❌ Revoked sender {}", packet.source_p_address);
+        METRICS.packet_rejected("revoked_key");
+        return (StatusCode::FORBIDDEN, "Forbidden".to_string());
+    }
+    if let Some(reason) = source_binding_error(&packet) {
+        error!("❌ {} is not registered to key {}", packet.source_p_address, packet.source_public_key);
+        METRICS.packet_rejected(reason);
+        return (StatusCode::FORBIDDEN, "Forbidden".to_string());
     }
 
-    if packet.destination_p_address == "gpt://main" {
-        match crate::gpt_responder::gpt_log_and_respond(&packet).await {
+    let blocked = crate::config::CONFIG
+        .read()
+        .unwrap()
+        .firewall
+        .denies(&packet.source_p_address, &packet.source_public_key);
+    if blocked {
+        warn!("🚫 [FIREWALL] Dropped packet from {}", packet.source_p_address);
+        METRICS.packet_rejected("firewall");
+        return (StatusCode::FORBIDDEN, "Blocked".to_string());
+    }
+
+    let inspection = crate::inspect::inspect(&packet);
+    for (rule, verdict) in &inspection.hits {
+        warn!("🔎 [INSPECT:{}] {:?} packet from {}", rule, verdict, packet.source_p_address);
+    }
+    let inspect_flags = match inspection.verdict {
+        Verdict::Pass => 0,
+        Verdict::Flag => flags::INSPECT_FLAGGED,
+        Verdict::Drop => flags::INSPECT_DROPPED,
+    };
+    if inspection.verdict == Verdict::Drop {
+        let (dst_ip, dst_port) = parse_destination_endpoint(&packet.destination_p_address);
+        record_witness(&SendRequest::new(&packet, dst_ip, dst_port, inspect_flags));
+        METRICS.packet_rejected("inspection");
+        return (StatusCode::FORBIDDEN, "Rejected".to_string());
+    }
+
+    let decision = apply_burst_policies(&packet);
+    if decision.blocks() {
+        let (dst_ip, dst_port) = parse_destination_endpoint(&packet.destination_p_address);
+        let req = SendRequest::new(&packet, dst_ip, dst_port, decision.witness_flags() | inspect_flags);
+        record_witness(&req);
+        return if decision.quarantined {
+            METRICS.packet_rejected("quarantined");
+            (StatusCode::FORBIDDEN, "Quarantined".to_string())
+        } else {
+            METRICS.packet_rejected("burst");
+            (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests".to_string())
+        };
+    }
+
+    if let Some(name) = packet.destination_p_address.strip_prefix("gpt://") {
+        let Some(backend) = crate::gpt_responder::backend_config(name) else {
+            warn!("❓ [GPT] No backend configured for {}", packet.destination_p_address);
+            METRICS.packet_rejected("unknown_backend");
+            return (StatusCode::NOT_FOUND, "Unknown GPT backend".to_string());
+        };
+        let route_flags = flags::ROUTE_GPT | decision.witness_flags() | inspect_flags;
+
+        // Classify the upstream before the prompt leaves the daemon.
+        let upstream = match crate::gpt_responder::resolve_upstream(&backend).await {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                error!("❌ [GPT] Cannot resolve backend {}: {}", name, e);
+                METRICS.packet_rejected("gpt_error");
+                return (StatusCode::BAD_GATEWAY, "Upstream unavailable".to_string());
+            }
+        };
+        let (allowed, blocked): (Vec<_>, Vec<_>) = upstream
+            .into_iter()
+            .map(|addr| (addr, classify_ip(addr.ip())))
+            .partition(|(_, class)| !is_blocked(*class));
+        if allowed.is_empty() {
+            let (addr, class) = blocked[0];
+            warn!("🚫 [CLASS] Refusing {:?} upstream {} for {}", class, addr, packet.destination_p_address);
+            record_witness(&SendRequest::new(&packet, encode_ip(addr.ip()), addr.port(), route_flags).with_class(class));
+            METRICS.packet_rejected("endpoint_class");
+            return (StatusCode::FORBIDDEN, "Blocked upstream".to_string());
+        }
+        let upstream: Vec<SocketAddr> = allowed.iter().map(|(addr, _)| *addr).collect();
+
+        match crate::gpt_responder::gpt_log_and_respond(&packet, &upstream).await {
             Ok(resp_tuple) => {
                 let (resp_str, actual_socket_addr) = resp_tuple;
 
@@ -125,11 +273,10 @@ pub async fn handle_send(packet: Packet) -> Result<impl warp::Reply, warp::Rejec
 
                 let dst_ip = encode_ip(actual_socket_addr.ip());
                 let dst_port = actual_socket_addr.port();
-                let route_flags = 1;
 
-                let req = SendRequest::new(&packet, dst_ip, dst_port, route_flags);
-                record_witness(&req);
                 let class = classify_ip(actual_socket_addr.ip());
+                let req = SendRequest::new(&packet, dst_ip, dst_port, route_flags).with_class(class);
+                record_witness(&req);
                 match class {
                     EndpointClass::Local => info!("[CLASS] Local traffic to {}", actual_socket_addr),
                     EndpointClass::KnownTest => info!("[CLASS] Test endpoint (example.com) {}", actual_socket_addr),
@@ -137,33 +284,111 @@ pub async fn handle_send(packet: Packet) -> Result<impl warp::Reply, warp::Rejec
                     EndpointClass::Suspicious => error!("[CLASS] Suspicious endpoint {}", actual_socket_addr),
                     EndpointClass::Unknown => warn!("[CLASS] Unknown endpoint {}", actual_socket_addr),
                 }
-                detect_burst(req.dst_ip, req.dst_port);
+                METRICS.packet_forwarded("gpt");
 
-                Ok(warp::reply::with_status(
-                    resp_str.as_str(),
-                    warp::http::StatusCode::OK,
-                ))
+                (StatusCode::OK, resp_str)
             }
             Err(e) => {
                 error!("❌ [GPT] Failed to handle packet: {}", e);
-                Ok(warp::reply::with_status(
-                    "Internal Server Error",
-                    warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                ))
+                METRICS.packet_rejected("gpt_error");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
             }
         }
     } else {
-        error!("❌ Unsupported destination: {}", packet.destination_p_address);
-
         let (dst_ip, dst_port) = parse_destination_endpoint(&packet.destination_p_address);
-        let route_flags = 2;
-        let req = SendRequest::new(&packet, dst_ip, dst_port, route_flags);
+        let class = destination_ip(&packet.destination_p_address).map(classify_ip);
+        if let Some(class) = class.filter(|c| is_blocked(*c)) {
+            warn!("🚫 [CLASS] Refusing {:?} destination {}", class, packet.destination_p_address);
+            METRICS.packet_rejected("endpoint_class");
+            return (StatusCode::FORBIDDEN, "Blocked".to_string());
+        }
+        let route_flags = flags::ROUTE_INBOX | decision.witness_flags() | inspect_flags;
+        let mut req = SendRequest::new(&packet, dst_ip, dst_port, route_flags);
+        if let Some(class) = class {
+            req = req.with_class(class);
+        }
         record_witness(&req);
-        detect_burst(req.dst_ip, req.dst_port);
 
-        Ok(warp::reply::with_status(
-            "Not Implemented",
-            warp::http::StatusCode::NOT_IMPLEMENTED,
-        ))
+        info!("📥 Queued packet for {}", packet.destination_p_address);
+        crate::inbox::enqueue(packet);
+        METRICS.packet_forwarded("inbox");
+
+        (StatusCode::OK, "packet_queued".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{LlmBackendConfig, LlmBackendKind};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// OpenAI-compatible backend on `ip` counting the requests it receives.
+    async fn backend(ip: &str) -> (SocketAddr, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({
+                    "id": "cmpl-1", "object": "chat.completion", "created": 0, "model": "m",
+                    "choices": [{ "index": 0, "message": { "role": "assistant", "content": "ok" } }]
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind((ip, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (addr, hits)
+    }
+
+    fn gpt_packet(backend: &str) -> AiTcpPacket {
+        AiTcpPacket {
+            source: "10.0.0.1/24".into(),
+            destination: format!("gpt://{}", backend),
+            version: 1,
+            source_p_address: "10.0.0.1/24".into(),
+            destination_p_address: format!("gpt://{}", backend),
+            source_public_key: "ab".repeat(32),
+            sequence: 1,
+            timestamp_utc: 0,
+            payload_type: String::new(),
+            payload: "hello".into(),
+            signature: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn blocked_upstreams_never_receive_the_prompt() {
+        let (blocked, blocked_hits) = backend("127.0.0.2").await;
+        let (allowed, allowed_hits) = backend("127.0.0.1").await;
+        {
+            let mut config = crate::config::CONFIG.write().unwrap();
+            config.gpt_log.enabled = false;
+            config.classifier.suspicious.push("127.0.0.2/32".into());
+            for (name, addr) in [("blocked_upstream", blocked), ("allowed_upstream", allowed)] {
+                let backend = LlmBackendConfig {
+                    kind: LlmBackendKind::Openai,
+                    base_url: format!("http://{}/v1", addr),
+                    api_key_env: None,
+                    ..Default::default()
+                };
+                config.gpt_backends.insert(name.into(), backend);
+            }
+        }
+        crate::ip_classifier::refresh();
+
+        let (status, _) = deliver(gpt_packet("blocked_upstream")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(blocked_hits.load(Ordering::SeqCst), 0);
+
+        let (status, reply) = deliver(gpt_packet("allowed_upstream")).await;
+        assert_eq!((status, reply.as_str()), (StatusCode::OK, "ok"));
+        assert_eq!(allowed_hits.load(Ordering::SeqCst), 1);
     }
 }
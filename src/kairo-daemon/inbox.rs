@@ -0,0 +1,76 @@
+use kairo_lib::packet::AiTcpPacket;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use axum::extract::Path;
+use axum::Json;
+
+/// Packets queued per destination P address until the agent polls `/receive`.
+pub(crate) static INBOXES: Lazy<Mutex<HashMap<String, VecDeque<AiTcpPacket>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn enqueue(packet: AiTcpPacket) {
+    let mut inboxes = INBOXES.lock().unwrap();
+    inboxes
+        .entry(packet.destination_p_address.clone())
+        .or_default()
+        .push_back(packet);
+}
+
+pub fn drain(p_address: &str) -> Vec<AiTcpPacket> {
+    let mut inboxes = INBOXES.lock().unwrap();
+    inboxes
+        .remove(p_address)
+        .map(Vec::from)
+        .unwrap_or_default()
+}
+
+/// Copy of the queued packets without removing them.
+pub fn peek(p_address: &str) -> Vec<AiTcpPacket> {
+    let inboxes = INBOXES.lock().unwrap();
+    inboxes
+        .get(p_address)
+        .map(|q| q.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Drop all queued packets for `p_address`, returning how many were removed.
+pub fn purge(p_address: &str) -> usize {
+    drain(p_address).len()
+}
+
+/// Copy of every inbox, for checkpointing.
+pub fn snapshot() -> HashMap<String, Vec<AiTcpPacket>> {
+    let inboxes = INBOXES.lock().unwrap();
+    inboxes
+        .iter()
+        .map(|(k, v)| (k.clone(), v.iter().cloned().collect()))
+        .collect()
+}
+
+/// Append checkpointed packets in front of anything queued since startup.
+pub fn restore(saved: HashMap<String, Vec<AiTcpPacket>>) {
+    let mut inboxes = INBOXES.lock().unwrap();
+    for (p_address, packets) in saved {
+        let queue = inboxes.entry(p_address).or_default();
+        for packet in packets.into_iter().rev() {
+            queue.push_front(packet);
+        }
+    }
+}
+
+/// Current queue length per destination.
+pub fn depths() -> Vec<(String, usize)> {
+    let inboxes = INBOXES.lock().unwrap();
+    inboxes.iter().map(|(k, v)| (k.clone(), v.len())).collect()
+}
+
+/// Handle GET /receive/:p_address
+pub async fn handle_receive(Path(p_address): Path<String>) -> Json<Vec<AiTcpPacket>> {
+    let messages = drain(&p_address);
+    if !messages.is_empty() {
+        println!("Delivered {} packets to {}", messages.len(), p_address);
+    }
+    Json(messages)
+}
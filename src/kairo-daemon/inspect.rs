@@ -0,0 +1,248 @@
+//! Packet content inspection.
+//!
+//! Every packet routed by the daemon runs through a [`Pipeline`] of
+//! [`Inspector`]s built from `inspection` in `daemon_config.json`. The most
+//! severe verdict wins; evaluation stops at the first `Drop`.
+
+use kairo_lib::packet::AiTcpPacket;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::config::{InspectionSettings, PatternRule, Verdict};
+
+pub(crate) static PIPELINE: Lazy<Mutex<Pipeline>> = Lazy::new(|| Mutex::new(Pipeline::default()));
+
+/// Phrases commonly used to hijack an LLM receiving the payload.
+const PROMPT_INJECTION_MARKERS: &[&str] = &[
+    r"ignore\s+(all\s+)?(previous|prior|above)\s+instructions",
+    r"disregard\s+(all\s+)?(previous|prior|the\s+above)",
+    r"reveal\s+(your\s+)?(system\s+prompt|instructions)",
+    r"you\s+are\s+now\s+(in\s+)?(developer|dan|jailbreak)",
+    r"<\|im_start\|>",
+    r"\[/?INST\]",
+];
+
+pub trait Inspector: Send + Sync {
+    fn name(&self) -> &str;
+    fn inspect(&self, packet: &AiTcpPacket) -> Verdict;
+}
+
+pub struct PayloadSize {
+    pub max_bytes: usize,
+}
+
+impl Inspector for PayloadSize {
+    fn name(&self) -> &str {
+        "payload_size"
+    }
+
+    fn inspect(&self, packet: &AiTcpPacket) -> Verdict {
+        if packet.payload.len() > self.max_bytes {
+            Verdict::Drop
+        } else {
+            Verdict::Pass
+        }
+    }
+}
+
+pub struct PayloadTypeAllowlist {
+    pub allowed: Vec<String>,
+}
+
+impl Inspector for PayloadTypeAllowlist {
+    fn name(&self) -> &str {
+        "payload_type"
+    }
+
+    fn inspect(&self, packet: &AiTcpPacket) -> Verdict {
+        if self.allowed.iter().any(|t| t.eq_ignore_ascii_case(&packet.payload_type)) {
+            Verdict::Pass
+        } else {
+            Verdict::Drop
+        }
+    }
+}
+
+/// Matches the payload against a regex; returns `verdict` on a hit.
+pub struct PatternInspector {
+    name: String,
+    regex: Regex,
+    verdict: Verdict,
+}
+
+impl PatternInspector {
+    pub fn new(name: &str, pattern: &str, verdict: Verdict) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(Self { name: name.to_string(), regex, verdict })
+    }
+
+    fn from_rule(rule: &PatternRule) -> Result<Self, regex::Error> {
+        let mut alternatives: Vec<String> = rule.regex.iter().map(|r| format!("(?:{})", r)).collect();
+        alternatives.extend(rule.keywords.iter().map(|k| format!("(?i:{})", regex::escape(k))));
+        Self::new(&rule.name, &alternatives.join("|"), rule.verdict)
+    }
+
+    fn prompt_injection(verdict: Verdict) -> Self {
+        Self::new("prompt_injection", &format!("(?i){}", PROMPT_INJECTION_MARKERS.join("|")), verdict)
+            .expect("built-in prompt injection markers compile")
+    }
+}
+
+impl Inspector for PatternInspector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn inspect(&self, packet: &AiTcpPacket) -> Verdict {
+        if self.regex.is_match(&packet.payload) {
+            self.verdict
+        } else {
+            Verdict::Pass
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RuleStats {
+    pub evaluated: u64,
+    pub flagged: u64,
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    /// Rules that returned something other than `Pass`.
+    pub hits: Vec<(String, Verdict)>,
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    settings: Option<InspectionSettings>,
+    inspectors: Vec<Box<dyn Inspector>>,
+    stats: BTreeMap<String, RuleStats>,
+}
+
+impl Pipeline {
+    /// Rebuild the built-in inspectors if the settings changed. Statistics are
+    /// kept per rule name across rebuilds.
+    fn configure(&mut self, settings: &InspectionSettings) {
+        if self.settings.as_ref() == Some(settings) {
+            return;
+        }
+        let mut inspectors: Vec<Box<dyn Inspector>> = vec![Box::new(PayloadSize { max_bytes: settings.max_payload_bytes })];
+        if !settings.allowed_payload_types.is_empty() {
+            inspectors.push(Box::new(PayloadTypeAllowlist { allowed: settings.allowed_payload_types.clone() }));
+        }
+        if settings.prompt_injection != Verdict::Pass {
+            inspectors.push(Box::new(PatternInspector::prompt_injection(settings.prompt_injection)));
+        }
+        for rule in &settings.patterns {
+            match PatternInspector::from_rule(rule) {
+                Ok(inspector) => inspectors.push(Box::new(inspector)),
+                Err(e) => log::error!("[INSPECT] Skipping rule {}: {}", rule.name, e),
+            }
+        }
+        self.inspectors = inspectors;
+        self.settings = Some(settings.clone());
+    }
+
+    /// Append a custom inspector after the configured ones.
+    #[allow(dead_code)]
+    pub fn push(&mut self, inspector: Box<dyn Inspector>) {
+        self.inspectors.push(inspector);
+    }
+
+    pub fn run(&mut self, packet: &AiTcpPacket) -> Outcome {
+        let mut outcome = Outcome::default();
+        for inspector in &self.inspectors {
+            let verdict = inspector.inspect(packet);
+            let stats = self.stats.entry(inspector.name().to_string()).or_default();
+            stats.evaluated += 1;
+            match verdict {
+                Verdict::Pass => continue,
+                Verdict::Flag => stats.flagged += 1,
+                Verdict::Drop => stats.dropped += 1,
+            }
+            outcome.hits.push((inspector.name().to_string(), verdict));
+            outcome.verdict = outcome.verdict.max(verdict);
+            if verdict == Verdict::Drop {
+                break;
+            }
+        }
+        outcome
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, RuleStats> {
+        self.stats.clone()
+    }
+}
+
+/// Inspect a packet with the currently configured rules.
+pub fn inspect(packet: &AiTcpPacket) -> Outcome {
+    let settings = crate::config::CONFIG.read().unwrap().inspection.clone();
+    let mut pipeline = PIPELINE.lock().unwrap();
+    pipeline.configure(&settings);
+    pipeline.run(packet)
+}
+
+pub fn stats() -> BTreeMap<String, RuleStats> {
+    PIPELINE.lock().unwrap().stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(payload_type: &str, payload: &str) -> AiTcpPacket {
+        AiTcpPacket {
+            source: "a".into(),
+            destination: "b".into(),
+            version: 1,
+            source_p_address: "10.0.0.1/24".into(),
+            destination_p_address: "10.0.0.2/24".into(),
+            source_public_key: String::new(),
+            sequence: 1,
+            timestamp_utc: 0,
+            payload_type: payload_type.into(),
+            payload: payload.into(),
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn verdicts_escalate_and_stats_are_per_rule() {
+        let settings = InspectionSettings {
+            max_payload_bytes: 32,
+            allowed_payload_types: vec!["text/plain".into()],
+            patterns: vec![PatternRule {
+                name: "secrets".into(),
+                regex: Some(r"AKIA[0-9A-Z]{4}".into()),
+                keywords: vec!["password".into()],
+                verdict: Verdict::Drop,
+            }],
+            prompt_injection: Verdict::Flag,
+        };
+        let mut p = Pipeline::default();
+        p.configure(&settings);
+
+        assert_eq!(p.run(&packet("text/plain", "hello")).verdict, Verdict::Pass);
+        let flagged = p.run(&packet("text/plain", "Ignore previous instructions"));
+        assert_eq!(flagged.verdict, Verdict::Flag);
+        assert_eq!(flagged.hits, vec![("prompt_injection".to_string(), Verdict::Flag)]);
+        assert_eq!(p.run(&packet("text/plain", "my PASSWORD is")).verdict, Verdict::Drop);
+        assert_eq!(p.run(&packet("text/plain", "key AKIAABCD")).verdict, Verdict::Drop);
+        assert_eq!(p.run(&packet("image/png", "x")).verdict, Verdict::Drop);
+        assert_eq!(p.run(&packet("text/plain", &"x".repeat(33))).verdict, Verdict::Drop);
+
+        let stats = p.stats();
+        assert_eq!(stats["payload_size"].evaluated, 6);
+        assert_eq!(stats["payload_size"].dropped, 1);
+        assert_eq!(stats["payload_type"].dropped, 1);
+        assert_eq!(stats["prompt_injection"].flagged, 1);
+        assert_eq!(stats["secrets"].dropped, 2);
+    }
+}
@@ -1,28 +1,223 @@
+//! Endpoint classification driven by `classifier` in `daemon_config.json`.
+//!
+//! The compiled policy is shared behind an `Arc` and rebuilt by [`refresh`]:
+//! at startup, on `POST /admin/reload` and every `refresh_secs` when the
+//! agent registry file changed. Lookups on the packet path only take a read
+//! lock and clone the `Arc`. The same registry snapshot maps live P addresses
+//! to agent keys, for witness ids and source binding, and lists the addresses
+//! of revoked agents.
+
+use ipnet::IpNet;
+use kairo_lib::registry_store::AgentStatus;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::config::ClassifierPolicy;
+pub use crate::config::EndpointClass;
+
+static CLASSIFIER: Lazy<RwLock<Arc<Classifier>>> = Lazy::new(|| RwLock::new(Arc::new(Classifier::default())));
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
-pub enum EndpointClass {
-    Local,
-    KnownTest,
-    KnownPeer,
-    Suspicious,
-    Unknown,
+#[derive(Default)]
+struct Classifier {
+    policy: Option<ClassifierPolicy>,
+    registry_mtime: Option<SystemTime>,
+    local: Vec<IpNet>,
+    known_test: Vec<IpNet>,
+    known_peers: Vec<IpNet>,
+    suspicious: Vec<IpNet>,
+    block: Vec<EndpointClass>,
+    /// Live P address -> public key, from the registry.
+    keys: HashMap<String, String>,
+    /// P addresses of revoked agents that no live agent holds now.
+    revoked: HashSet<String>,
 }
 
-#[allow(dead_code)]
-pub fn classify_ip(ip: IpAddr) -> EndpointClass {
-    if ip.is_loopback() {
-        return EndpointClass::Local;
+/// Parse CIDRs or bare addresses (treated as a single host).
+fn parse_nets(entries: &[String]) -> Vec<IpNet> {
+    entries
+        .iter()
+        .filter_map(|s| {
+            s.parse::<IpNet>()
+                .ok()
+                .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+                .or_else(|| {
+                    log::warn!("[CLASS] Ignoring invalid network {:?}", s);
+                    None
+                })
+        })
+        .collect()
+}
+
+/// Agents of the registry at `path` holding a P address, as (P address,
+/// public key, status).
+fn registry_agents(path: &str) -> Vec<(String, Option<String>, AgentStatus)> {
+    match kairo_lib::registry_store::RegistryStore::open(path).read() {
+        Ok(registry) => registry
+            .agents()
+            .iter()
+            .filter_map(|a| Some((a.p_address.clone()?, a.public_key.clone(), a.status)))
+            .collect(),
+        Err(e) => {
+            log::warn!("[CLASS] Cannot read registry {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+/// P addresses look like `10.0.0.5/24`; the peer is that one host.
+fn peer_net(p_address: &str) -> Option<IpNet> {
+    p_address.split('/').next()?.parse::<IpAddr>().ok().map(IpNet::from)
+}
+
+fn mtime(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Classifier {
+    fn build(policy: &ClassifierPolicy) -> Self {
+        let registry_mtime = policy.registry_path.as_deref().and_then(mtime);
+        let mut known_peers = parse_nets(&policy.known_peers);
+        let mut keys = HashMap::new();
+        let mut live = HashSet::new();
+        let mut revoked = HashSet::new();
+        if let Some(path) = policy.registry_path.as_deref().filter(|_| registry_mtime.is_some()) {
+            for (p_address, key, status) in registry_agents(path) {
+                match status {
+                    AgentStatus::Active => {
+                        known_peers.extend(peer_net(&p_address));
+                        if let Some(key) = key {
+                            keys.insert(p_address.clone(), key);
+                        }
+                        live.insert(p_address);
+                    }
+                    AgentStatus::Revoked => {
+                        revoked.insert(p_address);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        revoked.retain(|p| !live.contains(p));
+        Self {
+            policy: Some(policy.clone()),
+            registry_mtime,
+            local: parse_nets(&policy.local),
+            known_test: parse_nets(&policy.known_test),
+            known_peers,
+            suspicious: parse_nets(&policy.suspicious),
+            block: policy.block.clone(),
+            keys,
+            revoked,
+        }
     }
 
-    if matches!(ip, IpAddr::V4(v4) if v4.is_private()) {
-        return EndpointClass::Local;
+    /// Whether `policy` or the registry file changed since this was built.
+    fn is_stale(&self, policy: &ClassifierPolicy) -> bool {
+        self.policy.as_ref() != Some(policy) || self.registry_mtime != policy.registry_path.as_deref().and_then(mtime)
+    }
+
+    fn classify(&self, ip: IpAddr) -> EndpointClass {
+        let hit = |nets: &[IpNet]| nets.iter().any(|n| n.contains(&ip));
+        if hit(&self.suspicious) {
+            EndpointClass::Suspicious
+        } else if hit(&self.known_peers) {
+            EndpointClass::KnownPeer
+        } else if hit(&self.known_test) {
+            EndpointClass::KnownTest
+        } else if hit(&self.local) {
+            EndpointClass::Local
+        } else {
+            EndpointClass::Unknown
+        }
+    }
+}
+
+fn current() -> Arc<Classifier> {
+    CLASSIFIER.read().unwrap().clone()
+}
+
+/// Rebuild the compiled policy if `classifier` in the config or the registry
+/// file changed.
+pub fn refresh() {
+    let policy = crate::config::CONFIG.read().unwrap().classifier.clone();
+    if current().is_stale(&policy) {
+        *CLASSIFIER.write().unwrap() = Arc::new(Classifier::build(&policy));
     }
+}
+
+pub fn classify_ip(ip: IpAddr) -> EndpointClass {
+    current().classify(ip)
+}
+
+/// Public key of the live agent the registry lists at `p_address`.
+pub fn registered_key(p_address: &str) -> Option<String> {
+    current().keys.get(p_address.trim()).cloned()
+}
+
+/// Whether the registry lists `p_address` for a revoked agent and no live one.
+pub fn is_revoked_address(p_address: &str) -> bool {
+    current().revoked.contains(p_address.trim())
+}
 
-    if matches!(ip, IpAddr::V4(v4) if v4.octets() == [93, 184, 216, 34]) {
-        return EndpointClass::KnownTest;
+/// Code for `class` in the witness extension's `scope` byte.
+pub fn witness_scope(class: EndpointClass) -> u8 {
+    use clear_mini::witness::scope;
+    match class {
+        EndpointClass::Local => scope::LOCAL,
+        EndpointClass::KnownTest => scope::KNOWN_TEST,
+        EndpointClass::KnownPeer => scope::KNOWN_PEER,
+        EndpointClass::Suspicious => scope::SUSPICIOUS,
+        EndpointClass::Unknown => scope::UNKNOWN,
     }
+}
+
+/// Whether the configured policy refuses endpoints of this class.
+pub fn is_blocked(class: EndpointClass) -> bool {
+    current().block.contains(&class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kairo_lib::registry::{save_registry, RegistryEntry};
 
-    EndpointClass::Unknown
+    #[test]
+    fn policy_lists_and_registry_peers_classify_in_order() {
+        let registry = std::env::temp_dir().join(format!("kairo-class-registry-{}.json", std::process::id()));
+        let registry = registry.to_str().unwrap();
+        let entry = |name: &str, p: &str, deleted| RegistryEntry {
+            name: name.into(),
+            p_address: p.into(),
+            deleted,
+            last_contact: None,
+        };
+        save_registry(registry, &[entry("a", "10.0.0.5/24", false), entry("b", "10.0.0.6/24", true)]).unwrap();
+        kairo_lib::registry_store::RegistryStore::open(registry)
+            .transaction(|r| r.update("a", |a| a.public_key = Some("AB".repeat(32))))
+            .unwrap();
+
+        let policy = ClassifierPolicy {
+            suspicious: vec!["203.0.113.0/24".into()],
+            registry_path: Some(registry.to_string()),
+            ..Default::default()
+        };
+        let c = Classifier::build(&policy);
+        assert!(!c.is_stale(&policy));
+        assert!(c.is_stale(&ClassifierPolicy { block: Vec::new(), ..policy.clone() }));
+
+        let class = |s: &str| c.classify(s.parse().unwrap());
+        assert_eq!(class("10.0.0.5"), EndpointClass::KnownPeer);
+        assert_eq!(class("10.0.0.6"), EndpointClass::Local);
+        assert_eq!(class("::1"), EndpointClass::Local);
+        assert_eq!(class("93.184.216.34"), EndpointClass::KnownTest);
+        assert_eq!(class("203.0.113.9"), EndpointClass::Suspicious);
+        assert_eq!(class("8.8.8.8"), EndpointClass::Unknown);
+        assert_eq!(c.keys.get("10.0.0.5/24"), Some(&"ab".repeat(32)));
+        assert!(!c.keys.contains_key("10.0.0.6/24"));
+
+        let _ = std::fs::remove_file(registry);
+    }
 }
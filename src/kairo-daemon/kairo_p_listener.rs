@@ -1,71 +1,599 @@
-use tokio::net::TcpListener;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use serde_json;
+//! Native KAIRO-P listener.
+//!
+//! Each frame is a size-prefixed FlatBuffers `AITcpPacket` (u32 little-endian
+//! length followed by the buffer, as written by
+//! `finish_size_prefixed_aitcp_packet_buffer`). The `header` field carries a
+//! JSON `RouteHeader`, `ephemeral_key` is the sender's Ed25519 public key and
+//! `signature` covers [`frame_message`]: sequence, header and payload, so none
+//! of them can be changed or replayed under another sequence number. An
+//! optional `footer` holds an ISE stamp (`clear_mini::ise::Sig`) over
+//! [`ise_canon`], checked against the node keyring; stamps are required when
+//! the listener is bound to a non-loopback address. Every frame is answered
+//! with a binary `Ack` frame on the same connection.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clear_mini::ise::{IseError, Sig, SIG_SIZE};
+use ed25519_dalek::{Signature, VerifyingKey};
+use kairo_core::ai_tcp_packet_generated::aitcp as fb;
+use kairo_core::signature::verify_ed25519;
 use kairo_lib::packet::AiTcpPacket;
-use log::info;
-use reqwest;
+use log::{info, warn};
+
+use crate::metrics::METRICS;
+use crate::peers;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8081";
+/// Frames larger than this are rejected and the connection is closed.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+const SUPPORTED_VERSION: u8 = 1;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckStatus {
+    Ok = 0,
+    Malformed = 1,
+    Unsupported = 2,
+    BadSignature = 3,
+    Replay = 4,
+    DeliveryFailed = 5,
+}
+
+impl AckStatus {
+    #[allow(dead_code)]
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::Ok),
+            1 => Some(Self::Malformed),
+            2 => Some(Self::Unsupported),
+            3 => Some(Self::BadSignature),
+            4 => Some(Self::Replay),
+            5 => Some(Self::DeliveryFailed),
+            _ => None,
+        }
+    }
+}
+
+/// Reply frame: `[len: u32 LE][status: u8][sequence: u64 LE][message: UTF-8]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ack {
+    pub status: AckStatus,
+    pub sequence: u64,
+    pub message: String,
+}
+
+impl Ack {
+    fn new(status: AckStatus, sequence: u64, message: impl Into<String>) -> Self {
+        Self { status, sequence, message: message.into() }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let body_len = 1 + 8 + self.message.len();
+        let mut out = Vec::with_capacity(4 + body_len);
+        out.extend_from_slice(&(body_len as u32).to_le_bytes());
+        out.push(self.status as u8);
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(self.message.as_bytes());
+        out
+    }
+
+    /// Decode an ack body (without the length prefix).
+    #[allow(dead_code)]
+    pub fn decode(body: &[u8]) -> Option<Self> {
+        if body.len() < 9 {
+            return None;
+        }
+        let status = AckStatus::from_u8(body[0])?;
+        let sequence = u64::from_le_bytes(body[1..9].try_into().ok()?);
+        let message = String::from_utf8(body[9..].to_vec()).ok()?;
+        Some(Self { status, sequence, message })
+    }
+}
+
+/// Routing metadata carried in the unencrypted `header` field.
+#[derive(Debug, Deserialize)]
+pub struct RouteHeader {
+    pub source_p_address: String,
+    pub destination_p_address: String,
+    #[serde(default)]
+    pub payload_type: String,
+}
+
+/// A packet that passed structural and signature checks, before replay checks.
+struct VerifiedPacket {
+    sender: [u8; 32],
+    packet: AiTcpPacket,
+    /// ISE stamp from `footer` and the bytes it must cover.
+    stamp: Option<(Sig, Vec<u8>)>,
+}
+
+fn extend_frame(out: &mut Vec<u8>, sequence: u64, header: &[u8], payload: &[u8]) {
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    out.extend_from_slice(header);
+    out.extend_from_slice(payload);
+}
+
+/// Bytes the Ed25519 `signature` covers: sequence, route header and payload.
+pub fn frame_message(sequence: u64, header: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut message = b"KAIRO-P-v1\n".to_vec();
+    extend_frame(&mut message, sequence, header, payload);
+    message
+}
+
+/// Bytes an ISE stamp in `footer` covers: sender key, sequence, route header
+/// and payload.
+pub fn ise_canon(sender: &[u8; 32], sequence: u64, header: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut canon = b"KAIRO-P-ISE-v1\n".to_vec();
+    canon.extend_from_slice(sender);
+    extend_frame(&mut canon, sequence, header, payload);
+    canon
+}
+
+/// Highest accepted sequence per sender key; later frames must increase it.
+#[derive(Default)]
+pub struct SequenceTracker {
+    last: Mutex<HashMap<[u8; 32], u64>>,
+}
+
+impl SequenceTracker {
+    /// Replay windows keyed by hex sender key, for checkpointing.
+    pub fn snapshot(&self) -> HashMap<String, u64> {
+        let last = self.last.lock().unwrap();
+        last.iter().map(|(k, v)| (hex::encode(k), *v)).collect()
+    }
 
-pub async fn run_listener() -> std::io::Result<()> {
-    let listener = TcpListener::bind("0.0.0.0:8080").await?;
-    println!("KAIRO-P listening on 8080...");
+    pub fn restore(&self, saved: &HashMap<String, u64>) {
+        let mut last = self.last.lock().unwrap();
+        for (key_hex, seq) in saved {
+            if let Some(key) = hex::decode(key_hex).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) {
+                let entry = last.entry(key).or_insert(0);
+                *entry = (*entry).max(*seq);
+            }
+        }
+    }
+
+    fn accept(&self, sender: [u8; 32], sequence: u64) -> bool {
+        let mut last = self.last.lock().unwrap();
+        match last.get(&sender) {
+            Some(&prev) if sequence <= prev => false,
+            _ => {
+                last.insert(sender, sequence);
+                true
+            }
+        }
+    }
+}
+
+fn verify_frame(body: &[u8]) -> Result<VerifiedPacket, Ack> {
+    let pkt = fb::root_as_aitcp_packet(body)
+        .map_err(|e| Ack::new(AckStatus::Malformed, 0, format!("invalid flatbuffer: {}", e)))?;
+
+    let seq_vec = pkt.encrypted_sequence_id();
+    let sequence = <[u8; 8]>::try_from(seq_vec.bytes())
+        .map(u64::from_le_bytes)
+        .map_err(|_| Ack::new(AckStatus::Malformed, 0, "sequence id must be 8 bytes"))?;
+
+    if pkt.version() != SUPPORTED_VERSION {
+        return Err(Ack::new(
+            AckStatus::Unsupported,
+            sequence,
+            format!("unsupported version {}", pkt.version()),
+        ));
+    }
+
+    let header_bytes = pkt
+        .header()
+        .ok_or_else(|| Ack::new(AckStatus::Malformed, sequence, "missing route header"))?;
+    let header: RouteHeader = serde_json::from_slice(header_bytes.bytes())
+        .map_err(|e| Ack::new(AckStatus::Malformed, sequence, format!("invalid route header: {}", e)))?;
+
+    let sender = <[u8; 32]>::try_from(pkt.ephemeral_key().bytes())
+        .map_err(|_| Ack::new(AckStatus::Malformed, sequence, "sender key must be 32 bytes"))?;
+    let verifying_key = VerifyingKey::from_bytes(&sender)
+        .map_err(|_| Ack::new(AckStatus::BadSignature, sequence, "invalid sender key"))?;
+    let signature = Signature::from_slice(pkt.signature().bytes())
+        .map_err(|_| Ack::new(AckStatus::Malformed, sequence, "signature must be 64 bytes"))?;
+
+    let payload = pkt.encrypted_payload().bytes();
+    let message = frame_message(sequence, header_bytes.bytes(), payload);
+    if verify_ed25519(&verifying_key, &message, &signature).is_err() {
+        METRICS.signature_failure("kairo_p");
+        return Err(Ack::new(AckStatus::BadSignature, sequence, "signature verification failed"));
+    }
+    let stamp = match pkt.footer().map(|f| <&[u8; SIG_SIZE]>::try_from(f.bytes())) {
+        None => None,
+        Some(Ok(bytes)) => Some((Sig::from_bytes(bytes), ise_canon(&sender, sequence, header_bytes.bytes(), payload))),
+        Some(Err(_)) => {
+            return Err(Ack::new(AckStatus::Malformed, sequence, format!("ISE stamp must be {} bytes", SIG_SIZE)));
+        }
+    };
+    let payload = String::from_utf8(payload.to_vec())
+        .map_err(|_| Ack::new(AckStatus::Malformed, sequence, "payload is not UTF-8"))?;
+
+    let timestamp_utc = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    Ok(VerifiedPacket {
+        sender,
+        packet: AiTcpPacket {
+            source: header.source_p_address.clone(),
+            destination: header.destination_p_address.clone(),
+            version: pkt.version(),
+            source_p_address: header.source_p_address,
+            destination_p_address: header.destination_p_address,
+            source_public_key: hex::encode(sender),
+            sequence,
+            timestamp_utc,
+            payload_type: header.payload_type,
+            payload,
+            signature: hex::encode(signature.to_bytes()),
+        },
+        stamp,
+    })
+}
+
+/// Check the frame's ISE stamp, consuming its nonce. Without a stamp the
+/// frame is refused if `require` or `ise.require_on_kairo_p` is set.
+fn check_stamp(verified: &VerifiedPacket, require: bool) -> Result<(), Ack> {
+    let sequence = verified.packet.sequence;
+    let Some((sig, canon)) = &verified.stamp else {
+        if require || crate::config::CONFIG.read().unwrap().ise.require_on_kairo_p {
+            return Err(Ack::new(AckStatus::BadSignature, sequence, "missing ISE stamp"));
+        }
+        return Ok(());
+    };
+    crate::p_signature_validator::validate(sig, canon).map_err(|e| {
+        let status = if e == IseError::Replayed { AckStatus::Replay } else { AckStatus::BadSignature };
+        Ack::new(status, sequence, e.to_string())
+    })
+}
+
+impl AckStatus {
+    /// Label used for `kairo_packets_rejected_total`.
+    fn reject_reason(self) -> &'static str {
+        match self {
+            Self::Ok => "none",
+            Self::Malformed => "malformed",
+            Self::Unsupported => "unsupported_version",
+            Self::BadSignature => "bad_signature",
+            Self::Replay => "replay",
+            Self::DeliveryFailed => "delivery_failed",
+        }
+    }
+}
 
+/// Validate one frame body and deliver it in-process, producing the ack to
+/// send back together with the authenticated source P address, if any.
+async fn process_frame(body: &[u8], tracker: &SequenceTracker, require_stamp: bool) -> (Ack, Option<String>) {
+    METRICS.packet_received("kairo_p");
+    let verified = match verify_frame(body) {
+        Ok(v) => v,
+        Err(ack) => return (ack, None),
+    };
+    let sequence = verified.packet.sequence;
+    let source = Some(verified.packet.source_p_address.clone());
+    if let Err(ack) = check_stamp(&verified, require_stamp) {
+        return (ack, source);
+    }
+    if !tracker.accept(verified.sender, sequence) {
+        return (Ack::new(AckStatus::Replay, sequence, "sequence already seen"), source);
+    }
+    let (status, message) = crate::handle_send::deliver(verified.packet).await;
+    let ack = if status.is_success() {
+        Ack::new(AckStatus::Ok, sequence, message)
+    } else {
+        Ack::new(AckStatus::DeliveryFailed, sequence, message)
+    };
+    (ack, source)
+}
+
+/// Read one length-prefixed frame body; `None` on clean EOF.
+async fn read_frame(socket: &mut TcpStream) -> std::io::Result<Option<Result<Vec<u8>, usize>>> {
+    let len = match socket.read_u32_le().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if len > MAX_FRAME_LEN {
+        return Ok(Some(Err(len)));
+    }
+    let mut body = vec![0u8; len];
+    socket.read_exact(&mut body).await?;
+    Ok(Some(Ok(body)))
+}
+
+async fn serve_connection(
+    mut socket: TcpStream,
+    addr: SocketAddr,
+    tracker: Arc<SequenceTracker>,
+    require_stamp: bool,
+) -> std::io::Result<()> {
+    let peer = peers::register(addr);
     loop {
-        let (mut socket, addr) = listener.accept().await?;
-        info!("Accepted from {:?}", addr);
+        let frame = tokio::select! {
+            frame = read_frame(&mut socket) => frame?,
+            _ = peer.disconnect.notified() => {
+                info!("[KAIRO-P] Force-disconnecting peer {} ({})", peer.id, addr);
+                return Ok(());
+            }
+        };
+        let body = match frame {
+            None => return Ok(()),
+            Some(Ok(body)) => body,
+            Some(Err(len)) => {
+                METRICS.packet_rejected(AckStatus::Malformed.reject_reason());
+                let ack = Ack::new(AckStatus::Malformed, 0, format!("frame of {} bytes exceeds limit", len));
+                socket.write_all(&ack.encode()).await?;
+                return Ok(());
+            }
+        };
+
+        let (ack, source) = process_frame(&body, &tracker, require_stamp).await;
+        peers::record_frame(peer.id, source.as_deref());
+        if ack.status != AckStatus::Ok {
+            METRICS.packet_rejected(ack.status.reject_reason());
+            warn!("[KAIRO-P] Rejected frame seq={}: {:?} ({})", ack.sequence, ack.status, ack.message);
+        }
+        socket.write_all(&ack.encode()).await?;
+    }
+}
 
+/// Longest pause after repeated `accept` failures such as `EMFILE`.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Accept connections on an already bound listener. Frames must carry an ISE
+/// stamp unless the listener is bound to a loopback address.
+pub async fn serve(listener: TcpListener, tracker: Arc<SequenceTracker>) -> std::io::Result<()> {
+    let require_stamp = !listener.local_addr()?.ip().is_loopback();
+    if require_stamp {
+        info!("[KAIRO-P] Non-loopback bind: frames without an ISE stamp are refused");
+    }
+    let mut backoff = Duration::from_millis(10);
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("[KAIRO-P] accept failed: {}; retrying in {:?}", e, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        backoff = Duration::from_millis(10);
+        let class = crate::ip_classifier::classify_ip(addr.ip());
+        if crate::ip_classifier::is_blocked(class) {
+            warn!("🚫 [KAIRO-P] Refused {:?} peer {:?}", class, addr);
+            METRICS.packet_rejected("endpoint_class");
+            continue;
+        }
+        info!("[KAIRO-P] Accepted from {:?} ({:?})", addr, class);
+        let tracker = tracker.clone();
         tokio::spawn(async move {
-            let mut buf = vec![0; 4096];
-            match socket.read(&mut buf).await {
-                Ok(n) if n == 0 => return,
-                Ok(n) => {
-                    let received_data = &buf[..n];
-                    let received_str = match std::str::from_utf8(received_data) {
-                        Ok(s) => s,
-                        Err(e) => {
-                            eprintln!("Failed to convert received data to UTF-8: {:?}", e);
-                            return;
-                        }
-                    };
-
-                    match serde_json::from_str::<AiTcpPacket>(received_str) {
-                        Ok(packet) => {
-                            // パケットをJSONにシリアライズ
-                            let _packet_json = serde_json::to_string(&packet).expect("Failed to serialize packet");
-
-                            // 内部的なHTTP POSTリクエストを送信
-                            let client = reqwest::Client::new();
-                            let res = client.post("http://127.0.0.1:3030/send")
-                                .json(&packet) // AiTcpPacketを直接JSONとして送信
-                                .send()
-                                .await;
-
-                            match res {
-                                Ok(response) => {
-                                    if response.status().is_success() {
-                                        let response_body = response.text().await.unwrap_or_else(|_| "{}".to_string());
-                                        info!("Successfully forwarded packet to daemon. Response: {}", response_body);
-                                        let _ = socket.write_all(response_body.as_bytes()).await; // デーモンからの応答をクライアントに返す
-                                    } else {
-                                        eprintln!("Failed to forward packet to daemon. Status: {:?}", response.status());
-                                        let _ = socket.write_all(b"ERROR: Daemon forwarding failed").await;
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!("Failed to send HTTP request to daemon: {:?}", e);
-                                    let _ = socket.write_all(b"ERROR: Internal forwarding failed").await;
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            eprintln!("Failed to deserialize packet: {:?} from {:?}", e, addr);
-                            let _ = socket.write_all(b"ERROR: Invalid packet format").await;
-                        }
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to read from socket: {:?}", e);
-                }
+            if let Err(e) = serve_connection(socket, addr, tracker, require_stamp).await {
+                warn!("[KAIRO-P] Connection {:?} closed with error: {}", addr, e);
             }
         });
     }
-}
\ No newline at end of file
+}
+
+pub async fn run_listener(addr: &str, tracker: Arc<SequenceTracker>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("KAIRO-P listening on {}...", addr);
+    serve(listener, tracker).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use flatbuffers::FlatBufferBuilder;
+
+    const HEADER: &[u8] = br#"{"source_p_address":"10.0.0.1/24","destination_p_address":"10.0.0.2/24"}"#;
+    const STAMPED_HEADER: &[u8] = br#"{"source_p_address":"10.0.0.1/24","destination_p_address":"10.0.0.3/24"}"#;
+    const SIGNED_HEADER: &[u8] = br#"{"source_p_address":"10.0.0.1/24","destination_p_address":"10.0.0.5/24"}"#;
+    const REVOKED_HEADER: &[u8] = br#"{"source_p_address":"10.0.0.1/24","destination_p_address":"10.0.0.4/24"}"#;
+
+    fn build_frame(key: &SigningKey, seq: u64, payload: &[u8], tamper: bool) -> Vec<u8> {
+        build_stamped_frame(key, seq, payload, tamper, HEADER, None)
+    }
+
+    fn build_stamped_frame(key: &SigningKey, seq: u64, payload: &[u8], tamper: bool, header: &[u8], stamp: Option<&Sig>) -> Vec<u8> {
+        let sig = key.sign(&frame_message(seq, header, payload)).to_bytes();
+        let sent_payload: &[u8] = if tamper { b"tampered" } else { payload };
+        encode_frame(key, seq, sent_payload, header, &sig, stamp)
+    }
+
+    fn encode_frame(key: &SigningKey, seq: u64, payload: &[u8], header: &[u8], sig: &[u8; 64], stamp: Option<&Sig>) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let args = fb::AITcpPacketArgs {
+            version: 1,
+            ephemeral_key: Some(builder.create_vector(key.verifying_key().as_bytes())),
+            nonce: Some(builder.create_vector(&[0u8; 12])),
+            encrypted_sequence_id: Some(builder.create_vector(&seq.to_le_bytes())),
+            encrypted_payload: Some(builder.create_vector(payload)),
+            signature: Some(builder.create_vector(sig)),
+            header: Some(builder.create_vector(header)),
+            payload: None,
+            footer: stamp.map(|s| builder.create_vector(&s.to_bytes())),
+        };
+        let root = fb::AITcpPacket::create(&mut builder, &args);
+        fb::finish_size_prefixed_aitcp_packet_buffer(&mut builder, root);
+        builder.finished_data().to_vec()
+    }
+
+    async fn roundtrip(stream: &mut TcpStream, frame: &[u8]) -> Ack {
+        stream.write_all(frame).await.unwrap();
+        let len = stream.read_u32_le().await.unwrap() as usize;
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await.unwrap();
+        Ack::decode(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn acks_valid_and_rejects_replayed_or_tampered_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let ack = roundtrip(&mut stream, &build_frame(&key, 1, b"hello", false)).await;
+        assert_eq!(ack.status, AckStatus::Ok);
+        assert_eq!(ack.message, "packet_queued");
+        let delivered = crate::inbox::drain("10.0.0.2/24");
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].payload, "hello");
+        assert_eq!(delivered[0].source_public_key, hex::encode(key.verifying_key().as_bytes()));
+
+        let ack = roundtrip(&mut stream, &build_frame(&key, 1, b"hello", false)).await;
+        assert_eq!(ack.status, AckStatus::Replay);
+
+        let ack = roundtrip(&mut stream, &build_frame(&key, 2, b"hello", true)).await;
+        assert_eq!(ack.status, AckStatus::BadSignature);
+
+        let ack = roundtrip(&mut stream, &[3, 0, 0, 0, 1, 2, 3]).await;
+        assert_eq!(ack.status, AckStatus::Malformed);
+    }
+
+    #[tokio::test]
+    async fn ise_stamps_are_checked_once_and_forgeries_rejected() {
+        crate::p_signature_validator::init_for_tests();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+
+        let key = SigningKey::from_bytes(&[8u8; 32]);
+        let sender = key.verifying_key().to_bytes();
+        let stamp = |seq: u64| crate::p_signature_validator::stamp(&ise_canon(&sender, seq, STAMPED_HEADER, b"hi")).unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let good = build_stamped_frame(&key, 1, b"hi", false, STAMPED_HEADER, Some(&stamp(1)));
+        assert_eq!(roundtrip(&mut stream, &good).await.status, AckStatus::Ok);
+        assert_eq!(crate::inbox::drain("10.0.0.3/24").len(), 1);
+
+        // The same stamped frame again: the ISE nonce is spent.
+        let ack = roundtrip(&mut stream, &good).await;
+        assert_eq!((ack.status, ack.message.as_str()), (AckStatus::Replay, "ISE nonce was already used"));
+
+        // A stamp lifted onto another frame, or with a forged MAC.
+        let lifted = build_stamped_frame(&key, 2, b"hi", false, STAMPED_HEADER, Some(&stamp(3)));
+        assert_eq!(roundtrip(&mut stream, &lifted).await.status, AckStatus::BadSignature);
+        let mut forged = stamp(4);
+        forged.sig[0] ^= 1;
+        let ack = roundtrip(&mut stream, &build_stamped_frame(&key, 4, b"hi", false, STAMPED_HEADER, Some(&forged))).await;
+        assert_eq!(ack.status, AckStatus::BadSignature);
+        assert!(crate::inbox::drain("10.0.0.3/24").is_empty());
+    }
+
+    #[tokio::test]
+    async fn frames_from_revoked_keys_are_not_delivered() {
+        let _cache = crate::revocations::tests::CACHE_LOCK.lock().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+
+        let key = SigningKey::from_bytes(&rand::random());
+        crate::revocations::tests::install_revoked(&[&key]);
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let frame = build_stamped_frame(&key, 1, b"hi", false, REVOKED_HEADER, None);
+        let ack = roundtrip(&mut stream, &frame).await;
+        assert_eq!((ack.status, ack.message.as_str()), (AckStatus::DeliveryFailed, "Forbidden"));
+        assert!(crate::inbox::drain("10.0.0.4/24").is_empty());
+    }
+
+    #[tokio::test]
+    async fn signature_covers_the_sequence_and_route_header() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+
+        let key = SigningKey::from_bytes(&rand::random());
+        let sig = key.sign(&frame_message(1, SIGNED_HEADER, b"hi")).to_bytes();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        assert_eq!(roundtrip(&mut stream, &encode_frame(&key, 1, b"hi", SIGNED_HEADER, &sig, None)).await.status, AckStatus::Ok);
+
+        // A captured frame replayed under a higher sequence number.
+        let bumped = roundtrip(&mut stream, &encode_frame(&key, 2, b"hi", SIGNED_HEADER, &sig, None)).await;
+        assert_eq!(bumped.status, AckStatus::BadSignature);
+        // The same frame rerouted by rewriting its header.
+        let rerouted = br#"{"source_p_address":"10.0.0.1/24","destination_p_address":"10.0.0.9/24"}"#;
+        let rewritten = roundtrip(&mut stream, &encode_frame(&key, 3, b"hi", rerouted, &sig, None)).await;
+        assert_eq!(rewritten.status, AckStatus::BadSignature);
+        assert_eq!(crate::inbox::drain("10.0.0.5/24").len(), 1);
+        assert!(crate::inbox::drain("10.0.0.9/24").is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_loopback_listeners_require_ise_stamps() {
+        let listener = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+
+        let key = SigningKey::from_bytes(&rand::random());
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let ack = roundtrip(&mut stream, &build_stamped_frame(&key, 1, b"hi", false, SIGNED_HEADER, None)).await;
+        assert_eq!((ack.status, ack.message.as_str()), (AckStatus::BadSignature, "missing ISE stamp"));
+    }
+
+    #[tokio::test]
+    async fn sources_must_use_the_key_registered_for_them() {
+        use kairo_lib::registry_store::{AgentRecord, AgentStatus, RegistryStore};
+
+        let _cache = crate::revocations::tests::CACHE_LOCK.lock().await;
+        let owner = SigningKey::from_bytes(&rand::random());
+        let (banned, listed) = (SigningKey::from_bytes(&rand::random()), SigningKey::from_bytes(&rand::random()));
+        let dir = std::env::temp_dir().join(format!("kairo-p-binding-{}", std::process::id()));
+        let registry = dir.join("registry.json");
+        RegistryStore::open(&registry)
+            .transaction(|r| {
+                let agent = |name: &str, p: &str, key: &SigningKey| AgentRecord {
+                    p_address: Some(p.into()),
+                    public_key: Some(hex::encode(key.verifying_key().as_bytes())),
+                    ..AgentRecord::new(name)
+                };
+                r.insert(agent("owner", "10.0.0.6/24", &owner))?;
+                r.insert(agent("banned", "10.0.8.1/24", &banned))?;
+                r.set_status("banned", AgentStatus::Revoked)?;
+                r.insert(agent("listed", "10.0.8.2/24", &listed))
+            })
+            .unwrap();
+        // The seed revoked `listed`, but this registry has not caught up yet.
+        crate::revocations::tests::install_revoked(&[&listed]);
+        crate::config::CONFIG.write().unwrap().classifier.registry_path = Some(registry.to_str().unwrap().to_string());
+        crate::ip_classifier::refresh();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(SequenceTracker::default())));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let header = br#"{"source_p_address":"10.0.0.6/24","destination_p_address":"10.0.0.7/24"}"#;
+
+        let impostor = SigningKey::from_bytes(&rand::random());
+        let ack = roundtrip(&mut stream, &build_stamped_frame(&impostor, 1, b"hi", false, header, None)).await;
+        assert_eq!((ack.status, ack.message.as_str()), (AckStatus::DeliveryFailed, "Forbidden"));
+        let ack = roundtrip(&mut stream, &build_stamped_frame(&owner, 1, b"hi", false, header, None)).await;
+        assert_eq!(ack.status, AckStatus::Ok);
+        assert_eq!(crate::inbox::drain("10.0.0.7/24").len(), 1);
+
+        // Revoked agents signing with a fresh key, by registry record or by seed list.
+        for source in ["10.0.8.1/24", "10.0.8.2/24"] {
+            let header = format!(r#"{{"source_p_address":"{}","destination_p_address":"10.0.0.7/24"}}"#, source);
+            let fresh = SigningKey::from_bytes(&rand::random());
+            let ack = roundtrip(&mut stream, &build_stamped_frame(&fresh, 1, b"hi", false, header.as_bytes(), None)).await;
+            assert_eq!((ack.status, ack.message.as_str()), (AckStatus::DeliveryFailed, "Forbidden"));
+        }
+        assert!(crate::inbox::drain("10.0.0.7/24").is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
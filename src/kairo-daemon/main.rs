@@ -1,9 +1,24 @@
 mod handler;
+mod burst;
+mod checkpoint;
 mod clear_mini_state;
+mod config;
+mod gpt_log_processor;
+mod gpt_responder;
+mod handle_send;
+mod inbox;
+mod inspect;
 mod ip_classifier;
+mod kairo_p_listener;
+mod metrics;
+mod p_signature_validator;
+mod peers;
+mod revocations;
 mod task_queue;
 mod api {
+    pub mod admin;
     pub mod controller;
+    pub mod openai_compat;
 }
 
 use std::net::SocketAddr;
@@ -13,15 +28,35 @@ use tokio::net::TcpListener;
 
 use axum::routing::{get, post};
 use axum::Router;
+use clap::Parser;
 
 use simplelog::{CombinedLogger, TermLogger, WriteLogger, Config as LogConfig, TerminalMode, ColorChoice, LevelFilter};
-use handler::{handle_send, handle_gpt};
+use handler::handle_gpt;
+use handle_send::handle_send;
+use inbox::handle_receive;
+use metrics::{handle_metrics, track_latency};
+use kairo_p_listener::SequenceTracker;
 use task_queue::TaskQueue;
 use api::controller::add_task;
 
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to daemon_config.json
+    #[arg(long, default_value = config::DEFAULT_CONFIG_PATH)]
+    config: String,
+
+    /// Serve the OpenAI-compatible /v1/chat/completions gateway
+    /// (also enabled by KAIRO_ENABLE_OPENAI_COMPAT=1)
+    #[arg(long)]
+    enable_openai_compat: bool,
+}
+
 // エントリポイント
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     // ✅ Logger 初期化
     CombinedLogger::init(vec![
         TermLogger::new(LevelFilter::Info, LogConfig::default(), TerminalMode::Mixed, ColorChoice::Auto),
@@ -33,15 +68,99 @@ async fn main() {
     ])
     .unwrap();
 
+    // ✅ 設定読み込み
+    let settings = config::init(&args.config);
+
+    // ✅ 証跡チェーンの署名鍵
+    let witness_key = clear_mini_state::init_signer(&settings.witness);
+    log::info!("Witness checkpoints signed by {}", hex::encode(witness_key.to_bytes()));
+    clear_mini_state::open_log(&settings.witness);
+    if let Err(e) = p_signature_validator::init(&settings.ise) {
+        log::error!("Failed to load ISE keyring {}: {}", settings.ise.key_file, e);
+    }
+    if let Err(e) = revocations::init(&settings.revocations) {
+        log::error!("Revocation list disabled: {}", e);
+    }
+
     // ✅ TaskQueue 初期化
     let queue = Arc::new(Mutex::new(TaskQueue::new()));
+    let tracker = Arc::new(SequenceTracker::default());
+
+    // ✅ 前回のチェックポイントを復元
+    match checkpoint::restore(&settings.state_path, &queue, &tracker) {
+        Ok(true) => log::info!("Restored daemon state from {}", settings.state_path),
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to restore checkpoint {}: {}", settings.state_path, e),
+    }
+
+    // ✅ KAIRO-P バイナリリスナー起動
+    let kairo_p_addr = settings.kairo_p_address.clone();
+    let listener_tracker = tracker.clone();
+    let kairo_p_task = tokio::spawn(async move {
+        if let Err(e) = kairo_p_listener::run_listener(&kairo_p_addr, listener_tracker).await {
+            log::error!("KAIRO-P listener stopped: {}", e);
+        }
+    });
+
+    // ✅ 管理API起動
+    let admin_addr = settings.admin.listen_address.clone();
+    tokio::spawn(async move {
+        if let Err(e) = api::admin::serve(&admin_addr).await {
+            log::error!("Admin API stopped: {}", e);
+        }
+    });
+
+    // ✅ GPT 会話ログの保持期間を適用（起動時と以後1時間ごと）
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let settings = config::CONFIG.read().unwrap().gpt_log.clone();
+            if let Err(e) = gpt_log_processor::enforce_retention(&settings, chrono::Utc::now()) {
+                log::error!("GPT log retention failed: {}", e);
+            }
+        }
+    });
+
+    // ✅ 端点分類ポリシーとレジストリの変更を定期反映
+    ip_classifier::refresh();
+    tokio::spawn(async {
+        loop {
+            let secs = config::CONFIG.read().unwrap().classifier.refresh_secs.max(1);
+            tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+            ip_classifier::refresh();
+        }
+    });
+
+    // ✅ シードノードの失効リストを定期取得
+    tokio::spawn(async {
+        loop {
+            let settings = config::CONFIG.read().unwrap().revocations.clone();
+            if let Err(e) = revocations::refresh(&settings).await {
+                log::error!("Revocation list refresh failed: {}", e);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(settings.refresh_secs.max(1))).await;
+        }
+    });
 
     // ✅ Router 設定
-    let base_app = Router::new()
+    let mut routes = Router::new();
+    if api::openai_compat::enabled(args.enable_openai_compat) {
+        if let Err(e) = api::openai_compat::init(&settings.openai_compat) {
+            log::error!("OpenAI compatibility gateway cannot start: {}", e);
+            std::process::exit(1);
+        }
+        log::info!("OpenAI compatibility gateway enabled: POST /v1/chat/completions");
+        routes = routes.merge(api::openai_compat::router());
+    }
+    let base_app = routes
         .route("/", get(root))
         .route("/send", post(handle_send))
         .route("/gpt", post(handle_gpt))
+        .route("/receive/:p_address", get(handle_receive))
         .route("/add_task", post(add_task))
+        .route("/metrics", get(handle_metrics))
+        .route_layer(axum::middleware::from_fn(track_latency))
         .with_state(queue.clone());
 
     #[cfg(debug_assertions)]
@@ -53,12 +172,31 @@ async fn main() {
     #[cfg(not(debug_assertions))]
     let app = base_app;
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+    let ip = settings.listen_address.parse().unwrap_or_else(|_| {
+        log::error!("Invalid listen_address {:?}, falling back to 127.0.0.1", settings.listen_address);
+        std::net::IpAddr::from([127, 0, 0, 1])
+    });
+    let addr = SocketAddr::new(ip, settings.listen_port);
     println!("Listening on {}", addr);
 
-    // ✅ サーバ起動
+    // ✅ サーバ起動（SIGINT/SIGTERM で処理中リクエストを完了してから停止）
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app.into_make_service()).await.unwrap();
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(checkpoint::shutdown_signal())
+        .await
+        .unwrap();
+
+    // ✅ 新規接続を止め、既存接続をドレインしてから状態を保存
+    kairo_p_task.abort();
+    checkpoint::drain_peers().await;
+    if let Err(e) = clear_mini_state::CLEAR_MINI.sync_log() {
+        log::error!("Failed to sync witness log: {}", e);
+    }
+    let state = checkpoint::capture(&queue, &tracker);
+    match checkpoint::save(&settings.state_path, &state) {
+        Ok(()) => log::info!("💾 Daemon state saved to {}", settings.state_path),
+        Err(e) => log::error!("Failed to save checkpoint {}: {}", settings.state_path, e),
+    }
 }
 
 
@@ -76,6 +214,6 @@ async fn handle_debug_dump() -> impl axum::response::IntoResponse {
     use crate::clear_mini_state::CLEAR_MINI;
 
     log::warn!("Executing debug dump API. This MUST NOT appear in release builds.");
-    let snapshot = CLEAR_MINI.lock().unwrap().dump_witness_snapshot();
+    let snapshot = CLEAR_MINI.dump_witness_snapshot();
     Json(snapshot)
 }
@@ -0,0 +1,219 @@
+//! Prometheus text exposition for daemon traffic counters (GET /metrics).
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use once_cell::sync::Lazy;
+
+use crate::clear_mini_state::CLEAR_MINI;
+use crate::config::BurstAction;
+
+pub(crate) static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// Upper bounds (seconds) of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+#[derive(Default)]
+struct LabeledCounter(Mutex<HashMap<String, u64>>);
+
+impl LabeledCounter {
+    fn inc(&self, label: &str) {
+        *self.0.lock().unwrap().entry(label.to_string()).or_insert(0) += 1;
+    }
+
+    fn sorted(&self) -> Vec<(String, u64)> {
+        let mut v: Vec<_> = self.0.lock().unwrap().iter().map(|(k, v)| (k.clone(), *v)).collect();
+        v.sort();
+        v
+    }
+}
+
+#[derive(Default, Clone)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    received: LabeledCounter,
+    forwarded: LabeledCounter,
+    rejected: LabeledCounter,
+    signature_failures: LabeledCounter,
+    burst_detections: Mutex<HashMap<(String, &'static str), u64>>,
+    latency: Mutex<HashMap<String, Histogram>>,
+}
+
+impl Metrics {
+    /// `transport` is `http` or `kairo_p`.
+    pub fn packet_received(&self, transport: &str) {
+        self.received.inc(transport);
+    }
+
+    /// `destination` is the delivery path, e.g. `gpt` or `inbox`.
+    pub fn packet_forwarded(&self, destination: &str) {
+        self.forwarded.inc(destination);
+    }
+
+    pub fn packet_rejected(&self, reason: &str) {
+        self.rejected.inc(reason);
+    }
+
+    /// Labelled by transport only: the claimed source of a packet that
+    /// failed verification is attacker-chosen.
+    pub fn signature_failure(&self, transport: &str) {
+        self.signature_failures.inc(transport);
+    }
+
+    pub fn burst_detected(&self, rule: &str, action: BurstAction) {
+        *self
+            .burst_detections
+            .lock()
+            .unwrap()
+            .entry((rule.to_string(), action.as_str()))
+            .or_insert(0) += 1;
+    }
+
+    pub fn observe_request(&self, route: &str, elapsed: Duration) {
+        self.latency
+            .lock()
+            .unwrap()
+            .entry(route.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_counter(&mut out, "kairo_packets_received_total", "Packets received by transport.", "transport", &self.received);
+        render_counter(&mut out, "kairo_packets_forwarded_total", "Packets delivered by destination kind.", "destination", &self.forwarded);
+        render_counter(&mut out, "kairo_packets_rejected_total", "Packets rejected by reason.", "reason", &self.rejected);
+        render_counter(&mut out, "kairo_signature_failures_total", "Signature verification failures by transport.", "transport", &self.signature_failures);
+
+        let _ = writeln!(out, "# HELP kairo_burst_detections_total Burst rule threshold crossings.");
+        let _ = writeln!(out, "# TYPE kairo_burst_detections_total counter");
+        let mut bursts: Vec<_> = self.burst_detections.lock().unwrap().iter().map(|(k, v)| (k.clone(), *v)).collect();
+        bursts.sort();
+        for ((rule, action), count) in bursts {
+            let _ = writeln!(
+                out,
+                "kairo_burst_detections_total{{rule=\"{}\",action=\"{}\"}} {}",
+                escape(&rule),
+                action,
+                count
+            );
+        }
+
+        let _ = writeln!(out, "# HELP kairo_inspection_verdicts_total Content inspection results by rule.");
+        let _ = writeln!(out, "# TYPE kairo_inspection_verdicts_total counter");
+        for (rule, stats) in crate::inspect::stats() {
+            let rule = escape(&rule);
+            let passed = stats.evaluated - stats.flagged - stats.dropped;
+            for (verdict, count) in [("pass", passed), ("flag", stats.flagged), ("drop", stats.dropped)] {
+                let _ = writeln!(out, "kairo_inspection_verdicts_total{{rule=\"{}\",verdict=\"{}\"}} {}", rule, verdict, count);
+            }
+        }
+
+        let _ = writeln!(out, "# HELP kairo_inbox_depth Packets waiting in each inbox.");
+        let _ = writeln!(out, "# TYPE kairo_inbox_depth gauge");
+        let mut depths = crate::inbox::depths();
+        depths.sort();
+        for (p_address, depth) in depths {
+            let _ = writeln!(out, "kairo_inbox_depth{{p_address=\"{}\"}} {}", escape(&p_address), depth);
+        }
+
+        let (ring_len, ring_cap) = (CLEAR_MINI.ring.len(), CLEAR_MINI.ring.capacity());
+        let _ = writeln!(out, "# HELP kairo_witness_ring_records Records held in the clear-mini witness ring.");
+        let _ = writeln!(out, "# TYPE kairo_witness_ring_records gauge");
+        let _ = writeln!(out, "kairo_witness_ring_records {}", ring_len);
+        let _ = writeln!(out, "# HELP kairo_witness_ring_capacity Capacity of the clear-mini witness ring.");
+        let _ = writeln!(out, "# TYPE kairo_witness_ring_capacity gauge");
+        let _ = writeln!(out, "kairo_witness_ring_capacity {}", ring_cap);
+
+        let _ = writeln!(out, "# HELP kairo_request_duration_seconds HTTP request latency by route.");
+        let _ = writeln!(out, "# TYPE kairo_request_duration_seconds histogram");
+        let mut latency: Vec<_> = self.latency.lock().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        latency.sort_by(|a, b| a.0.cmp(&b.0));
+        for (route, h) in latency {
+            let route = escape(&route);
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(h.buckets) {
+                let _ = writeln!(out, "kairo_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}", route, bound, count);
+            }
+            let _ = writeln!(out, "kairo_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}", route, h.count);
+            let _ = writeln!(out, "kairo_request_duration_seconds_sum{{route=\"{}\"}} {}", route, h.sum);
+            let _ = writeln!(out, "kairo_request_duration_seconds_count{{route=\"{}\"}} {}", route, h.count);
+        }
+        out
+    }
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, label: &str, counter: &LabeledCounter) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (value, count) in counter.sorted() {
+        let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape(&value), count);
+    }
+}
+
+/// Escape a label value per the Prometheus text format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Handle GET /metrics
+pub async fn handle_metrics() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], METRICS.render())
+}
+
+/// Middleware recording request latency under the matched route pattern.
+pub async fn track_latency(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let start = Instant::now();
+    let response = next.run(req).await;
+    METRICS.observe_request(&route, start.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_labeled_counters_and_cumulative_buckets() {
+        let m = Metrics::default();
+        m.packet_rejected("bad_signature");
+        m.packet_rejected("bad_signature");
+        m.signature_failure("kairo_p");
+        m.observe_request("/send", Duration::from_millis(20));
+
+        let text = m.render();
+        assert!(text.contains("kairo_packets_rejected_total{reason=\"bad_signature\"} 2"));
+        assert!(text.contains("kairo_signature_failures_total{transport=\"kairo_p\"} 1"));
+        assert!(text.contains("kairo_request_duration_seconds_bucket{route=\"/send\",le=\"0.01\"} 0"));
+        assert!(text.contains("kairo_request_duration_seconds_bucket{route=\"/send\",le=\"0.025\"} 1"));
+        assert!(text.contains("kairo_request_duration_seconds_count{route=\"/send\"} 1"));
+    }
+}
@@ -1,12 +1,127 @@
-use sha2::{Digest, Sha256};
+#[cfg(test)]
+use clear_mini::ise::ise_sign;
+use clear_mini::ise::{ise_verify, IseError, Keyring, ReplayGuard, Sig};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use kairo_lib::packet::AiTcpPacket;
+use once_cell::sync::OnceCell;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 
-/// Minimal validator: recompute canonical SHA256 from nonce+meta and compare to provided sig32.
-/// This is intentionally minimal: replace with TPM-backed verification for production.
+use crate::config::IseSettings;
 
-pub fn validate(sig_nonce: &[u8;16], sig32: &[u8;32], canon: &[u8]) -> bool {
-    let mut h = Sha256::new();
-    h.update(sig_nonce);
-    h.update(canon);
-    let out = h.finalize();
-    out.as_slice() == &sig32[..]
+struct IseState {
+    keys: RwLock<Keyring>,
+    replay: Mutex<ReplayGuard>,
+    key_file: String,
+}
+
+static ISE: OnceCell<IseState> = OnceCell::new();
+
+/// Load (or create) the ISE keyring from `ise.key_file`.
+pub fn init(settings: &IseSettings) -> std::io::Result<()> {
+    let keys = Keyring::load_or_create(&settings.key_file)?.with_max_retired(settings.max_retired_keys);
+    log::info!("ISE keyring {} (active key {})", settings.key_file, keys.active().id);
+    let state = IseState {
+        keys: RwLock::new(keys),
+        replay: Mutex::new(ReplayGuard::new(
+            Duration::from_secs(settings.replay_window_secs),
+            settings.replay_max_entries,
+        )),
+        key_file: settings.key_file.clone(),
+    };
+    let _ = ISE.set(state);
+    Ok(())
+}
+
+fn state() -> Result<&'static IseState, std::io::Error> {
+    ISE.get()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "ISE keyring not initialised"))
+}
+
+/// Stamp `canon` with this node's active ISE key, as a peer sharing the
+/// keyring would.
+#[cfg(test)]
+pub fn stamp(canon: &[u8]) -> std::io::Result<Sig> {
+    Ok(ise_sign(state()?.keys.read().unwrap().active(), canon))
+}
+
+/// Verify an ISE stamp over `canon` against this node's keyring. Each stamp
+/// is accepted once.
+pub fn validate(sig: &Sig, canon: &[u8]) -> Result<(), IseError> {
+    let state = state().map_err(|_| IseError::UnknownKey(sig.key_id))?;
+    let keys = state.keys.read().unwrap();
+    ise_verify(&keys, sig, canon, &mut state.replay.lock().unwrap())
+}
+
+/// Make a fresh ISE key active and persist the keyring. Returns the active
+/// and still-accepted key ids.
+pub fn rotate() -> std::io::Result<(u32, Vec<u32>)> {
+    let state = state()?;
+    let mut keys = state.keys.write().unwrap();
+    let mut next = keys.clone();
+    next.rotate();
+    next.save(&state.key_file)?;
+    *keys = next;
+    Ok((keys.active().id, keys.ids()))
+}
+
+/// Active and still-accepted ISE key ids.
+pub fn key_ids() -> Option<(u32, Vec<u32>)> {
+    let keys = ISE.get()?.keys.read().unwrap();
+    Some((keys.active().id, keys.ids()))
+}
+
+/// Verify the hex Ed25519 `signature` over `payload` with `source_public_key`.
+/// Revocation is checked by `handle_send::deliver`.
+pub fn validate_packet(packet: &AiTcpPacket) -> bool {
+    let key_bytes: [u8; 32] = match hex::decode(&packet.source_public_key)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+    {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let key = match VerifyingKey::from_bytes(&key_bytes) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let signature = match hex::decode(&packet.signature)
+        .ok()
+        .and_then(|b| Signature::from_slice(&b).ok())
+    {
+        Some(sig) => sig,
+        None => return false,
+    };
+    key.verify(packet.payload.as_bytes(), &signature).is_ok()
+}
+
+/// Load one keyring for every test in the process; the keyring is global.
+#[cfg(test)]
+pub fn init_for_tests() -> IseSettings {
+    let dir = std::env::temp_dir().join(format!("kairo-ise-{}", std::process::id()));
+    let settings = IseSettings {
+        key_file: dir.join("ise_keys.json").to_str().unwrap().to_string(),
+        ..Default::default()
+    };
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| init(&settings).unwrap());
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_verify_once_and_survive_rotation() {
+        let settings = init_for_tests();
+
+        let before = stamp(b"canon").unwrap();
+        let (active, keys) = rotate().unwrap();
+        assert_eq!(keys, vec![before.key_id, active]);
+        assert_eq!(validate(&before, b"canon"), Ok(()));
+        assert_eq!(validate(&before, b"canon"), Err(IseError::Replayed));
+        assert_eq!(validate(&stamp(b"canon").unwrap(), b"other"), Err(IseError::BadMac));
+        assert_eq!(Keyring::load(&settings.key_file).unwrap().active().id, active);
+    }
 }
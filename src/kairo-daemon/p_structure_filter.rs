@@ -1,5 +0,0 @@
-pub fn _is_threat(_packet: &[u8]) -> bool {
-    // Placeholder for ASI/DeepSeek pattern matching
-    println!("Checking for ASI/DeepSeek structures...");
-    false // Assume safe for now
-}
\ No newline at end of file
@@ -0,0 +1,89 @@
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// A KAIRO-P connection as reported by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerInfo {
+    pub id: u64,
+    pub addr: SocketAddr,
+    pub connected_at: String,
+    pub frames: u64,
+    pub last_source: Option<String>,
+}
+
+struct PeerEntry {
+    info: PeerInfo,
+    disconnect: Arc<Notify>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static PEERS: Lazy<Mutex<HashMap<u64, PeerEntry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Registration of a live connection; dropping it removes the peer.
+pub struct PeerHandle {
+    pub id: u64,
+    pub disconnect: Arc<Notify>,
+}
+
+impl Drop for PeerHandle {
+    fn drop(&mut self) {
+        PEERS.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub fn register(addr: SocketAddr) -> PeerHandle {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let disconnect = Arc::new(Notify::new());
+    let info = PeerInfo {
+        id,
+        addr,
+        connected_at: Utc::now().to_rfc3339(),
+        frames: 0,
+        last_source: None,
+    };
+    PEERS.lock().unwrap().insert(id, PeerEntry { info, disconnect: disconnect.clone() });
+    PeerHandle { id, disconnect }
+}
+
+pub fn record_frame(id: u64, source: Option<&str>) {
+    if let Some(entry) = PEERS.lock().unwrap().get_mut(&id) {
+        entry.info.frames += 1;
+        if let Some(source) = source {
+            entry.info.last_source = Some(source.to_string());
+        }
+    }
+}
+
+pub fn list() -> Vec<PeerInfo> {
+    let mut peers: Vec<_> = PEERS.lock().unwrap().values().map(|e| e.info.clone()).collect();
+    peers.sort_by_key(|p| p.id);
+    peers
+}
+
+/// Ask the connection task to close. Returns false if no such peer exists.
+pub fn force_disconnect(id: u64) -> bool {
+    match PEERS.lock().unwrap().get(&id) {
+        Some(entry) => {
+            entry.disconnect.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Ask every connection to close after its current frame.
+pub fn disconnect_all() {
+    for entry in PEERS.lock().unwrap().values() {
+        entry.disconnect.notify_one();
+    }
+}
+
+pub fn count() -> usize {
+    PEERS.lock().unwrap().len()
+}
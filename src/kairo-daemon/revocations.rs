@@ -0,0 +1,214 @@
+//! Revocation list fetched from the seed node (`revocations.seed_url`).
+//!
+//! Every list must be signed by `revocations.seed_public_key`. The daemon
+//! asks for the delta since its cached version and falls back to the full
+//! list on a gap or after `max_deltas` deltas. The signed lists are kept in
+//! `cache_file`, so revoked senders stay rejected across restarts even while
+//! the seed is unreachable.
+
+use kairo_lib::revocation::{RevocationCache, RevocationError, RevocationList};
+use kairo_lib::seed_key;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::config::RevocationSettings;
+
+static CACHE: Lazy<RwLock<Option<RevocationCache>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevocationStatus {
+    pub seed_public_key: String,
+    pub version: u64,
+    pub revoked: usize,
+    pub deltas: usize,
+}
+
+/// Load the cached lists for the configured seed key. A cache that no longer
+/// verifies is discarded and rebuilt by the next refresh.
+pub fn init(settings: &RevocationSettings) -> Result<(), String> {
+    let Some(key) = settings.seed_public_key.as_deref() else {
+        return Ok(());
+    };
+    let issuer = seed_key::parse_public_key(key)?;
+    let cache = RevocationCache::load(&settings.cache_file, issuer).unwrap_or_else(|e| {
+        log::error!("Discarding revocation cache {}: {}", settings.cache_file, e);
+        RevocationCache::new(issuer)
+    });
+    log::info!("Revocation list version {} ({} keys revoked)", cache.version(), cache.len());
+    install(cache);
+    Ok(())
+}
+
+/// Make `cache` the set of revoked keys that [`is_revoked`] checks.
+pub fn install(cache: RevocationCache) {
+    *CACHE.write().unwrap() = Some(cache);
+}
+
+pub fn is_revoked(public_key: &str) -> bool {
+    CACHE.read().unwrap().as_ref().is_some_and(|c| c.is_revoked(public_key))
+}
+
+pub fn status() -> Option<RevocationStatus> {
+    CACHE.read().unwrap().as_ref().map(|c| RevocationStatus {
+        seed_public_key: hex::encode(c.issuer().to_bytes()),
+        version: c.version(),
+        revoked: c.len(),
+        deltas: c.deltas(),
+    })
+}
+
+async fn fetch(seed_url: &str, since: u64) -> Result<RevocationList, String> {
+    let url = format!("{}/revocations?since={}", seed_url.trim_end_matches('/'), since);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let resp = client.get(&url).send().await.map_err(|e| format!("{}: {}", url, e))?;
+    if !resp.status().is_success() {
+        return Err(format!("{}: HTTP {}", url, resp.status()));
+    }
+    resp.json().await.map_err(|e| format!("{}: {}", url, e))
+}
+
+/// Bring the cache up to date with the seed. Returns the number of newly
+/// revoked keys.
+pub async fn refresh(settings: &RevocationSettings) -> Result<usize, String> {
+    let (Some(seed_url), Some(key)) = (settings.seed_url.as_deref(), settings.seed_public_key.as_deref()) else {
+        return Ok(0);
+    };
+    let issuer = seed_key::parse_public_key(key)?;
+    // A reload may pin a different seed key; lists from the old one no longer count.
+    let mut cache = CACHE
+        .read()
+        .unwrap()
+        .clone()
+        .filter(|c| *c.issuer() == issuer)
+        .unwrap_or_else(|| RevocationCache::new(issuer));
+    let before = (cache.version(), cache.deltas());
+    let since = if cache.deltas() >= settings.max_deltas { 0 } else { cache.version() };
+    let added = match cache.apply(fetch(seed_url, since).await?) {
+        Err(RevocationError::Gap { .. }) => cache.apply(fetch(seed_url, 0).await?),
+        other => other,
+    }
+    .map_err(|e| e.to_string())?;
+    if (cache.version(), cache.deltas()) != before {
+        cache.save(&settings.cache_file).map_err(|e| format!("{}: {}", settings.cache_file, e))?;
+    }
+    if added > 0 {
+        log::warn!("🚫 {} more keys revoked (revocation list version {})", added, cache.version());
+    }
+    install(cache);
+    Ok(added)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::extract::{Query, State};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use chrono::Utc;
+    use ed25519_dalek::{Signer, SigningKey};
+    use kairo_lib::packet::AiTcpPacket;
+    use kairo_lib::revocation::RevocationEntry;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Seed = Arc<(SigningKey, Mutex<Vec<RevocationEntry>>)>;
+
+    /// Held by tests that install or depend on the process-wide revocation set.
+    pub(crate) static CACHE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Install a list, signed by a throwaway seed, revoking `keys`.
+    pub(crate) fn install_revoked(keys: &[&SigningKey]) {
+        let seed = SigningKey::from_bytes(&rand::random());
+        let log: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| RevocationEntry {
+                serial: i as u64 + 1,
+                name: format!("agent-{}", i + 1),
+                public_key: hex::encode(key.verifying_key().to_bytes()),
+                revoked_at: Utc::now(),
+            })
+            .collect();
+        let mut cache = RevocationCache::new(seed.verifying_key());
+        cache.apply(RevocationList::issue(&log, 0, &seed, Utc::now().timestamp())).unwrap();
+        install(cache);
+    }
+
+    async fn serve_list(State(seed): State<Seed>, Query(q): Query<HashMap<String, u64>>) -> Json<RevocationList> {
+        let log = seed.1.lock().unwrap().clone();
+        Json(RevocationList::issue(&log, q.get("since").copied().unwrap_or(0), &seed.0, Utc::now().timestamp()))
+    }
+
+    fn revoke(seed: &Seed, key: &SigningKey) {
+        let mut log = seed.1.lock().unwrap();
+        let serial = log.len() as u64 + 1;
+        log.push(RevocationEntry {
+            serial,
+            name: format!("agent-{}", serial),
+            public_key: hex::encode(key.verifying_key().to_bytes()),
+            revoked_at: Utc::now(),
+        });
+    }
+
+    fn packet_from(key: &SigningKey) -> AiTcpPacket {
+        AiTcpPacket {
+            source: "10.8.0.1/24".to_string(),
+            destination: "10.8.0.2/24".to_string(),
+            version: 1,
+            source_p_address: "10.8.0.1/24".to_string(),
+            destination_p_address: "10.8.0.2/24".to_string(),
+            source_public_key: hex::encode(key.verifying_key().to_bytes()),
+            sequence: 1,
+            timestamp_utc: 0,
+            payload_type: "text".to_string(),
+            payload: "hello".to_string(),
+            signature: hex::encode(key.sign(b"hello").to_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn revoked_senders_are_rejected_after_refresh_and_restart() {
+        let _cache = CACHE_LOCK.lock().await;
+        let seed: Seed = Arc::new((SigningKey::from_bytes(&rand::random()), Mutex::new(Vec::new())));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/revocations", get(serve_list)).with_state(seed.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let cache_file = std::env::temp_dir().join(format!("kairo-revocations-{}.json", std::process::id()));
+        let settings = RevocationSettings {
+            seed_url: Some(format!("http://{}", addr)),
+            seed_public_key: Some(hex::encode(seed.0.verifying_key().to_bytes())),
+            cache_file: cache_file.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        init(&settings).unwrap();
+
+        let (alice, bob) = (SigningKey::from_bytes(&rand::random()), SigningKey::from_bytes(&rand::random()));
+        revoke(&seed, &alice);
+        assert_eq!(refresh(&settings).await.unwrap(), 1);
+        let (status_code, _) = crate::handle_send::deliver(packet_from(&alice)).await;
+        assert_eq!(status_code, StatusCode::FORBIDDEN);
+        assert!(crate::inbox::drain("10.8.0.2/24").is_empty());
+        assert!(!is_revoked(&hex::encode(bob.verifying_key().to_bytes())));
+
+        revoke(&seed, &bob);
+        assert_eq!(refresh(&settings).await.unwrap(), 1);
+        assert_eq!(status().unwrap().deltas, 1);
+        let (status_code, _) = crate::handle_send::deliver(packet_from(&bob)).await;
+        assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+        // The cache alone restores both revocations.
+        *CACHE.write().unwrap() = None;
+        init(&settings).unwrap();
+        assert_eq!(status().unwrap().version, 2);
+        assert!(is_revoked(&hex::encode(alice.verifying_key().to_bytes())));
+        std::fs::remove_file(&cache_file).unwrap();
+    }
+}
@@ -20,4 +20,8 @@ impl TaskQueue {
     pub fn add_task(&mut self, task: Task) {
         self.tasks.push(task);
     }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
 }
@@ -0,0 +1,329 @@
+//! kairo-lib/federation.rs
+//! Seed-to-seed registry replication.
+//!
+//! Each seed serves its whole registry as a [`FederationState`] signed with
+//! its seed key (`GET /federation/state`) and periodically pulls the state of
+//! the peers in its [`FederationConfig`]. A pulled state is verified against
+//! the peer's pinned key and merged with [`crate::registry_store::Registry::merge`],
+//! a state-based CRDT merge: registrations are unioned, revocation always
+//! wins, and of two registrations claiming the same name, P address or key on
+//! different seeds the earlier one keeps it. Seeds that have exchanged state
+//! hold the same records, so losing one seed loses no registrations. A record
+//! that loses a conflict gives up its P address; [`apply_state_and_release`]
+//! also frees the loser's lease so the agent can lease a new address.
+
+use crate::p_allocator::{with_lease_file, AllocError, Allocator};
+use crate::registry_store::{AgentRecord, MergeReport, Registry, RegistryError, RegistryStore};
+use crate::revocation::RevocationEntry;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const DEFAULT_FEDERATION_CONFIG: &str = ".kairo/config/federation.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerConfig {
+    /// e.g. `http://10.0.1.254:8000`
+    pub url: String,
+    /// The peer's seed public key, hex.
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FederationConfig {
+    /// Stamped as `origin` on records this seed accepts; defaults to
+    /// [`seed_id`] of the seed key.
+    pub seed_id: Option<String>,
+    pub peers: Vec<PeerConfig>,
+    pub sync_secs: u64,
+}
+
+impl Default for FederationConfig {
+    fn default() -> Self {
+        Self { seed_id: None, peers: Vec::new(), sync_secs: 30 }
+    }
+}
+
+/// Default seed id: the first 8 bytes of the seed key, hex.
+pub fn seed_id(key: &VerifyingKey) -> String {
+    hex::encode(&key.to_bytes()[..8])
+}
+
+#[derive(Debug)]
+pub enum FederationError {
+    /// Signed by a key other than the peer's pinned key.
+    WrongIssuer(String),
+    BadSignature,
+    Registry(RegistryError),
+    Lease(AllocError),
+}
+
+impl fmt::Display for FederationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongIssuer(k) => write!(f, "federation state signed by unknown key {}", k),
+            Self::BadSignature => write!(f, "federation state signature is invalid"),
+            Self::Registry(e) => write!(f, "{}", e),
+            Self::Lease(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FederationError {}
+
+impl From<RegistryError> for FederationError {
+    fn from(e: RegistryError) -> Self {
+        Self::Registry(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederationState {
+    /// Seed public key, hex.
+    pub issuer: String,
+    pub seed_id: String,
+    pub generation: u64,
+    /// Unix seconds.
+    pub issued_at: i64,
+    pub agents: Vec<AgentRecord>,
+    pub revocations: Vec<RevocationEntry>,
+    /// Signature over everything above, hex.
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    issuer: &'a str,
+    seed_id: &'a str,
+    generation: u64,
+    issued_at: i64,
+    agents: &'a [AgentRecord],
+    revocations: &'a [RevocationEntry],
+}
+
+impl FederationState {
+    pub fn issue(registry: &Registry, seed_id: &str, key: &SigningKey, now: i64) -> Self {
+        let mut state = Self {
+            issuer: hex::encode(key.verifying_key().to_bytes()),
+            seed_id: seed_id.to_string(),
+            generation: registry.generation(),
+            issued_at: now,
+            agents: registry.agents().to_vec(),
+            revocations: registry.revocations().to_vec(),
+            signature: String::new(),
+        };
+        state.signature = hex::encode(key.sign(&state.signed_bytes()).to_bytes());
+        state
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let fields = SignedFields {
+            issuer: &self.issuer,
+            seed_id: &self.seed_id,
+            generation: self.generation,
+            issued_at: self.issued_at,
+            agents: &self.agents,
+            revocations: &self.revocations,
+        };
+        let mut bytes = b"KAIRO-FEDERATION-v1\n".to_vec();
+        bytes.extend(serde_json::to_vec(&fields).expect("federation state serializes"));
+        bytes
+    }
+
+    pub fn verify(&self, issuer: &VerifyingKey) -> Result<(), FederationError> {
+        if !self.issuer.eq_ignore_ascii_case(&hex::encode(issuer.to_bytes())) {
+            return Err(FederationError::WrongIssuer(self.issuer.clone()));
+        }
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| Signature::from_slice(&b).ok())
+            .ok_or(FederationError::BadSignature)?;
+        issuer.verify(&self.signed_bytes(), &signature).map_err(|_| FederationError::BadSignature)
+    }
+}
+
+/// Verify a peer's `state` and merge it into `store` in one transaction.
+pub fn apply_state(store: &RegistryStore, state: &FederationState, peer: &VerifyingKey) -> Result<MergeReport, FederationError> {
+    state.verify(peer)?;
+    Ok(store.transaction(|r| Ok(r.merge(&state.agents, &state.revocations)))?)
+}
+
+/// [`apply_state`], then release the leases in `lease_file` of `origin`'s
+/// agents that lost a conflict. A key still held by an active record keeps
+/// its lease.
+pub fn apply_state_and_release(
+    store: &RegistryStore,
+    state: &FederationState,
+    peer: &VerifyingKey,
+    origin: Option<&str>,
+    lease_file: &str,
+    allocator: &Allocator,
+) -> Result<MergeReport, FederationError> {
+    let report = apply_state(store, state, peer)?;
+    let registry = store.read()?;
+    let released: Vec<&str> = report
+        .conflicted
+        .iter()
+        .filter(|a| a.origin.as_deref() == origin)
+        .filter_map(|a| a.public_key.as_deref())
+        .filter(|k| registry.by_public_key(k).is_none())
+        .collect();
+    if !released.is_empty() {
+        with_lease_file(lease_file, |leases| {
+            for key in &released {
+                allocator.release(leases, key);
+            }
+            Ok(())
+        })
+        .map_err(FederationError::Lease)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_snapshot::Snapshot;
+    use crate::registry_store::AgentStatus;
+    use chrono::DateTime;
+
+    struct Seed {
+        id: String,
+        key: SigningKey,
+        store: RegistryStore,
+    }
+
+    impl Seed {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("kairo-federation-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let key = SigningKey::from_bytes(&rand::random());
+            Self { id: name.to_string(), key, store: RegistryStore::open(dir.join("registry.json")) }
+        }
+
+        fn register(&self, name: &str, p_address: &str, key: u32, at: i64) {
+            self.store
+                .transaction(|r| {
+                    r.insert(AgentRecord {
+                        p_address: Some(p_address.to_string()),
+                        public_key: Some(format!("{:064x}", key)),
+                        registered_at: DateTime::from_timestamp(at, 0),
+                        origin: Some(self.id.clone()),
+                        ..AgentRecord::new(name)
+                    })
+                })
+                .unwrap();
+        }
+
+        fn state(&self) -> FederationState {
+            FederationState::issue(&self.store.read().unwrap(), &self.id, &self.key, 0)
+        }
+
+        fn pull(&self, from: &Seed) -> MergeReport {
+            apply_state(&self.store, &from.state(), &from.key.verifying_key()).unwrap()
+        }
+
+        fn status(&self, name: &str, origin: &str) -> AgentStatus {
+            let registry = self.store.read().unwrap();
+            registry.agents().iter().find(|a| a.name == name && a.origin.as_deref() == Some(origin)).unwrap().status
+        }
+
+        fn root(&self) -> String {
+            Snapshot::build(&self.store.read().unwrap(), &self.key, 0).head.root
+        }
+    }
+
+    impl Drop for Seed {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.store.path().parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn three_seeds_converge_on_registrations_conflicts_and_revocations() {
+        let (a, b, c) = (Seed::new("a"), Seed::new("b"), Seed::new("c"));
+        a.register("alice", "10.0.0.5/24", 1, 100);
+        a.register("carol", "10.0.0.7/24", 3, 120);
+        // Concurrent claims while the seeds were partitioned: b hands out
+        // alice's P address, c registers a second "alice".
+        b.register("bob", "10.0.0.5/24", 2, 110);
+        c.register("alice", "10.0.1.9/24", 4, 130);
+
+        let report = b.pull(&a);
+        assert_eq!(report.added, 2);
+        assert_eq!(report.conflicted.len(), 1);
+        assert_eq!(report.conflicted[0].name, "bob");
+        c.pull(&a);
+        assert_eq!(c.status("alice", "c"), AgentStatus::Conflicted);
+
+        // carol is revoked on c; the revocation reaches a through b.
+        c.store.transaction(|r| r.set_status("carol", AgentStatus::Revoked)).unwrap();
+        b.pull(&c);
+        a.pull(&b);
+        c.pull(&b);
+        b.pull(&a);
+
+        for seed in [&a, &b, &c] {
+            assert_eq!(seed.status("alice", "a"), AgentStatus::Active);
+            assert_eq!(seed.status("bob", "b"), AgentStatus::Conflicted);
+            assert_eq!(seed.status("alice", "c"), AgentStatus::Conflicted);
+            assert_eq!(seed.status("carol", "a"), AgentStatus::Revoked);
+            let registry = seed.store.read().unwrap();
+            assert_eq!(registry.by_p_address("10.0.0.5/24").unwrap().name, "alice");
+            assert_eq!(registry.revocations().len(), 1);
+        }
+        assert_eq!(a.root(), b.root());
+        assert_eq!(b.root(), c.root());
+
+        // Converged seeds change nothing when they sync again.
+        let generation = a.store.read().unwrap().generation();
+        let again = a.pull(&c);
+        assert_eq!((again.added, again.updated), (0, 0));
+        assert_eq!(a.store.read().unwrap().generation(), generation);
+
+        // A state not signed by the pinned peer key is refused.
+        let forged = FederationState { agents: Vec::new(), ..c.state() };
+        assert!(matches!(apply_state(&a.store, &forged, &c.key.verifying_key()), Err(FederationError::BadSignature)));
+        assert!(matches!(
+            apply_state(&a.store, &c.state(), &b.key.verifying_key()),
+            Err(FederationError::WrongIssuer(_))
+        ));
+    }
+
+    #[test]
+    fn conflict_losers_give_up_their_address_and_lease() {
+        use crate::p_allocator::{assign_in_file, AllocatorConfig};
+
+        let (a, b) = (Seed::new("release-a"), Seed::new("release-b"));
+        a.register("alice", "10.0.0.5/24", 1, 100);
+        // b leased alice's address to bob, and alice's key to dave, while partitioned.
+        b.register("bob", "10.0.0.5/24", 2, 110);
+        b.register("dave", "10.0.0.8/24", 1, 120);
+        let leases = b.store.path().parent().unwrap().join("p_leases.json");
+        let leases = leases.to_str().unwrap();
+        let allocator = Allocator::new(AllocatorConfig::default()).unwrap();
+        for key in [1, 2, 3] {
+            assign_in_file(leases, &allocator, &format!("{:064x}", key), None, 0).unwrap();
+        }
+
+        let report =
+            apply_state_and_release(&b.store, &a.state(), &a.key.verifying_key(), Some("release-b"), leases, &allocator).unwrap();
+        assert_eq!(report.conflicted.len(), 2);
+        let registry = b.store.read().unwrap();
+        let bob = registry.latest("bob").unwrap();
+        assert_eq!((bob.status, bob.p_address.as_deref()), (AgentStatus::Conflicted, None));
+        assert_eq!(registry.by_p_address("10.0.0.5/24").unwrap().name, "alice");
+        drop(registry);
+
+        // bob's lease is gone; alice's key is still active, so its lease stays.
+        let held: Vec<String> = with_lease_file(leases, |l| Ok(l.iter().map(|l| l.public_key.clone()).collect())).unwrap();
+        assert_eq!(held, vec![format!("{:064x}", 1), format!("{:064x}", 3)]);
+
+        // A stale copy from a peer that still lists bob's address does not bring it back.
+        let mut registry = b.store.read().unwrap();
+        let stale = AgentRecord { p_address: Some("10.0.0.5/24".into()), status: AgentStatus::Active, ..registry.latest("bob").unwrap().clone() };
+        registry.merge(&[stale], &[]);
+        assert_eq!(registry.latest("bob").unwrap().p_address, None);
+    }
+}
@@ -3,10 +3,17 @@
 // --- モジュール公開宣言 ---
 pub mod comm;
 pub mod config;
+pub mod federation;
 pub mod governance;
+pub mod p_allocator;
 pub mod packet;
+pub mod registration;
 pub mod registry;
+pub mod registry_snapshot;
+pub mod registry_store;
 pub mod resolvers;
+pub mod revocation;
+pub mod seed_key;
 pub mod wau_config;
 pub mod mesh_scope_manager;
 
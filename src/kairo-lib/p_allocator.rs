@@ -0,0 +1,371 @@
+//! kairo-lib/p_allocator.rs
+//! P-address allocation from configured IPv4/IPv6 pools.
+//!
+//! Leases are keyed by the agent's public key: asking again renews the same
+//! address. Leases expire after `lease_secs` unless renewed; `reservations`
+//! pin an address to a key for good and `reserved` addresses are never handed
+//! out. [`assign_in_file`] holds an exclusive file lock for the whole
+//! read-allocate-write, so concurrent allocators never hand out one address twice.
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Family {
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reservation {
+    pub p_address: String,
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AllocatorConfig {
+    /// CIDR blocks, tried in order: `10.0.0.0/24`, `fd00::/64`.
+    pub pools: Vec<String>,
+    /// Addresses never assigned (gateways, seed nodes).
+    pub reserved: Vec<String>,
+    /// Addresses pinned to a key; these never expire.
+    pub reservations: Vec<Reservation>,
+    pub lease_secs: u64,
+}
+
+impl Default for AllocatorConfig {
+    fn default() -> Self {
+        Self {
+            pools: vec!["10.0.0.0/24".to_string(), "fd00:6b61:6972:6f00::/64".to_string()],
+            reserved: Vec::new(),
+            reservations: Vec::new(),
+            lease_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Address with its pool prefix, e.g. `10.0.0.5/24`.
+    pub p_address: String,
+    pub public_key: String,
+    /// Unix seconds; `None` for reservations.
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllocError {
+    InvalidKey,
+    /// No pool of the requested family has a free address.
+    Exhausted,
+    Config(String),
+    Io(String),
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKey => write!(f, "public key must be 32 hex-encoded bytes"),
+            Self::Exhausted => write!(f, "no free P address in the configured pools"),
+            Self::Config(e) => write!(f, "bad allocator config: {}", e),
+            Self::Io(e) => write!(f, "lease store error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// An address as `(family, value)`; IPv4 uses the low 32 bits.
+type Addr = (Family, u128);
+
+fn parse_ip(s: &str) -> Result<Addr, String> {
+    let host = s.split('/').next().unwrap_or(s);
+    match host.parse::<IpAddr>().map_err(|_| format!("bad address {:?}", s))? {
+        IpAddr::V4(v4) => Ok((Family::Ipv4, u32::from(v4) as u128)),
+        IpAddr::V6(v6) => Ok((Family::Ipv6, u128::from(v6))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pool {
+    family: Family,
+    network: u128,
+    prefix: u8,
+}
+
+impl Pool {
+    fn parse(cidr: &str) -> Result<Self, String> {
+        let (addr, prefix) = cidr.split_once('/').ok_or_else(|| format!("pool {:?} needs a prefix length", cidr))?;
+        let (family, value) = parse_ip(addr)?;
+        let bits = if family == Family::Ipv4 { 32 } else { 128 };
+        let prefix: u8 = prefix.parse().map_err(|_| format!("bad prefix length in {:?}", cidr))?;
+        if prefix > bits || bits - prefix < 2 {
+            return Err(format!("pool {:?} has no host addresses", cidr));
+        }
+        let mut pool = Self { family, network: 0, prefix };
+        pool.network = value & pool.mask();
+        Ok(pool)
+    }
+
+    /// Network bits of an address; shifting by 128 would overflow, so an
+    /// IPv6 `/0` pool gets an empty mask.
+    fn mask(&self) -> u128 {
+        u128::MAX.checked_shl(self.host_bits()).unwrap_or(0)
+    }
+
+    fn host_bits(&self) -> u32 {
+        let bits = if self.family == Family::Ipv4 { 32 } else { 128 };
+        (bits - self.prefix) as u32
+    }
+
+    /// First and last assignable host; skips the network address (and the
+    /// IPv4 broadcast address).
+    fn hosts(&self) -> (u128, u128) {
+        let size_minus_one = if self.host_bits() == 128 { u128::MAX } else { (1u128 << self.host_bits()) - 1 };
+        let last = self.network + size_minus_one;
+        let last = if self.family == Family::Ipv4 { last - 1 } else { last };
+        (self.network + 1, last)
+    }
+
+    fn contains(&self, addr: Addr) -> bool {
+        addr.0 == self.family && addr.1 & self.mask() == self.network
+    }
+
+    fn format(&self, value: u128) -> String {
+        match self.family {
+            Family::Ipv4 => format!("{}/{}", Ipv4Addr::from(value as u32), self.prefix),
+            Family::Ipv6 => format!("{}/{}", Ipv6Addr::from(value), self.prefix),
+        }
+    }
+}
+
+fn valid_key(public_key: &str) -> bool {
+    public_key.len() == 64 && hex::decode(public_key).is_ok()
+}
+
+#[derive(Clone)]
+pub struct Allocator {
+    config: AllocatorConfig,
+    pools: Vec<Pool>,
+    reserved: HashSet<Addr>,
+}
+
+impl Allocator {
+    pub fn new(config: AllocatorConfig) -> Result<Self, AllocError> {
+        let pools = config
+            .pools
+            .iter()
+            .map(|p| Pool::parse(p))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(AllocError::Config)?;
+        let mut reserved = HashSet::new();
+        for addr in config.reserved.iter().chain(config.reservations.iter().map(|r| &r.p_address)) {
+            reserved.insert(parse_ip(addr).map_err(AllocError::Config)?);
+        }
+        Ok(Self { config, pools, reserved })
+    }
+
+    pub fn config(&self) -> &AllocatorConfig {
+        &self.config
+    }
+
+    /// Never hand out `addr` (e.g. the seed's own listen address).
+    pub fn reserve(&mut self, addr: &str) -> Result<(), AllocError> {
+        self.reserved.insert(parse_ip(addr).map_err(AllocError::Config)?);
+        Ok(())
+    }
+
+    /// Lease an address to `public_key` in `leases`, reusing and renewing the
+    /// key's current lease if it has one. `family` picks the pool family;
+    /// `None` takes the first pool with room.
+    pub fn assign(
+        &self,
+        leases: &mut Vec<Lease>,
+        public_key: &str,
+        family: Option<Family>,
+        now: i64,
+    ) -> Result<Lease, AllocError> {
+        if !valid_key(public_key) {
+            return Err(AllocError::InvalidKey);
+        }
+        let public_key = public_key.to_ascii_lowercase();
+        leases.retain(|l| l.expires_at.is_none_or(|t| t >= now));
+        // A lease whose address has since been reserved is void; its holder
+        // gets a fresh address below.
+        leases.retain(|l| l.expires_at.is_none() || !parse_ip(&l.p_address).is_ok_and(|a| self.reserved.contains(&a)));
+        let wanted = |p_address: &str| family.is_none_or(|f| parse_ip(p_address).is_ok_and(|a| a.0 == f));
+
+        if let Some(r) = self
+            .config
+            .reservations
+            .iter()
+            .find(|r| r.public_key.eq_ignore_ascii_case(&public_key) && wanted(&r.p_address))
+        {
+            let lease = Lease { p_address: r.p_address.clone(), public_key, expires_at: None };
+            if !leases.contains(&lease) {
+                leases.push(lease.clone());
+            }
+            return Ok(lease);
+        }
+
+        let expires_at = Some(now + self.config.lease_secs as i64);
+        if let Some(lease) = leases.iter_mut().find(|l| l.public_key == public_key && wanted(&l.p_address)) {
+            if lease.expires_at.is_some() {
+                lease.expires_at = expires_at;
+            }
+            return Ok(lease.clone());
+        }
+
+        let taken: HashSet<Addr> = leases
+            .iter()
+            .filter_map(|l| parse_ip(&l.p_address).ok())
+            .chain(self.reserved.iter().copied())
+            .collect();
+        for pool in self.pools.iter().filter(|p| family.is_none_or(|f| p.family == f)) {
+            let (first, last) = pool.hosts();
+            // Taken addresses in this pool bound how far the scan can go.
+            let in_pool = taken.iter().filter(|&&a| pool.contains(a)).count() as u128;
+            let end = last.min(first.saturating_add(in_pool));
+            if let Some(value) = (first..=end).find(|v| !taken.contains(&(pool.family, *v))) {
+                let lease = Lease { p_address: pool.format(value), public_key, expires_at };
+                leases.push(lease.clone());
+                return Ok(lease);
+            }
+        }
+        Err(AllocError::Exhausted)
+    }
+
+    /// Drop `public_key`'s leases (reservations stay); true if any were held.
+    pub fn release(&self, leases: &mut Vec<Lease>, public_key: &str) -> bool {
+        let before = leases.len();
+        leases.retain(|l| l.expires_at.is_none() || !l.public_key.eq_ignore_ascii_case(public_key));
+        leases.len() != before
+    }
+}
+
+/// Run `f` on the leases in the file at `path` under an exclusive lock and
+/// write them back if it succeeds.
+pub fn with_lease_file<T>(path: &str, f: impl FnOnce(&mut Vec<Lease>) -> Result<T, AllocError>) -> Result<T, AllocError> {
+    let io = |e: std::io::Error| AllocError::Io(e.to_string());
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(io)?;
+    file.lock_exclusive().map_err(io)?;
+    let result = (|| {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(io)?;
+        let mut leases: Vec<Lease> = if contents.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&contents).map_err(|e| AllocError::Io(e.to_string()))?
+        };
+        let out = f(&mut leases)?;
+        let json = serde_json::to_string_pretty(&leases).map_err(|e| AllocError::Io(e.to_string()))?;
+        file.set_len(0).map_err(io)?;
+        file.seek(std::io::SeekFrom::Start(0)).map_err(io)?;
+        file.write_all(json.as_bytes()).map_err(io)?;
+        file.sync_all().map_err(io)?;
+        Ok(out)
+    })();
+    FileExt::unlock(&file).ok();
+    result
+}
+
+/// [`Allocator::assign`] against the lease file at `path`, under an exclusive lock.
+pub fn assign_in_file(
+    path: &str,
+    allocator: &Allocator,
+    public_key: &str,
+    family: Option<Family>,
+    now: i64,
+) -> Result<Lease, AllocError> {
+    with_lease_file(path, |leases| allocator.assign(leases, public_key, family, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> String {
+        format!("{:064x}", i)
+    }
+
+    #[test]
+    fn leases_renew_expire_and_respect_reservations() {
+        let allocator = Allocator::new(AllocatorConfig {
+            pools: vec!["10.1.0.0/30".into(), "fd00::/126".into()],
+            reserved: vec!["fd00::1".into()],
+            reservations: vec![Reservation { p_address: "10.9.9.9/24".into(), public_key: key(99) }],
+            lease_secs: 60,
+        })
+        .unwrap();
+        let mut leases = Vec::new();
+
+        let a = allocator.assign(&mut leases, &key(1), None, 0).unwrap();
+        assert_eq!(a.p_address, "10.1.0.1/30");
+        assert_eq!(allocator.assign(&mut leases, &key(1), None, 30).unwrap().expires_at, Some(90));
+        assert_eq!(allocator.assign(&mut leases, &key(2), None, 30).unwrap().p_address, "10.1.0.2/30");
+        // The IPv4 pool is full (no network or broadcast); IPv6 skips the reserved ::1.
+        assert_eq!(allocator.assign(&mut leases, &key(3), None, 30).unwrap().p_address, "fd00::2/126");
+        assert_eq!(allocator.assign(&mut leases, &key(4), Some(Family::Ipv4), 30), Err(AllocError::Exhausted));
+        assert_eq!(allocator.assign(&mut leases, "zz", None, 30), Err(AllocError::InvalidKey));
+
+        // key(2)'s lease lapses at 90; key(1) renewed to 90 as well, so both free up.
+        assert_eq!(allocator.assign(&mut leases, &key(4), Some(Family::Ipv4), 91).unwrap().p_address, "10.1.0.1/30");
+
+        let pinned = allocator.assign(&mut leases, &key(99), None, 91).unwrap();
+        assert_eq!((pinned.p_address.as_str(), pinned.expires_at), ("10.9.9.9/24", None));
+        assert!(!allocator.release(&mut leases, &key(99)));
+        assert!(allocator.release(&mut leases, &key(4)));
+    }
+
+    #[test]
+    fn whole_ipv6_pool_parses_and_reserved_leases_are_not_reused() {
+        let pool = Pool::parse("::/0").unwrap();
+        assert!(pool.contains(parse_ip("fd00::1").unwrap()));
+        assert!(!pool.contains(parse_ip("10.0.0.1").unwrap()));
+        assert_eq!(pool.hosts(), (1, u128::MAX));
+        assert!(Pool::parse("0.0.0.0/0").is_ok());
+
+        let mut allocator = Allocator::new(AllocatorConfig {
+            pools: vec!["10.2.0.0/29".into()],
+            lease_secs: 60,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut leases = Vec::new();
+        assert_eq!(allocator.assign(&mut leases, &key(1), None, 0).unwrap().p_address, "10.2.0.1/29");
+        allocator.reserve("10.2.0.1").unwrap();
+        assert_eq!(allocator.assign(&mut leases, &key(1), None, 10).unwrap().p_address, "10.2.0.2/29");
+        assert!(leases.iter().all(|l| l.p_address != "10.2.0.1/29"));
+    }
+
+    #[test]
+    fn concurrent_file_allocations_never_collide() {
+        let dir = std::env::temp_dir().join(format!("kairo-alloc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leases.json").to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let allocator = Allocator::new(AllocatorConfig::default()).unwrap();
+                    (0..10)
+                        .map(|i| assign_in_file(&path, &allocator, &key(t * 100 + i), None, 0).unwrap().p_address)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let all: Vec<String> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let unique: HashSet<&String> = all.iter().collect();
+        assert_eq!((all.len(), unique.len()), (80, 80));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! kairo-lib/registration.rs
+//! Proof-of-possession registration with a seed node.
+//!
+//! The agent fetches a one-time nonce (`POST /register/challenge`), signs
+//! [`registration_message`] with its Ed25519 key and sends a
+//! [`RegisterRequest`]. The seed checks the signature and the nonce before it
+//! stores the public key, so only the key holder can claim an identity.
+//! Leasing a P address (`POST /assign_p_address`) answers the same kind of
+//! challenge with an [`AssignRequest`], and a seed administrator revokes an
+//! agent (`POST /revoke`) with a [`RevokeRequest`].
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::p_allocator::Family;
+
+/// How long an issued nonce stays usable.
+pub const CHALLENGE_TTL_SECS: i64 = 300;
+/// Outstanding nonces kept per seed; the oldest are dropped beyond this.
+pub const MAX_PENDING_CHALLENGES: usize = 10_000;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegistrationChallenge {
+    /// 32 random bytes, hex.
+    pub nonce: String,
+    /// Unix seconds after which the nonce is refused.
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegisterRequest {
+    pub agent_id: String,
+    /// Ed25519 public key, hex.
+    pub public_key: String,
+    pub nonce: String,
+    /// Signature over [`registration_message`], hex.
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AssignRequest {
+    /// Ed25519 public key, hex.
+    pub public_key: String,
+    #[serde(default)]
+    pub family: Option<Family>,
+    pub nonce: String,
+    /// Signature over [`assign_message`], hex.
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RevokeRequest {
+    pub agent_id: String,
+    /// The administrator's Ed25519 public key, hex.
+    pub admin_key: String,
+    pub nonce: String,
+    /// Signature over [`revoke_message`], hex.
+    pub signature: String,
+}
+
+/// Bytes signed by the agent: binds the identity and key to the seed's nonce.
+pub fn registration_message(agent_id: &str, public_key: &str, nonce: &str) -> Vec<u8> {
+    format!("KAIRO-REGISTER-v1\n{}\n{}\n{}", agent_id, public_key.to_ascii_lowercase(), nonce).into_bytes()
+}
+
+/// Bytes signed for a lease: binds the key and requested family to the nonce.
+pub fn assign_message(public_key: &str, family: Option<Family>, nonce: &str) -> Vec<u8> {
+    let family = match family {
+        Some(Family::Ipv4) => "ipv4",
+        Some(Family::Ipv6) => "ipv6",
+        None => "any",
+    };
+    format!("KAIRO-ASSIGN-v1\n{}\n{}\n{}", public_key.to_ascii_lowercase(), family, nonce).into_bytes()
+}
+
+/// Bytes signed by an administrator: binds the agent to revoke to the nonce.
+pub fn revoke_message(agent_id: &str, admin_key: &str, nonce: &str) -> Vec<u8> {
+    format!("KAIRO-REVOKE-v1\n{}\n{}\n{}", agent_id, admin_key.to_ascii_lowercase(), nonce).into_bytes()
+}
+
+/// Check a hex `signature` by hex `public_key` over `message`.
+fn verify_signed(public_key: &str, signature: &str, message: &[u8]) -> Result<VerifyingKey, String> {
+    let key_bytes: [u8; 32] = hex::decode(public_key)
+        .map_err(|_| "Invalid public key format".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid public key length".to_string())?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| "Invalid public key".to_string())?;
+    let sig_bytes = hex::decode(signature).map_err(|_| "Invalid signature format".to_string())?;
+    let signature = Signature::try_from(sig_bytes.as_slice()).map_err(|_| "Invalid signature format".to_string())?;
+    key.verify(message, &signature)
+        .map_err(|_| "Signature does not match the public key".to_string())?;
+    Ok(key)
+}
+
+impl RegisterRequest {
+    /// Answer `challenge` for `agent_id` with `key`.
+    pub fn sign(agent_id: &str, key: &SigningKey, challenge: &RegistrationChallenge) -> Self {
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+        let signature = key.sign(&registration_message(agent_id, &public_key, &challenge.nonce));
+        Self {
+            agent_id: agent_id.to_string(),
+            public_key,
+            nonce: challenge.nonce.clone(),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Check the signature; returns the verified key. The nonce is checked
+    /// separately with [`ChallengeStore::redeem`].
+    pub fn verify(&self) -> Result<VerifyingKey, String> {
+        verify_signed(&self.public_key, &self.signature, &registration_message(&self.agent_id, &self.public_key, &self.nonce))
+    }
+}
+
+impl AssignRequest {
+    /// Answer `challenge` with `key` to lease an address of `family`.
+    pub fn sign(key: &SigningKey, family: Option<Family>, challenge: &RegistrationChallenge) -> Self {
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+        let signature = key.sign(&assign_message(&public_key, family, &challenge.nonce));
+        Self { public_key, family, nonce: challenge.nonce.clone(), signature: hex::encode(signature.to_bytes()) }
+    }
+
+    /// Check the signature; returns the verified key. The nonce is checked
+    /// separately with [`ChallengeStore::redeem`].
+    pub fn verify(&self) -> Result<VerifyingKey, String> {
+        verify_signed(&self.public_key, &self.signature, &assign_message(&self.public_key, self.family, &self.nonce))
+    }
+}
+
+impl RevokeRequest {
+    /// Answer `challenge` with the administrator `key` to revoke `agent_id`.
+    pub fn sign(agent_id: &str, key: &SigningKey, challenge: &RegistrationChallenge) -> Self {
+        let admin_key = hex::encode(key.verifying_key().to_bytes());
+        let signature = key.sign(&revoke_message(agent_id, &admin_key, &challenge.nonce));
+        Self {
+            agent_id: agent_id.to_string(),
+            admin_key,
+            nonce: challenge.nonce.clone(),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Check the signature and that it comes from one of `admins`; returns
+    /// the verified key. The nonce is checked separately with
+    /// [`ChallengeStore::redeem`].
+    pub fn verify(&self, admins: &[VerifyingKey]) -> Result<VerifyingKey, String> {
+        let key = verify_signed(&self.admin_key, &self.signature, &revoke_message(&self.agent_id, &self.admin_key, &self.nonce))?;
+        if !admins.contains(&key) {
+            return Err("Not an administrator key for this seed".to_string());
+        }
+        Ok(key)
+    }
+}
+
+/// Nonces issued by a seed, each redeemable once before it expires.
+#[derive(Debug, Default)]
+pub struct ChallengeStore {
+    pending: HashMap<String, i64>,
+}
+
+impl ChallengeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn issue(&mut self, now: i64) -> RegistrationChallenge {
+        self.pending.retain(|_, &mut expires| expires >= now);
+        if self.pending.len() >= MAX_PENDING_CHALLENGES {
+            if let Some(oldest) = self.pending.iter().min_by_key(|(_, &e)| e).map(|(n, _)| n.clone()) {
+                self.pending.remove(&oldest);
+            }
+        }
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let challenge = RegistrationChallenge { nonce: hex::encode(bytes), expires_at: now + CHALLENGE_TTL_SECS };
+        self.pending.insert(challenge.nonce.clone(), challenge.expires_at);
+        challenge
+    }
+
+    /// Consume `nonce`; false if it was never issued, already used or expired.
+    pub fn redeem(&mut self, nonce: &str, now: i64) -> bool {
+        matches!(self.pending.remove(nonce), Some(expires) if expires >= now)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_key_holder_can_answer_a_challenge_once() {
+        let mut store = ChallengeStore::new();
+        let challenge = store.issue(1_000);
+        let key = SigningKey::from_bytes(&rand::random());
+        let req = RegisterRequest::sign("agent-a", &key, &challenge);
+        assert_eq!(req.verify().unwrap(), key.verifying_key());
+
+        // Claiming someone else's key or identity breaks the signature.
+        let other = SigningKey::from_bytes(&rand::random());
+        let forged = RegisterRequest { public_key: hex::encode(other.verifying_key().to_bytes()), ..req.clone() };
+        assert!(forged.verify().is_err());
+        let renamed = RegisterRequest { agent_id: "agent-b".into(), ..req.clone() };
+        assert!(renamed.verify().is_err());
+
+        assert!(store.redeem(&req.nonce, 1_001));
+        assert!(!store.redeem(&req.nonce, 1_002));
+        let late = store.issue(2_000);
+        assert!(!store.redeem(&late.nonce, 2_000 + CHALLENGE_TTL_SECS + 1));
+        assert!(!store.redeem("not-issued", 2_000));
+    }
+
+    #[test]
+    fn assign_requests_bind_key_and_family() {
+        let challenge = ChallengeStore::new().issue(1_000);
+        let key = SigningKey::from_bytes(&rand::random());
+        let req = AssignRequest::sign(&key, Some(Family::Ipv6), &challenge);
+        assert_eq!(req.verify().unwrap(), key.verifying_key());
+
+        let other = SigningKey::from_bytes(&rand::random());
+        let stolen = AssignRequest { public_key: hex::encode(other.verifying_key().to_bytes()), ..req.clone() };
+        assert!(stolen.verify().is_err());
+        assert!(AssignRequest { family: None, ..req.clone() }.verify().is_err());
+        // A registration signature over the same nonce is not a lease request.
+        let registration = RegisterRequest::sign("agent-a", &key, &challenge);
+        assert!(AssignRequest { signature: registration.signature, ..req }.verify().is_err());
+    }
+
+    #[test]
+    fn only_administrators_can_revoke() {
+        let challenge = ChallengeStore::new().issue(1_000);
+        let admin = SigningKey::from_bytes(&rand::random());
+        let admins = [admin.verifying_key()];
+        let req = RevokeRequest::sign("agent-a", &admin, &challenge);
+        assert_eq!(req.verify(&admins).unwrap(), admin.verifying_key());
+
+        // Unsigned, retargeted, or signed by a key that is not an administrator.
+        assert!(RevokeRequest { signature: String::new(), ..req.clone() }.verify(&admins).is_err());
+        assert!(RevokeRequest { agent_id: "agent-b".into(), ..req.clone() }.verify(&admins).is_err());
+        let outsider = SigningKey::from_bytes(&rand::random());
+        assert!(RevokeRequest::sign("agent-a", &outsider, &challenge).verify(&admins).is_err());
+    }
+}
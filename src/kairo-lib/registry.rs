@@ -1,8 +1,9 @@
+//! Name and P-address view of the shared registry in [`crate::registry_store`].
+
 use chrono::{DateTime, Utc};
-use fs2::FileExt;
 use serde::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::io::{Read, Seek, Write};
+
+use crate::registry_store::{AgentRecord, AgentStatus, RegistryError, RegistryStore};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RegistryEntry {
@@ -14,110 +15,69 @@ pub struct RegistryEntry {
     pub last_contact: Option<DateTime<Utc>>,
 }
 
-pub fn load_registry(path: &str) -> Result<Vec<RegistryEntry>, std::io::Error> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)?;
-    FileExt::lock_shared(&file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    FileExt::unlock(&file)?;
-    if contents.trim().is_empty() {
-        Ok(Vec::new())
-    } else {
-        let entries: Vec<RegistryEntry> = serde_json::from_str(&contents).unwrap_or_default();
-        Ok(entries)
+impl From<&AgentRecord> for RegistryEntry {
+    fn from(a: &AgentRecord) -> Self {
+        Self {
+            name: a.name.clone(),
+            p_address: a.p_address.clone().unwrap_or_default(),
+            deleted: !a.is_active(),
+            last_contact: a.last_contact,
+        }
     }
 }
 
-pub fn save_registry(path: &str, registry: &[RegistryEntry]) -> Result<(), std::io::Error> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(path)?;
-    file.lock_exclusive()?;
-    let json = serde_json::to_string_pretty(registry)?;
-    file.write_all(json.as_bytes())?;
-    FileExt::unlock(&file)?;
-    Ok(())
+fn io_error(e: RegistryError) -> std::io::Error {
+    match e {
+        RegistryError::Io(e) => e,
+        other => std::io::Error::new(std::io::ErrorKind::InvalidData, other.to_string()),
+    }
 }
 
-pub fn register_agent(path: &str, entry: RegistryEntry) -> Result<(), String> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)
-        .map_err(|e| e.to_string())?;
-    file.lock_exclusive().map_err(|e| e.to_string())?;
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e| e.to_string())?;
-    let mut registry: Vec<RegistryEntry> = if contents.trim().is_empty() {
-        Vec::new()
-    } else {
-        serde_json::from_str(&contents).unwrap_or_default()
-    };
-
-    if registry.iter().any(|e| !e.deleted && e.name == entry.name) {
-        FileExt::unlock(&file).ok();
-        return Err(format!("Agent name '{}' already registered", entry.name));
-    }
-    if registry
-        .iter()
-        .any(|e| !e.deleted && e.p_address == entry.p_address)
-    {
-        FileExt::unlock(&file).ok();
-        return Err(format!(
-            "P address '{}' already registered",
-            entry.p_address
-        ));
-    }
+pub fn load_registry(path: &str) -> Result<Vec<RegistryEntry>, std::io::Error> {
+    let registry = RegistryStore::open(path).read().map_err(io_error)?;
+    Ok(registry.agents().iter().map(RegistryEntry::from).collect())
+}
 
-    registry.push(entry);
+/// Replace the registry with `registry`. Keys and history of agents not in
+/// `registry` are dropped.
+pub fn save_registry(path: &str, registry: &[RegistryEntry]) -> Result<(), std::io::Error> {
+    RegistryStore::open(path)
+        .transaction(|r| {
+            let records = registry
+                .iter()
+                .map(|e| {
+                    let existing = r.agents().iter().rev().find(|a| a.name == e.name && a.p_address.as_deref() == Some(&e.p_address));
+                    AgentRecord {
+                        p_address: Some(e.p_address.clone()),
+                        status: if e.deleted { AgentStatus::Deleted } else { AgentStatus::Active },
+                        last_contact: e.last_contact,
+                        ..existing.cloned().unwrap_or_else(|| AgentRecord::new(&e.name))
+                    }
+                })
+                .collect();
+            r.replace_all(records);
+            Ok(())
+        })
+        .map_err(io_error)
+}
 
-    file.set_len(0).map_err(|e| e.to_string())?;
-    file.seek(std::io::SeekFrom::Start(0))
-        .map_err(|e| e.to_string())?;
-    let json = serde_json::to_string_pretty(&registry).map_err(|e| e.to_string())?;
-    file.write_all(json.as_bytes()).map_err(|e| e.to_string())?;
-    FileExt::unlock(&file).ok();
-    Ok(())
+pub fn register_agent(path: &str, entry: RegistryEntry) -> Result<(), String> {
+    RegistryStore::open(path)
+        .transaction(|r| {
+            r.insert(AgentRecord {
+                p_address: Some(entry.p_address.clone()),
+                status: if entry.deleted { AgentStatus::Deleted } else { AgentStatus::Active },
+                last_contact: entry.last_contact,
+                ..AgentRecord::new(&entry.name)
+            })
+        })
+        .map_err(|e| e.to_string())
 }
 
 pub fn soft_delete_agent(path: &str, name: &str) -> Result<(), String> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)
-        .map_err(|e| e.to_string())?;
-    file.lock_exclusive().map_err(|e| e.to_string())?;
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e| e.to_string())?;
-    let mut registry: Vec<RegistryEntry> = serde_json::from_str(&contents).unwrap_or_default();
-
-    match registry.iter_mut().find(|e| e.name == name && !e.deleted) {
-        Some(entry) => entry.deleted = true,
-        None => {
-            FileExt::unlock(&file).ok();
-            return Err(format!("Agent '{}' not found", name));
-        }
-    }
-
-    file.set_len(0).map_err(|e| e.to_string())?;
-    file.seek(std::io::SeekFrom::Start(0))
-        .map_err(|e| e.to_string())?;
-    let json = serde_json::to_string_pretty(&registry).map_err(|e| e.to_string())?;
-    file.write_all(json.as_bytes()).map_err(|e| e.to_string())?;
-    FileExt::unlock(&file).ok();
-    Ok(())
+    RegistryStore::open(path)
+        .transaction(|r| r.set_status(name, AgentStatus::Deleted))
+        .map_err(|e| e.to_string())
 }
 
 pub fn add_entry(path: &str, entry: RegistryEntry) -> Result<(), String> {
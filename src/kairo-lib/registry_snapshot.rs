@@ -0,0 +1,398 @@
+//! kairo-lib/registry_snapshot.rs
+//! Signed, Merkle-rooted registry snapshots.
+//!
+//! A seed publishes a [`SnapshotHead`]: the Merkle root over every registry
+//! record (RFC 6962 tree hashing), the registry `generation` and the
+//! revocation log version, signed with the seed key. An [`InclusionProof`]
+//! shows that one (name, P address, public key, status) leaf is in the tree,
+//! and can be checked offline with only the pinned seed key. The generation
+//! grows with every registry change, so two validly signed heads with the
+//! same generation but different roots prove the seed served different views;
+//! [`HeadTracker`] catches that and rollbacks to older generations.
+
+use crate::registry_store::{AgentRecord, AgentStatus, Registry};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotLeaf {
+    pub name: String,
+    pub p_address: Option<String>,
+    /// Lowercase hex.
+    pub public_key: Option<String>,
+    pub status: AgentStatus,
+}
+
+impl From<&AgentRecord> for SnapshotLeaf {
+    fn from(a: &AgentRecord) -> Self {
+        Self {
+            name: a.name.clone(),
+            p_address: a.p_address.clone(),
+            public_key: a.public_key.as_deref().map(str::to_ascii_lowercase),
+            status: a.status,
+        }
+    }
+}
+
+impl SnapshotLeaf {
+    fn hash(&self) -> [u8; 32] {
+        let mut h = Sha256::new();
+        h.update([0u8]);
+        h.update(serde_json::to_vec(self).expect("snapshot leaf serializes"));
+        h.finalize().into()
+    }
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update([1u8]);
+    h.update(left);
+    h.update(right);
+    h.finalize().into()
+}
+
+/// Largest power of two strictly below `n` (n > 1).
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn tree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            node_hash(&tree_root(&leaves[..k]), &tree_root(&leaves[k..]))
+        }
+    }
+}
+
+fn audit_path(index: usize, leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split(n);
+    let (mut path, sibling) = if index < k {
+        (audit_path(index, &leaves[..k]), tree_root(&leaves[k..]))
+    } else {
+        (audit_path(index - k, &leaves[k..]), tree_root(&leaves[..k]))
+    };
+    path.push(sibling);
+    path
+}
+
+/// Root implied by `leaf` at `index` of `size` leaves and its audit path.
+fn root_from_path(index: u64, size: u64, leaf: [u8; 32], path: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if index >= size {
+        return None;
+    }
+    let (mut f, mut s, mut r) = (index, size - 1, leaf);
+    for p in path {
+        if s == 0 {
+            return None;
+        }
+        if f & 1 == 1 || f == s {
+            r = node_hash(p, &r);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    (s == 0).then_some(r)
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Signed by a key other than the pinned seed key.
+    WrongIssuer(String),
+    BadSignature,
+    /// The proof does not lead to the signed root.
+    NotIncluded,
+    /// Older than a head already seen.
+    Rollback { seen: u64, generation: u64 },
+    /// Two signed heads for one generation with different roots.
+    Equivocation { generation: u64, roots: (String, String) },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongIssuer(k) => write!(f, "snapshot signed by unknown key {}", k),
+            Self::BadSignature => write!(f, "snapshot signature is invalid"),
+            Self::NotIncluded => write!(f, "inclusion proof does not match the snapshot root"),
+            Self::Rollback { seen, generation } => {
+                write!(f, "snapshot generation {} is older than generation {} already seen", generation, seen)
+            }
+            Self::Equivocation { generation, roots } => write!(
+                f,
+                "seed signed two different roots for generation {}: {} and {}",
+                generation, roots.0, roots.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHead {
+    /// Seed public key, hex.
+    pub issuer: String,
+    /// Registry generation; grows with every committed change.
+    pub generation: u64,
+    pub tree_size: u64,
+    /// Merkle root over the leaves in registry order, hex.
+    pub root: String,
+    /// Revocation log version at this generation.
+    pub revocation_version: u64,
+    /// Unix seconds.
+    pub issued_at: i64,
+    /// Signature over everything above, hex.
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    issuer: &'a str,
+    generation: u64,
+    tree_size: u64,
+    root: &'a str,
+    revocation_version: u64,
+    issued_at: i64,
+}
+
+impl SnapshotHead {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let fields = SignedFields {
+            issuer: &self.issuer,
+            generation: self.generation,
+            tree_size: self.tree_size,
+            root: &self.root,
+            revocation_version: self.revocation_version,
+            issued_at: self.issued_at,
+        };
+        let mut bytes = b"KAIRO-SNAPSHOT-v1\n".to_vec();
+        bytes.extend(serde_json::to_vec(&fields).expect("snapshot head serializes"));
+        bytes
+    }
+
+    pub fn verify(&self, issuer: &VerifyingKey) -> Result<(), SnapshotError> {
+        if !self.issuer.eq_ignore_ascii_case(&hex::encode(issuer.to_bytes())) {
+            return Err(SnapshotError::WrongIssuer(self.issuer.clone()));
+        }
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| Signature::from_slice(&b).ok())
+            .ok_or(SnapshotError::BadSignature)?;
+        issuer.verify(&self.signed_bytes(), &signature).map_err(|_| SnapshotError::BadSignature)
+    }
+}
+
+/// A registry's leaves and the head signed over them.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub head: SnapshotHead,
+    leaves: Vec<SnapshotLeaf>,
+    hashes: Vec<[u8; 32]>,
+}
+
+impl Snapshot {
+    pub fn build(registry: &Registry, key: &SigningKey, now: i64) -> Self {
+        let leaves: Vec<SnapshotLeaf> = registry.agents().iter().map(SnapshotLeaf::from).collect();
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(SnapshotLeaf::hash).collect();
+        let mut head = SnapshotHead {
+            issuer: hex::encode(key.verifying_key().to_bytes()),
+            generation: registry.generation(),
+            tree_size: leaves.len() as u64,
+            root: hex::encode(tree_root(&hashes)),
+            revocation_version: registry.revocation_version(),
+            issued_at: now,
+            signature: String::new(),
+        };
+        head.signature = hex::encode(key.sign(&head.signed_bytes()).to_bytes());
+        Self { head, leaves, hashes }
+    }
+
+    pub fn leaves(&self) -> &[SnapshotLeaf] {
+        &self.leaves
+    }
+
+    pub fn prove(&self, index: usize) -> Option<InclusionProof> {
+        let leaf = self.leaves.get(index)?.clone();
+        Some(InclusionProof {
+            head: self.head.clone(),
+            index: index as u64,
+            leaf,
+            path: audit_path(index, &self.hashes).iter().map(hex::encode).collect(),
+        })
+    }
+
+    /// Proof for the newest record with this P address, or else this name.
+    pub fn prove_entry(&self, p_address: Option<&str>, name: Option<&str>) -> Option<InclusionProof> {
+        let index = self.leaves.iter().rposition(|l| {
+            p_address.is_some_and(|p| l.p_address.as_deref() == Some(p)) || name.is_some_and(|n| l.name == n)
+        })?;
+        self.prove(index)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub head: SnapshotHead,
+    pub index: u64,
+    pub leaf: SnapshotLeaf,
+    /// Sibling hashes from the leaf up, hex.
+    pub path: Vec<String>,
+}
+
+impl InclusionProof {
+    /// Check the head's signature and that `leaf` is in its tree.
+    pub fn verify(&self, issuer: &VerifyingKey) -> Result<(), SnapshotError> {
+        self.head.verify(issuer)?;
+        let path = self
+            .path
+            .iter()
+            .map(|h| hex::decode(h).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SnapshotError::NotIncluded)?;
+        let root = root_from_path(self.index, self.head.tree_size, self.leaf.hash(), &path).ok_or(SnapshotError::NotIncluded)?;
+        if !hex::encode(root).eq_ignore_ascii_case(&self.head.root) {
+            return Err(SnapshotError::NotIncluded);
+        }
+        Ok(())
+    }
+}
+
+/// Fail if two heads (already verified) claim the same generation with
+/// different trees. Heads of different generations never conflict.
+pub fn compare(a: &SnapshotHead, b: &SnapshotHead) -> Result<(), SnapshotError> {
+    if a.issuer.eq_ignore_ascii_case(&b.issuer)
+        && a.generation == b.generation
+        && (a.root != b.root || a.tree_size != b.tree_size)
+    {
+        return Err(SnapshotError::Equivocation { generation: a.generation, roots: (a.root.clone(), b.root.clone()) });
+    }
+    Ok(())
+}
+
+/// The newest head seen from one seed, from any source (the seed itself,
+/// peers, saved proofs).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadTracker {
+    pub latest: Option<SnapshotHead>,
+}
+
+impl HeadTracker {
+    pub fn new() -> Self {
+        Self { latest: None }
+    }
+
+    /// Accept a verified `head` unless it is older than, or contradicts, the
+    /// newest one seen.
+    pub fn observe(&mut self, head: &SnapshotHead, issuer: &VerifyingKey) -> Result<(), SnapshotError> {
+        head.verify(issuer)?;
+        if let Some(seen) = &self.latest {
+            if head.generation < seen.generation {
+                return Err(SnapshotError::Rollback { seen: seen.generation, generation: head.generation });
+            }
+            compare(seen, head)?;
+        }
+        self.latest = Some(head.clone());
+        Ok(())
+    }
+}
+
+impl Default for HeadTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_store::RegistryStore;
+
+    fn registry_with(name: &str, n: usize) -> (RegistryStore, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("kairo-snapshot-{}-{}-{}", name, n, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = RegistryStore::open(dir.join("registry.json"));
+        for i in 0..n {
+            store
+                .transaction(|r| {
+                    r.insert(AgentRecord {
+                        p_address: Some(format!("10.0.0.{}/24", i + 1)),
+                        public_key: Some(format!("{:064x}", i + 1)),
+                        ..AgentRecord::new(&format!("agent-{}", i))
+                    })
+                })
+                .unwrap();
+        }
+        (store, dir)
+    }
+
+    #[test]
+    fn every_leaf_proves_against_the_signed_root() {
+        let seed = SigningKey::from_bytes(&rand::random());
+        for n in [1, 2, 3, 5, 8, 13] {
+            let (store, dir) = registry_with("proofs", n);
+            let snapshot = Snapshot::build(&store.read().unwrap(), &seed, 100);
+            assert_eq!(snapshot.head.generation, n as u64);
+            for i in 0..n {
+                let proof = snapshot.prove(i).unwrap();
+                proof.verify(&seed.verifying_key()).unwrap();
+                // The same path does not prove a different status or position.
+                let revoked = InclusionProof {
+                    leaf: SnapshotLeaf { status: AgentStatus::Revoked, ..proof.leaf.clone() },
+                    ..proof.clone()
+                };
+                assert!(matches!(revoked.verify(&seed.verifying_key()), Err(SnapshotError::NotIncluded)));
+                if n > 1 {
+                    let moved = InclusionProof { index: ((i + 1) % n) as u64, ..proof.clone() };
+                    assert!(moved.verify(&seed.verifying_key()).is_err());
+                }
+            }
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn trackers_catch_split_views_and_rollbacks() {
+        let seed = SigningKey::from_bytes(&rand::random());
+        let (store, dir) = registry_with("tracker", 3);
+        let honest = Snapshot::build(&store.read().unwrap(), &seed, 100);
+        let proof = honest.prove_entry(Some("10.0.0.2/24"), None).unwrap();
+        assert_eq!(proof.leaf.name, "agent-1");
+
+        // A view that hides agent-1's key swap but claims the same generation.
+        let mut forked = store.read().unwrap();
+        forked.update("agent-1", |a| a.public_key = Some("f".repeat(64))).unwrap();
+        let mut fork = Snapshot::build(&forked, &seed, 101).head;
+        fork.generation = honest.head.generation;
+        fork.signature = hex::encode(seed.sign(&fork.signed_bytes()).to_bytes());
+
+        let mut tracker = HeadTracker::new();
+        tracker.observe(&proof.head, &seed.verifying_key()).unwrap();
+        assert!(matches!(tracker.observe(&fork, &seed.verifying_key()), Err(SnapshotError::Equivocation { .. })));
+
+        store.transaction(|r| r.set_status("agent-0", AgentStatus::Revoked)).unwrap();
+        let newer = Snapshot::build(&store.read().unwrap(), &seed, 102).head;
+        assert_eq!(newer.revocation_version, 1);
+        tracker.observe(&newer, &seed.verifying_key()).unwrap();
+        assert!(matches!(
+            tracker.observe(&honest.head, &seed.verifying_key()),
+            Err(SnapshotError::Rollback { .. })
+        ));
+        let other = SigningKey::from_bytes(&rand::random());
+        assert!(matches!(newer.verify(&other.verifying_key()), Err(SnapshotError::WrongIssuer(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,625 @@
+//! kairo-lib/registry_store.rs
+//! Shared agent registry: one versioned JSON document with transactions.
+//!
+//! [`RegistryStore::transaction`] holds an exclusive lock on `<path>.lock`,
+//! loads the document, runs the closure and, only if it succeeds, replaces
+//! the file atomically (temp file, fsync, rename). Readers take a shared lock
+//! and never see a half-written file. Older layouts (the bare arrays written
+//! by `kairo_lib::registry` and both seed nodes) are migrated on load and
+//! rewritten in the current schema by the next transaction.
+//!
+//! Since schema 2 the document also carries the seed's revocation log (see
+//! `kairo_lib::revocation`): every record that becomes revoked with a key
+//! appends an entry in the same transaction. Since schema 3 it counts
+//! committed changes in `generation`, which signed snapshots
+//! (`kairo_lib::registry_snapshot`) are numbered by. Schema 4 adds the
+//! record `origin` and the `conflicted` status used when seeds merge their
+//! registries (`kairo_lib::federation`).
+
+use crate::revocation::RevocationEntry;
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// Ordered by precedence: when seeds merge a record, the greater status wins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentStatus {
+    #[default]
+    Active,
+    /// Lost a name, P address or key to an earlier registration on another seed.
+    Conflicted,
+    Deleted,
+    Revoked,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRecord {
+    /// Agent name; the seed node's `agent_id`.
+    pub name: String,
+    #[serde(default)]
+    pub p_address: Option<String>,
+    /// Ed25519 public key, lowercase hex.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub status: AgentStatus,
+    #[serde(default)]
+    pub registered_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_contact: Option<DateTime<Utc>>,
+    /// Name of the revoked agent this one was reissued from.
+    #[serde(default)]
+    pub replaces: Option<String>,
+    /// Id of the seed that accepted the registration.
+    #[serde(default)]
+    pub origin: Option<String>,
+}
+
+impl AgentRecord {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            p_address: None,
+            public_key: None,
+            status: AgentStatus::Active,
+            registered_at: Some(Utc::now()),
+            last_contact: None,
+            replaces: None,
+            origin: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == AgentStatus::Active
+    }
+
+    /// The same registration as seen by two seeds.
+    fn same_record(&self, other: &AgentRecord) -> bool {
+        self.name == other.name && self.origin == other.origin && self.registered_at == other.registered_at
+    }
+
+    /// Merge `other`'s copy of this record; every field takes the greater value.
+    fn join(&mut self, other: &AgentRecord) {
+        self.status = self.status.max(other.status);
+        self.last_contact = self.last_contact.max(other.last_contact);
+        self.p_address = self.p_address.take().max(other.p_address.clone());
+        self.public_key = self.public_key.take().max(other.public_key.clone());
+        self.replaces = self.replaces.take().max(other.replaces.clone());
+    }
+
+    /// Earlier registrations win conflicts; ties go to the smaller origin.
+    fn precedence(&self) -> (Option<DateTime<Utc>>, Option<&str>, &str) {
+        (self.registered_at, self.origin.as_deref(), &self.name)
+    }
+}
+
+/// What [`Registry::merge`] changed.
+#[derive(Debug, Default, Clone)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    /// Previously active records that lost to an earlier registration.
+    pub conflicted: Vec<AgentRecord>,
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    /// The file exists but cannot be parsed; it is left untouched.
+    Corrupt(String),
+    /// Written by a newer build.
+    UnsupportedVersion(u32),
+    /// An active agent already holds this name, P address or key.
+    Conflict(String),
+    NotFound(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "registry I/O error: {}", e),
+            Self::Corrupt(e) => write!(f, "registry file is corrupt: {}", e),
+            Self::UnsupportedVersion(v) => write!(f, "registry schema version {} is newer than {}", v, SCHEMA_VERSION),
+            Self::Conflict(e) | Self::NotFound(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<io::Error> for RegistryError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    schema_version: u32,
+    /// Added in schema 3; 0 for older files.
+    #[serde(default)]
+    generation: u64,
+    agents: Vec<AgentRecord>,
+    /// Added in schema 2; derived from revoked records for older files.
+    #[serde(default)]
+    revocations: Vec<RevocationEntry>,
+}
+
+/// Registry contents with indexes over active agents by name, P address and
+/// public key.
+#[derive(Debug, Default, Clone)]
+pub struct Registry {
+    generation: u64,
+    agents: Vec<AgentRecord>,
+    revocations: Vec<RevocationEntry>,
+    by_name: HashMap<String, usize>,
+    by_p_address: HashMap<String, usize>,
+    by_public_key: HashMap<String, usize>,
+}
+
+impl Registry {
+    fn from_records(agents: Vec<AgentRecord>, revocations: Vec<RevocationEntry>) -> Self {
+        let mut registry = Self { agents, revocations, ..Default::default() };
+        registry.reindex();
+        // Revocations predating the log get the record's last known time.
+        registry.log_revocations(|a| a.last_contact.or(a.registered_at).unwrap_or(DateTime::UNIX_EPOCH));
+        registry
+    }
+
+    /// Append a log entry for each revoked key not logged yet.
+    fn log_revocations(&mut self, revoked_at: impl Fn(&AgentRecord) -> DateTime<Utc>) {
+        let mut logged: HashSet<String> = self.revocations.iter().map(|e| e.public_key.clone()).collect();
+        let mut serial = self.revocation_version();
+        for a in self.agents.iter().filter(|a| a.status == AgentStatus::Revoked) {
+            let Some(key) = a.public_key.as_deref().map(str::to_ascii_lowercase) else { continue };
+            if !logged.insert(key.clone()) {
+                continue;
+            }
+            serial += 1;
+            self.revocations.push(RevocationEntry { serial, name: a.name.clone(), public_key: key, revoked_at: revoked_at(a) });
+        }
+    }
+
+    fn reindex(&mut self) {
+        self.by_name.clear();
+        self.by_p_address.clear();
+        self.by_public_key.clear();
+        for (i, a) in self.agents.iter().enumerate().filter(|(_, a)| a.is_active()) {
+            self.by_name.entry(a.name.clone()).or_insert(i);
+            if let Some(p) = &a.p_address {
+                self.by_p_address.entry(p.clone()).or_insert(i);
+            }
+            if let Some(k) = &a.public_key {
+                self.by_public_key.entry(k.to_ascii_lowercase()).or_insert(i);
+            }
+        }
+    }
+
+    /// Every record, including revoked and deleted ones, in insertion order.
+    pub fn agents(&self) -> &[AgentRecord] {
+        &self.agents
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &AgentRecord> {
+        self.agents.iter().filter(|a| a.is_active())
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&AgentRecord> {
+        self.by_name.get(name).map(|&i| &self.agents[i])
+    }
+
+    pub fn by_p_address(&self, p_address: &str) -> Option<&AgentRecord> {
+        self.by_p_address.get(p_address).map(|&i| &self.agents[i])
+    }
+
+    pub fn by_public_key(&self, public_key: &str) -> Option<&AgentRecord> {
+        self.by_public_key.get(&public_key.to_ascii_lowercase()).map(|&i| &self.agents[i])
+    }
+
+    /// Number of committed transactions that changed the registry.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The revocation log, oldest first.
+    pub fn revocations(&self) -> &[RevocationEntry] {
+        &self.revocations
+    }
+
+    /// Serial of the newest revocation; 0 if none.
+    pub fn revocation_version(&self) -> u64 {
+        self.revocations.last().map_or(0, |e| e.serial)
+    }
+
+    /// Most recent record named `name`, whatever its status.
+    pub fn latest(&self, name: &str) -> Option<&AgentRecord> {
+        self.agents.iter().rev().find(|a| a.name == name)
+    }
+
+    /// Fail if active `record` shares its name, P address or key with an
+    /// active agent other than the one at `except`.
+    fn check_unique(&self, record: &AgentRecord, except: Option<usize>) -> Result<(), RegistryError> {
+        if !record.is_active() {
+            return Ok(());
+        }
+        let other = |hit: Option<&usize>| hit.is_some_and(|&i| Some(i) != except);
+        if other(self.by_name.get(&record.name)) {
+            return Err(RegistryError::Conflict(format!("Agent name '{}' already registered", record.name)));
+        }
+        if let Some(p) = record.p_address.as_deref().filter(|p| other(self.by_p_address.get(*p))) {
+            return Err(RegistryError::Conflict(format!("P address '{}' already registered", p)));
+        }
+        if let Some(&i) = record.public_key.as_deref().and_then(|k| self.by_public_key.get(k)).filter(|&&i| Some(i) != except) {
+            return Err(RegistryError::Conflict(format!("Public key already registered to {}", self.agents[i].name)));
+        }
+        if let Some(k) = record.public_key.as_deref().filter(|k| self.revocations.iter().any(|e| e.public_key == *k)) {
+            return Err(RegistryError::Conflict(format!("Public key {} has been revoked", k)));
+        }
+        Ok(())
+    }
+
+    /// Add an agent; an active one must not share its name, P address or key
+    /// with another active agent.
+    pub fn insert(&mut self, mut record: AgentRecord) -> Result<(), RegistryError> {
+        record.public_key = record.public_key.map(|k| k.to_ascii_lowercase());
+        self.check_unique(&record, None)?;
+        self.agents.push(record);
+        self.reindex();
+        self.log_revocations(|_| Utc::now());
+        Ok(())
+    }
+
+    /// Change the active agent `name` in place, under the same uniqueness rules.
+    pub fn update<T>(&mut self, name: &str, f: impl FnOnce(&mut AgentRecord) -> T) -> Result<T, RegistryError> {
+        let i = *self
+            .by_name
+            .get(name)
+            .ok_or_else(|| RegistryError::NotFound(format!("Agent '{}' not found", name)))?;
+        let mut record = self.agents[i].clone();
+        let out = f(&mut record);
+        record.public_key = record.public_key.map(|k| k.to_ascii_lowercase());
+        self.check_unique(&record, Some(i))?;
+        self.agents[i] = record;
+        self.reindex();
+        self.log_revocations(|_| Utc::now());
+        Ok(out)
+    }
+
+    pub fn set_status(&mut self, name: &str, status: AgentStatus) -> Result<(), RegistryError> {
+        self.update(name, |a| a.status = status)
+    }
+
+    /// Add `record` as the successor of `old`, which must have been revoked.
+    /// The caller checks that whoever asks holds `record`'s key.
+    pub fn reissue(&mut self, old: &str, record: AgentRecord) -> Result<(), RegistryError> {
+        if self.by_name(old).is_some() {
+            return Err(RegistryError::Conflict(format!("Agent '{}' is not revoked", old)));
+        }
+        if !self.agents.iter().any(|a| a.name == old && a.status == AgentStatus::Revoked) {
+            return Err(RegistryError::NotFound(format!("No revoked agent '{}'", old)));
+        }
+        self.insert(AgentRecord { replaces: Some(old.to_string()), ..record })
+    }
+
+    /// Merge another seed's records and revocation log into this registry.
+    ///
+    /// Records are the same if name, origin and registration time match;
+    /// their status and last contact take the greater value. Then every
+    /// active record holding a revoked key is revoked, and of active records
+    /// sharing a name, P address or key the earliest registration (by time,
+    /// then origin) keeps it and the others become `conflicted`, giving up
+    /// their P address. Merging is commutative and idempotent, so seeds that
+    /// exchange state converge.
+    pub fn merge(&mut self, remote: &[AgentRecord], revocations: &[RevocationEntry]) -> MergeReport {
+        let before: Vec<AgentRecord> = self.agents.clone();
+        for r in remote {
+            let r = AgentRecord { public_key: r.public_key.as_deref().map(str::to_ascii_lowercase), ..r.clone() };
+            match self.agents.iter_mut().find(|a| a.same_record(&r)) {
+                Some(a) => a.join(&r),
+                None => self.agents.push(r),
+            }
+        }
+        self.agents.sort_by(|a, b| a.precedence().cmp(&b.precedence()));
+
+        let revoked: HashSet<String> = self
+            .agents
+            .iter()
+            .filter(|a| a.status == AgentStatus::Revoked)
+            .filter_map(|a| a.public_key.clone())
+            .chain(self.revocations.iter().chain(revocations).map(|e| e.public_key.clone()))
+            .collect();
+        let (mut names, mut addrs, mut keys) = (HashSet::new(), HashSet::new(), HashSet::new());
+        for a in self.agents.iter_mut().filter(|a| a.is_active()) {
+            if a.public_key.as_ref().is_some_and(|k| revoked.contains(k)) {
+                a.status = AgentStatus::Revoked;
+                continue;
+            }
+            let taken = names.contains(&a.name)
+                || a.p_address.as_ref().is_some_and(|p| addrs.contains(p))
+                || a.public_key.as_ref().is_some_and(|k| keys.contains(k));
+            if taken {
+                a.status = AgentStatus::Conflicted;
+                continue;
+            }
+            names.insert(a.name.clone());
+            addrs.extend(a.p_address.clone());
+            keys.extend(a.public_key.clone());
+        }
+        // Stale copies from other seeds may still carry the address; drop it on every merge.
+        for a in self.agents.iter_mut().filter(|a| a.status == AgentStatus::Conflicted) {
+            a.p_address = None;
+        }
+        self.reindex();
+        let times: HashMap<&str, DateTime<Utc>> = revocations.iter().map(|e| (e.public_key.as_str(), e.revoked_at)).collect();
+        self.log_revocations(|a| a.public_key.as_deref().and_then(|k| times.get(k).copied()).unwrap_or_else(Utc::now));
+
+        let mut report = MergeReport::default();
+        for a in &self.agents {
+            match before.iter().find(|b| b.same_record(a)) {
+                None => report.added += 1,
+                Some(b) if b == a => {}
+                Some(b) => {
+                    report.updated += 1;
+                    if b.is_active() && a.status == AgentStatus::Conflicted {
+                        report.conflicted.push(a.clone());
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Replace every record (e.g. a bulk import); no uniqueness checks. The
+    /// revocation log is kept and only ever grows.
+    pub fn replace_all(&mut self, agents: Vec<AgentRecord>) {
+        let revocations = std::mem::take(&mut self.revocations);
+        *self = Self { generation: self.generation, ..Self::from_records(agents, revocations) };
+    }
+}
+
+/// Records from the pre-versioned bare-array layouts.
+fn migrate_v0(items: Vec<Value>) -> Result<Vec<AgentRecord>, RegistryError> {
+    let text = |v: &Value, k: &str| v.get(k).and_then(Value::as_str).map(str::to_string);
+    let time = |v: &Value, k: &str| text(v, k).and_then(|s| DateTime::parse_from_rfc3339(&s).ok()).map(|t| t.with_timezone(&Utc));
+    items
+        .iter()
+        .map(|v| {
+            // `kairo_lib::registry` used `name`, the server seed `agent_id` and
+            // the mesh seed only `p_address`.
+            let name = text(v, "name")
+                .or_else(|| text(v, "agent_id"))
+                .or_else(|| text(v, "p_address"))
+                .ok_or_else(|| RegistryError::Corrupt(format!("registry entry without a name: {}", v)))?;
+            let status = match text(v, "status").as_deref() {
+                Some("revoked") => AgentStatus::Revoked,
+                Some("deleted") => AgentStatus::Deleted,
+                _ if v.get("deleted").and_then(Value::as_bool) == Some(true) => AgentStatus::Deleted,
+                _ => AgentStatus::Active,
+            };
+            // Early seeds stored the agent id or "" as the key; drop anything
+            // that is not a 32-byte hex key.
+            let public_key = text(v, "public_key")
+                .filter(|k| k.len() == 64 && hex::decode(k).is_ok())
+                .map(|k| k.to_ascii_lowercase());
+            Ok(AgentRecord {
+                name,
+                p_address: text(v, "p_address"),
+                public_key,
+                status,
+                registered_at: time(v, "registered_at"),
+                last_contact: time(v, "last_contact"),
+                replaces: text(v, "replaces"),
+                origin: None,
+            })
+        })
+        .collect()
+}
+
+fn parse(bytes: &[u8]) -> Result<Registry, RegistryError> {
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Registry::default());
+    }
+    let value: Value = serde_json::from_slice(bytes).map_err(|e| RegistryError::Corrupt(e.to_string()))?;
+    let (generation, agents, revocations) = match value {
+        Value::Array(items) => (0, migrate_v0(items)?, Vec::new()),
+        Value::Object(ref obj) => {
+            let version = obj.get("schema_version").and_then(Value::as_u64).unwrap_or(0) as u32;
+            if version > SCHEMA_VERSION {
+                return Err(RegistryError::UnsupportedVersion(version));
+            }
+            let doc: Document = serde_json::from_value(value).map_err(|e| RegistryError::Corrupt(e.to_string()))?;
+            (doc.generation, doc.agents, doc.revocations)
+        }
+        _ => return Err(RegistryError::Corrupt("expected an object or array".to_string())),
+    };
+    Ok(Registry { generation, ..Registry::from_records(agents, revocations) })
+}
+
+pub struct RegistryStore {
+    path: PathBuf,
+}
+
+impl RegistryStore {
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn lock_file(&self) -> io::Result<File> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".lock");
+        OpenOptions::new().read(true).write(true).create(true).truncate(false).open(PathBuf::from(name))
+    }
+
+    fn load(&self) -> Result<Registry, RegistryError> {
+        match fs::read(&self.path) {
+            Ok(bytes) => parse(&bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Registry::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// A consistent snapshot of the registry.
+    pub fn read(&self) -> Result<Registry, RegistryError> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+        let result = self.load();
+        FileExt::unlock(&lock).ok();
+        result
+    }
+
+    /// Run `f` against the registry and persist its changes if it returns Ok.
+    pub fn transaction<T>(&self, f: impl FnOnce(&mut Registry) -> Result<T, RegistryError>) -> Result<T, RegistryError> {
+        let lock = self.lock_file()?;
+        lock.lock_exclusive()?;
+        let result = self.load().and_then(|mut registry| {
+            let before = (registry.agents.clone(), registry.revocations.len());
+            let out = f(&mut registry)?;
+            if (&registry.agents, registry.revocations.len()) != (&before.0, before.1) {
+                registry.generation += 1;
+            }
+            self.write(&registry)?;
+            Ok(out)
+        });
+        FileExt::unlock(&lock).ok();
+        result
+    }
+
+    fn write(&self, registry: &Registry) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let doc = Document {
+            schema_version: SCHEMA_VERSION,
+            generation: registry.generation,
+            agents: registry.agents.clone(),
+            revocations: registry.revocations.clone(),
+        };
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(format!(".tmp.{}", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        let mut out = File::create(&tmp)?;
+        out.write_all(&serde_json::to_vec_pretty(&doc)?)?;
+        out.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kairo-registry-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("registry.json")
+    }
+
+    fn agent(name: &str, p: &str, key: char) -> AgentRecord {
+        AgentRecord {
+            p_address: Some(p.to_string()),
+            public_key: Some(key.to_string().repeat(64)),
+            ..AgentRecord::new(name)
+        }
+    }
+
+    #[test]
+    fn transactions_enforce_unique_indexes_and_roll_back_on_error() {
+        let path = temp_path("tx");
+        let store = RegistryStore::open(&path);
+        store.transaction(|r| r.insert(agent("a", "10.0.0.1/24", 'a'))).unwrap();
+
+        let dup_key = store.transaction(|r| {
+            r.insert(agent("b", "10.0.0.2/24", 'b'))?;
+            r.insert(agent("c", "10.0.0.3/24", 'A'))
+        });
+        assert!(matches!(dup_key, Err(RegistryError::Conflict(_))));
+        // Nothing from the failed transaction was written.
+        assert!(store.read().unwrap().by_name("b").is_none());
+
+        store.transaction(|r| r.set_status("a", AgentStatus::Revoked)).unwrap();
+        // A revoked key is logged and cannot come back under any name.
+        let reuse = store.transaction(|r| r.insert(agent("a", "10.0.0.1/24", 'a')));
+        assert!(matches!(reuse, Err(RegistryError::Conflict(_))));
+        store.transaction(|r| r.insert(agent("a", "10.0.0.1/24", 'd'))).unwrap();
+        let reg = store.read().unwrap();
+        assert_eq!(reg.agents().len(), 2);
+        assert_eq!(reg.revocation_version(), 1);
+        assert_eq!(reg.revocations()[0].public_key, "a".repeat(64));
+        assert!(reg.by_public_key(&"A".repeat(64)).is_none());
+        assert_eq!(reg.by_public_key(&"D".repeat(64)).unwrap().status, AgentStatus::Active);
+        assert_eq!(reg.by_p_address("10.0.0.1/24").unwrap().name, "a");
+        assert!(matches!(
+            store.transaction(|r| r.set_status("zz", AgentStatus::Revoked)),
+            Err(RegistryError::NotFound(_))
+        ));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reissue_needs_a_revoked_predecessor() {
+        let mut reg = Registry::default();
+        reg.insert(agent("a", "10.0.0.1/24", 'a')).unwrap();
+        assert!(matches!(reg.reissue("a", agent("a2", "10.0.0.2/24", 'b')), Err(RegistryError::Conflict(_))));
+        assert!(matches!(reg.reissue("zz", agent("a2", "10.0.0.2/24", 'b')), Err(RegistryError::NotFound(_))));
+
+        reg.set_status("a", AgentStatus::Revoked).unwrap();
+        // The revoked key cannot carry over to the successor.
+        assert!(matches!(reg.reissue("a", agent("a2", "10.0.0.2/24", 'a')), Err(RegistryError::Conflict(_))));
+        reg.reissue("a", agent("a2", "10.0.0.2/24", 'b')).unwrap();
+        assert_eq!(reg.by_name("a2").unwrap().replaces.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn legacy_arrays_migrate_and_corrupt_files_are_not_overwritten() {
+        let path = temp_path("migrate");
+        let legacy = serde_json::json!([
+            { "name": "alice", "p_address": "10.0.0.5/24", "deleted": false },
+            { "agent_id": "bob", "public_key": "bob", "registered_at": "2025-01-01T00:00:00Z", "status": "revoked" },
+            { "p_address": "10.0.0.7/24", "public_key": "", "status": "active" },
+        ]);
+        fs::write(&path, legacy.to_string()).unwrap();
+        let store = RegistryStore::open(&path);
+        let reg = store.read().unwrap();
+        assert_eq!(reg.by_name("alice").unwrap().p_address.as_deref(), Some("10.0.0.5/24"));
+        assert_eq!(reg.latest("bob").unwrap().status, AgentStatus::Revoked);
+        assert_eq!(reg.latest("bob").unwrap().public_key, None);
+        // bob's key was dropped, so there is nothing to log for him.
+        assert_eq!(reg.revocation_version(), 0);
+        assert!(reg.by_p_address("10.0.0.7/24").is_some());
+
+        store.transaction(|_| Ok(())).unwrap();
+        let doc: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(doc["schema_version"], SCHEMA_VERSION);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(store.read(), Err(RegistryError::Corrupt(_))));
+        assert!(store.transaction(|_| Ok(())).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ not json");
+
+        fs::write(&path, r#"{"schema_version": 99, "agents": []}"#).unwrap();
+        assert!(matches!(store.read(), Err(RegistryError::UnsupportedVersion(99))));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+//! kairo-lib/revocation.rs
+//! Seed-signed, versioned revocation lists.
+//!
+//! A seed appends one [`RevocationEntry`] to its registry's revocation log
+//! for every revoked key; an entry's `serial` is its position in the log and
+//! the list's `version` is the newest serial. `GET /revocations` serves the
+//! whole log and `GET /revocations?since=N` only the entries after `N`, both
+//! as a [`RevocationList`] signed with the seed key. Nodes keep a
+//! [`RevocationCache`] that applies full lists and deltas in order, refuses
+//! gaps and rollbacks, and persists the signed lists so the cache can be
+//! re-verified after a restart.
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationEntry {
+    /// Position in the seed's revocation log, starting at 1.
+    pub serial: u64,
+    /// Registry name of the revoked agent.
+    pub name: String,
+    /// Revoked Ed25519 key, lowercase hex.
+    pub public_key: String,
+    pub revoked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevocationList {
+    /// Seed public key, hex.
+    pub issuer: String,
+    /// Serial of the newest revocation; 0 if nothing is revoked.
+    pub version: u64,
+    /// The entries after this version are included; 0 for the full list.
+    pub since: u64,
+    /// Unix seconds.
+    pub issued_at: i64,
+    pub entries: Vec<RevocationEntry>,
+    /// Signature over everything above, hex.
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    issuer: &'a str,
+    version: u64,
+    since: u64,
+    issued_at: i64,
+    entries: &'a [RevocationEntry],
+}
+
+#[derive(Debug)]
+pub enum RevocationError {
+    /// Signed by a key other than the pinned seed key.
+    WrongIssuer(String),
+    BadSignature,
+    /// Entries missing, out of order or outside `since..=version`.
+    Malformed(String),
+    /// A delta starts after the cached version; fetch the full list.
+    Gap { have: u64, since: u64 },
+    /// Older than what is already cached.
+    Rollback { have: u64, version: u64 },
+    Io(io::Error),
+}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongIssuer(k) => write!(f, "revocation list issued by unknown key {}", k),
+            Self::BadSignature => write!(f, "revocation list signature is invalid"),
+            Self::Malformed(e) => write!(f, "malformed revocation list: {}", e),
+            Self::Gap { have, since } => write!(f, "revocation delta since {} does not follow cached version {}", since, have),
+            Self::Rollback { have, version } => write!(f, "revocation list version {} is older than cached version {}", version, have),
+            Self::Io(e) => write!(f, "revocation cache I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RevocationError {}
+
+impl From<io::Error> for RevocationError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl RevocationList {
+    /// Sign the entries of `log` after `since` (the full log for 0).
+    pub fn issue(log: &[RevocationEntry], since: u64, key: &SigningKey, now: i64) -> Self {
+        let version = log.last().map_or(0, |e| e.serial);
+        let since = since.min(version);
+        let mut list = Self {
+            issuer: hex::encode(key.verifying_key().to_bytes()),
+            version,
+            since,
+            issued_at: now,
+            entries: log.iter().filter(|e| e.serial > since).cloned().collect(),
+            signature: String::new(),
+        };
+        list.signature = hex::encode(key.sign(&list.signed_bytes()).to_bytes());
+        list
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.since == 0
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let fields = SignedFields {
+            issuer: &self.issuer,
+            version: self.version,
+            since: self.since,
+            issued_at: self.issued_at,
+            entries: &self.entries,
+        };
+        let mut bytes = b"KAIRO-CRL-v1\n".to_vec();
+        bytes.extend(serde_json::to_vec(&fields).expect("revocation list serializes"));
+        bytes
+    }
+
+    /// Check the signature against the pinned seed key and that the entries
+    /// are exactly serials `since + 1 ..= version`.
+    pub fn verify(&self, issuer: &VerifyingKey) -> Result<(), RevocationError> {
+        if !self.issuer.eq_ignore_ascii_case(&hex::encode(issuer.to_bytes())) {
+            return Err(RevocationError::WrongIssuer(self.issuer.clone()));
+        }
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| Signature::from_slice(&b).ok())
+            .ok_or(RevocationError::BadSignature)?;
+        issuer.verify(&self.signed_bytes(), &signature).map_err(|_| RevocationError::BadSignature)?;
+        let serials: Vec<u64> = self.entries.iter().map(|e| e.serial).collect();
+        if serials != (self.since + 1..=self.version).collect::<Vec<_>>() {
+            return Err(RevocationError::Malformed(format!(
+                "expected serials {}..={}, got {:?}",
+                self.since + 1,
+                self.version,
+                serials
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Revoked keys known to a node, built from verified lists.
+#[derive(Debug, Clone)]
+pub struct RevocationCache {
+    issuer: VerifyingKey,
+    /// The last full list followed by the deltas applied since.
+    lists: Vec<RevocationList>,
+    revoked: HashSet<String>,
+    version: u64,
+}
+
+impl RevocationCache {
+    pub fn new(issuer: VerifyingKey) -> Self {
+        Self { issuer, lists: Vec::new(), revoked: HashSet::new(), version: 0 }
+    }
+
+    pub fn issuer(&self) -> &VerifyingKey {
+        &self.issuer
+    }
+
+    /// Newest revocation serial applied.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Number of revoked keys.
+    pub fn len(&self) -> usize {
+        self.revoked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.revoked.is_empty()
+    }
+
+    /// Deltas applied since the last full list.
+    pub fn deltas(&self) -> usize {
+        self.lists.len().saturating_sub(1)
+    }
+
+    pub fn is_revoked(&self, public_key: &str) -> bool {
+        self.revoked.contains(&public_key.to_ascii_lowercase())
+    }
+
+    /// Verify `list` and merge it. Returns the number of newly revoked keys.
+    pub fn apply(&mut self, list: RevocationList) -> Result<usize, RevocationError> {
+        list.verify(&self.issuer)?;
+        if list.version < self.version {
+            return Err(RevocationError::Rollback { have: self.version, version: list.version });
+        }
+        if list.since > self.version {
+            return Err(RevocationError::Gap { have: self.version, since: list.since });
+        }
+        // A full list at the cached version still replaces the delta chain.
+        if list.version == self.version && !list.is_full() {
+            return Ok(0);
+        }
+        let before = self.revoked.len();
+        for entry in list.entries.iter().filter(|e| e.serial > self.version) {
+            self.revoked.insert(entry.public_key.to_ascii_lowercase());
+        }
+        self.version = list.version;
+        if list.is_full() {
+            self.lists.clear();
+        }
+        self.lists.push(list);
+        Ok(self.revoked.len() - before)
+    }
+
+    /// Rebuild the cache from `path`, re-verifying every stored list. A
+    /// missing file is an empty cache.
+    pub fn load(path: impl AsRef<Path>, issuer: VerifyingKey) -> Result<Self, RevocationError> {
+        let mut cache = Self::new(issuer);
+        let bytes = match fs::read(path.as_ref()) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e.into()),
+        };
+        let lists: Vec<RevocationList> =
+            serde_json::from_slice(&bytes).map_err(|e| RevocationError::Malformed(e.to_string()))?;
+        for list in lists {
+            cache.apply(list)?;
+        }
+        Ok(cache)
+    }
+
+    /// Write the stored lists to `path` atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".tmp.{}", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        let mut out = File::create(&tmp)?;
+        out.write_all(&serde_json::to_vec_pretty(&self.lists)?)?;
+        out.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(serial: u64, key: char) -> RevocationEntry {
+        RevocationEntry {
+            serial,
+            name: format!("agent-{}", serial),
+            public_key: key.to_string().repeat(64),
+            revoked_at: DateTime::from_timestamp(1_700_000_000 + serial as i64, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn deltas_apply_in_order_and_forgeries_are_refused() {
+        let seed = SigningKey::from_bytes(&rand::random());
+        let log = vec![entry(1, 'a'), entry(2, 'b'), entry(3, 'c')];
+        let mut cache = RevocationCache::new(seed.verifying_key());
+
+        assert_eq!(cache.apply(RevocationList::issue(&log[..1], 0, &seed, 10)).unwrap(), 1);
+        assert!(cache.is_revoked(&"A".repeat(64)));
+        assert!(!cache.is_revoked(&"b".repeat(64)));
+
+        // A delta that skips serial 2 cannot be applied; one that follows can.
+        let gap = RevocationList::issue(&log, 2, &seed, 11);
+        assert!(matches!(cache.apply(gap), Err(RevocationError::Gap { have: 1, since: 2 })));
+        assert_eq!(cache.apply(RevocationList::issue(&log, 1, &seed, 11)).unwrap(), 2);
+        assert_eq!((cache.version(), cache.len(), cache.deltas()), (3, 3, 1));
+        let stale = RevocationList::issue(&log[..2], 0, &seed, 12);
+        assert!(matches!(cache.apply(stale), Err(RevocationError::Rollback { have: 3, version: 2 })));
+
+        // Dropping an entry or signing with another key breaks verification.
+        let mut trimmed = RevocationList::issue(&log, 0, &seed, 13);
+        trimmed.entries.remove(1);
+        assert!(matches!(cache.apply(trimmed), Err(RevocationError::BadSignature)));
+        let other = SigningKey::from_bytes(&rand::random());
+        let foreign = RevocationList::issue(&log, 0, &other, 13);
+        assert!(matches!(cache.apply(foreign), Err(RevocationError::WrongIssuer(_))));
+
+        let path = std::env::temp_dir().join(format!("kairo-crl-{}.json", std::process::id()));
+        cache.save(&path).unwrap();
+        let reloaded = RevocationCache::load(&path, seed.verifying_key()).unwrap();
+        assert_eq!((reloaded.version(), reloaded.len()), (3, 3));
+        assert!(RevocationCache::load(&path, other.verifying_key()).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
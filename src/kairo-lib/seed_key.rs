@@ -0,0 +1,72 @@
+//! kairo-lib/seed_key.rs
+//! The seed node's Ed25519 signing key, kept as 32 hex-encoded bytes.
+//!
+//! Everything a seed publishes for other nodes to trust (revocation lists,
+//! registry snapshots) is signed with this key; nodes pin its public half in
+//! their config.
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const DEFAULT_SEED_KEY_FILE: &str = ".kairo/config/seed_signing_key";
+/// JSON array of hex public keys allowed to revoke agents on this seed.
+pub const DEFAULT_SEED_ADMINS_FILE: &str = ".kairo/config/seed_admins.json";
+
+/// Load the key at `path`, creating it on first start.
+/// On unix the file is created with mode 0600, so the key is never readable
+/// by others, not even briefly.
+pub fn load_or_create(path: impl AsRef<Path>) -> io::Result<SigningKey> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let bytes: [u8; 32] = hex::decode(text.trim())
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{}: expected 32 hex-encoded bytes", path.display())))?;
+            Ok(SigningKey::from_bytes(&bytes))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let key = SigningKey::from_bytes(&rand::random());
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            options.open(path)?.write_all(hex::encode(key.to_bytes()).as_bytes())?;
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Parse a pinned seed public key (hex).
+pub fn parse_public_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .map_err(|_| "Invalid seed public key format".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid seed public key length".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| "Invalid seed public key".to_string())
+}
+
+/// Administrator keys from the file at `path`, plus the seed's own key, which
+/// is all there is when the file does not exist.
+pub fn load_admin_keys(path: impl AsRef<Path>, seed: &VerifyingKey) -> Result<Vec<VerifyingKey>, String> {
+    let path = path.as_ref();
+    let mut keys = vec![*seed];
+    let listed: Vec<String> = match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    for hex_key in &listed {
+        keys.push(parse_public_key(hex_key).map_err(|e| format!("{}: {}", path.display(), e))?);
+    }
+    Ok(keys)
+}
@@ -5,25 +5,30 @@ mod seed_node;
 use kairo_lib::packet::AiTcpPacket;
 use tokio::sync::Mutex;
 use warp::{Filter, Rejection, Reply};
-use crate::seed_node::{read_registry, AgentInfo};
+use kairo_lib::registry_store::Registry;
+use kairo_lib::revocation::{RevocationCache, RevocationError, RevocationList};
 use std::sync::Arc;
 
-// (Existing structs like AgentInfo, RegisterRequest, etc.)
+// (Existing structs like RegisterRequest, etc.)
 
 // --- Signature Verification Logic ---
 // Validate packet authenticity by checking the sender's ed25519 signature.
 use ed25519_dalek::{VerifyingKey, Signature, Verifier};
 
-fn verify_packet_signature(packet: &AiTcpPacket, registry: &[AgentInfo]) -> bool {
-    let source_agent = match registry.iter().find(|a| a.public_key == packet.source_public_key) {
-        Some(agent) => agent,
+fn verify_packet_signature(packet: &AiTcpPacket, registry: &Registry) -> bool {
+    if REVOCATIONS.read().unwrap().as_ref().is_some_and(|c| c.is_revoked(&packet.source_public_key)) {
+        println!("Signature Fail: key {} is on the seed's revocation list.", packet.source_public_key);
+        return false;
+    }
+    let source_key = match registry.by_public_key(&packet.source_public_key).and_then(|a| a.public_key.as_deref()) {
+        Some(key) => key,
         None => {
             println!("Signature Fail: Source agent {} not found in registry.", packet.source_public_key);
             return false;
         }
     };
 
-    let public_key_bytes = match hex::decode(&source_agent.public_key) {
+    let public_key_bytes = match hex::decode(source_key) {
         Ok(bytes) => bytes,
         Err(_) => return false,
     };
@@ -47,16 +52,71 @@ fn verify_packet_signature(packet: &AiTcpPacket, registry: &[AgentInfo]) -> bool
     public_key.verify(packet.payload.as_bytes(), &signature).is_ok()
 }
 
+// --- Revocation list from the upstream seed ---
+// Set KAIRO_SEED_URL and KAIRO_SEED_PUBLIC_KEY to reject keys the seed has revoked.
+const REVOCATION_CACHE: &str = ".kairo/revocations.json";
+const REVOCATION_REFRESH_SECS: u64 = 60;
+
+static REVOCATIONS: once_cell::sync::Lazy<std::sync::RwLock<Option<RevocationCache>>> =
+    once_cell::sync::Lazy::new(|| std::sync::RwLock::new(None));
+
+async fn fetch_revocations(seed_url: &str, since: u64) -> Result<RevocationList, String> {
+    let url = format!("{}/revocations?since={}", seed_url.trim_end_matches('/'), since);
+    let resp = reqwest::get(&url).await.map_err(|e| format!("{}: {}", url, e))?;
+    resp.error_for_status().map_err(|e| e.to_string())?.json().await.map_err(|e| format!("{}: {}", url, e))
+}
+
+async fn refresh_revocations(seed_url: &str) -> Result<usize, String> {
+    let Some(mut cache) = REVOCATIONS.read().unwrap().clone() else { return Ok(0) };
+    let before = (cache.version(), cache.deltas());
+    let added = match cache.apply(fetch_revocations(seed_url, cache.version()).await?) {
+        Err(RevocationError::Gap { .. }) => cache.apply(fetch_revocations(seed_url, 0).await?),
+        other => other,
+    }
+    .map_err(|e| e.to_string())?;
+    if (cache.version(), cache.deltas()) == before {
+        return Ok(0);
+    }
+    cache.save(REVOCATION_CACHE).map_err(|e| e.to_string())?;
+    *REVOCATIONS.write().unwrap() = Some(cache);
+    Ok(added)
+}
+
+fn start_revocation_sync() {
+    let (Ok(seed_url), Ok(key)) = (std::env::var("KAIRO_SEED_URL"), std::env::var("KAIRO_SEED_PUBLIC_KEY")) else {
+        return;
+    };
+    let issuer = match kairo_lib::seed_key::parse_public_key(&key) {
+        Ok(issuer) => issuer,
+        Err(e) => {
+            println!("KAIRO_SEED_PUBLIC_KEY: {}; revocation sync disabled", e);
+            return;
+        }
+    };
+    let cache = RevocationCache::load(REVOCATION_CACHE, issuer).unwrap_or_else(|_| RevocationCache::new(issuer));
+    *REVOCATIONS.write().unwrap() = Some(cache);
+    tokio::spawn(async move {
+        loop {
+            match refresh_revocations(&seed_url).await {
+                Ok(added) if added > 0 => println!("{} more keys revoked by the seed", added),
+                Ok(_) => {}
+                Err(e) => println!("Revocation list refresh failed: {}", e),
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(REVOCATION_REFRESH_SECS)).await;
+        }
+    });
+}
+
 // In-memory message queue, now stores full packets
 static MESSAGE_QUEUE: once_cell::sync::Lazy<Arc<Mutex<std::collections::HashMap<String, Vec<AiTcpPacket>>>>> = once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(std::collections::HashMap::new())));
 
-// (Existing functions like read_registry, write_registry, handle_registration, etc.)
+// (Existing functions like handle_registration, etc.)
 
 // --- AI-TCP Communication Handlers ---
 async fn handle_send(packet: AiTcpPacket) -> Result<impl Reply, Rejection> {
     println!("Received packet to: {}, from: {}", packet.destination_p_address, packet.source_public_key);
     let mut queue = MESSAGE_QUEUE.lock().await;
-    let registry = read_registry().expect("DB read error during send");
+    let registry = seed_node::store().read().expect("DB read error during send");
     if verify_packet_signature(&packet, &registry) {
         println!("Signature VERIFIED for packet from {}", packet.source_public_key);
         let inbox = queue.entry(packet.destination_p_address.clone()).or_insert_with(Vec::new);
@@ -83,6 +143,7 @@ async fn handle_receive(p_address: String) -> Result<impl Reply, Rejection> {
 #[tokio::main]
 async fn main() {
     // (Existing setup...)
+    start_revocation_sync();
 
     let send = warp::post()
         .and(warp::path("send"))
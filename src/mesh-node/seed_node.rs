@@ -1,21 +1,58 @@
 use warp::{Filter, Rejection, Reply};
 use serde::{Deserialize, Serialize};
-use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use chrono::Utc;
+use ed25519_dalek::VerifyingKey;
 use kairo_lib::governance::OverridePackage;
+use kairo_lib::registration::ChallengeStore;
+use kairo_lib::registry_store::{AgentRecord, AgentStatus, RegistryError, RegistryStore};
+use once_cell::sync::Lazy;
 
+/// Registration with a proof of possession; see `kairo_lib::registration`.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct RegisterRequest {
     pub p_address: String,
+    pub public_key: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl RegisterRequest {
+    fn proof(&self) -> kairo_lib::registration::RegisterRequest {
+        kairo_lib::registration::RegisterRequest {
+            agent_id: self.p_address.clone(),
+            public_key: self.public_key.clone(),
+            nonce: self.nonce.clone(),
+            signature: self.signature.clone(),
+        }
+    }
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RevokeRequest {
+    pub p_address: String,
+}
+
+/// Reissue onto a new key, with the same proof as registration for `new_p_address`.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ReissueRequest {
     pub old_p_address: String,
     pub new_p_address: String,
+    pub public_key: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl ReissueRequest {
+    fn proof(&self) -> kairo_lib::registration::RegisterRequest {
+        kairo_lib::registration::RegisterRequest {
+            agent_id: self.new_p_address.clone(),
+            public_key: self.public_key.clone(),
+            nonce: self.nonce.clone(),
+            signature: self.signature.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -24,84 +61,100 @@ pub struct RegisterResponse {
     pub message: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct AgentInfo {
-    pub p_address: String,
-    pub public_key: String,
-    pub registered_at: String,
-    pub status: String,
-    pub replaces: Option<String>,
+const DB_FILE: &str = "registry.json";
+
+static CHALLENGES: Lazy<Mutex<ChallengeStore>> = Lazy::new(|| Mutex::new(ChallengeStore::new()));
+
+/// Agents here are named by their P address.
+pub fn store() -> RegistryStore {
+    RegistryStore::open(DB_FILE)
 }
 
-const DB_FILE: &str = "registry.json";
+fn reply(status: &str, message: String, code: warp::http::StatusCode) -> warp::reply::WithStatus<warp::reply::Json> {
+    let res = RegisterResponse { status: status.to_string(), message };
+    warp::reply::with_status(warp::reply::json(&res), code)
+}
 
-pub fn read_registry() -> Result<Vec<AgentInfo>, std::io::Error> {
-    let file = File::open(DB_FILE).unwrap_or_else(|_| File::create(DB_FILE).unwrap());
-    let reader = BufReader::new(file);
-    let registry = serde_json::from_reader(reader).unwrap_or_else(|_| Vec::new());
-    Ok(registry)
+fn registry_error(e: RegistryError) -> warp::reply::WithStatus<warp::reply::Json> {
+    match e {
+        RegistryError::Conflict(m) => reply("exists", m, warp::http::StatusCode::CONFLICT),
+        RegistryError::NotFound(m) => reply("not_found", m, warp::http::StatusCode::NOT_FOUND),
+        other => reply("error", other.to_string(), warp::http::StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Check a signed proof and use up its nonce; returns the lowercased key.
+async fn redeem_proof(
+    verified: Result<VerifyingKey, String>,
+    public_key: &str,
+    nonce: &str,
+) -> Result<String, warp::reply::WithStatus<warp::reply::Json>> {
+    if let Err(e) = verified {
+        return Err(reply("error", e, warp::http::StatusCode::UNAUTHORIZED));
+    }
+    if !CHALLENGES.lock().await.redeem(nonce, Utc::now().timestamp()) {
+        let message = "Unknown, used or expired nonce; request a new challenge".to_string();
+        return Err(reply("error", message, warp::http::StatusCode::UNAUTHORIZED));
+    }
+    Ok(public_key.to_ascii_lowercase())
 }
 
-pub fn write_registry(registry: &[AgentInfo]) -> std::io::Result<()> {
-    let file = OpenOptions::new().write(true).truncate(true).create(true).open(DB_FILE)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, registry)?;
-    Ok(())
+async fn handle_challenge() -> Result<impl Reply, Rejection> {
+    let challenge = CHALLENGES.lock().await.issue(Utc::now().timestamp());
+    Ok(warp::reply::json(&challenge))
 }
 
 async fn handle_registration(req: RegisterRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
     let _lock = db_lock.lock().await;
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if registry.iter().any(|agent| agent.p_address == req.p_address && agent.status == "active") {
-        let res = RegisterResponse { status: "exists".to_string(), message: "Active agent already registered".to_string() };
-        Ok(warp::reply::json(&res))
-    } else {
-        let new_agent = AgentInfo {
-            p_address: req.p_address.clone(),
-            public_key: String::new(),
-            registered_at: Utc::now().to_rfc3339(),
-            status: "active".to_string(),
-            replaces: None,
-        };
-        registry.push(new_agent);
-        write_registry(&registry).expect("Failed to write to DB");
-        let res = RegisterResponse { status: "success".to_string(), message: "Agent successfully registered".to_string() };
-        Ok(warp::reply::json(&res))
-    }
+    let public_key = match redeem_proof(req.proof().verify(), &req.public_key, &req.nonce).await {
+        Ok(key) => key,
+        Err(reply) => return Ok(reply),
+    };
+    let result = store().transaction(|r| {
+        if r.by_p_address(&req.p_address).is_some() {
+            return Ok(reply("exists", "Active agent already registered".to_string(), warp::http::StatusCode::OK));
+        }
+        r.insert(AgentRecord {
+            p_address: Some(req.p_address.clone()),
+            public_key: Some(public_key),
+            ..AgentRecord::new(&req.p_address)
+        })?;
+        Ok(reply("success", "Agent successfully registered".to_string(), warp::http::StatusCode::OK))
+    });
+    Ok(result.unwrap_or_else(registry_error))
 }
 
-async fn handle_revocation(req: RegisterRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
+async fn handle_revocation(req: RevokeRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
     let _lock = db_lock.lock().await;
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if let Some(agent) = registry.iter_mut().find(|a| a.p_address == req.p_address && a.status == "active") {
-        agent.status = "revoked".to_string();
-        write_registry(&registry).expect("Failed to write to DB");
-        let res = RegisterResponse { status: "success".to_string(), message: "Agent successfully revoked".to_string() };
-        Ok(warp::reply::json(&res))
-    } else {
-        let res = RegisterResponse { status: "not_found".to_string(), message: "Active agent not found".to_string() };
-        Ok(warp::reply::json(&res))
-    }
+    let result = store().transaction(|r| {
+        let name = match r.by_p_address(&req.p_address) {
+            Some(agent) => agent.name.clone(),
+            None => return Ok(reply("not_found", "Active agent not found".to_string(), warp::http::StatusCode::OK)),
+        };
+        r.set_status(&name, AgentStatus::Revoked)?;
+        Ok(reply("success", "Agent successfully revoked".to_string(), warp::http::StatusCode::OK))
+    });
+    Ok(result.unwrap_or_else(registry_error))
 }
 
 async fn handle_reissue(req: ReissueRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
     let _lock = db_lock.lock().await;
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if registry.iter().any(|a| a.p_address == req.new_p_address && a.status == "active") {
-        let res = RegisterResponse { status: "exists".to_string(), message: "New P address already exists".to_string() };
-        return Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::CONFLICT));
-    }
-    let new_agent = AgentInfo {
-        p_address: req.new_p_address.clone(),
-        public_key: String::new(),
-        registered_at: Utc::now().to_rfc3339(),
-        status: "active".to_string(),
-        replaces: Some(req.old_p_address.clone()),
+    let public_key = match redeem_proof(req.proof().verify(), &req.public_key, &req.nonce).await {
+        Ok(key) => key,
+        Err(reply) => return Ok(reply),
     };
-    registry.push(new_agent);
-    write_registry(&registry).expect("Failed to write to DB");
-    let res = RegisterResponse { status: "success".to_string(), message: "Agent ID successfully reissued".to_string() };
-    Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::OK))
+    let result = store().transaction(|r| {
+        r.reissue(
+            &req.old_p_address,
+            AgentRecord {
+                p_address: Some(req.new_p_address.clone()),
+                public_key: Some(public_key),
+                ..AgentRecord::new(&req.new_p_address)
+            },
+        )?;
+        Ok(reply("success", "Agent ID successfully reissued".to_string(), warp::http::StatusCode::OK))
+    });
+    Ok(result.unwrap_or_else(registry_error))
 }
 
 async fn handle_emergency_reissue(_req: OverridePackage) -> Result<impl warp::Reply, warp::Rejection> {
@@ -111,9 +164,13 @@ async fn handle_emergency_reissue(_req: OverridePackage) -> Result<impl warp::Re
 pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     let db_lock = Arc::new(Mutex::new(()));
 
+    let challenge = warp::post()
+        .and(warp::path!("register" / "challenge"))
+        .and_then(handle_challenge);
+
     let register_lock = Arc::clone(&db_lock);
     let register = warp::post()
-        .and(warp::path("register"))
+        .and(warp::path!("register"))
         .and(warp::body::json())
         .and(warp::any().map({
             let register_lock = Arc::clone(&register_lock);
@@ -146,5 +203,5 @@ pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone
         .and(warp::body::json())
         .and_then(handle_emergency_reissue);
 
-    register.or(revoke).or(reissue).or(emergency_reissue)
+    challenge.or(register).or(revoke).or(reissue).or(emergency_reissue)
 }
@@ -3,8 +3,6 @@
 
 use warp::{Filter, Rejection, Reply};
 use serde::{Deserialize, Serialize};
-use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use chrono::Utc;
@@ -15,6 +13,14 @@ use ed25519_dalek::{VerifyingKey, Signature, Verifier};
 
 use kairo_lib::governance::OverridePackage;
 use kairo_lib::config::DaemonConfig;
+use kairo_lib::federation::{self, apply_state_and_release, FederationConfig, FederationState, PeerConfig};
+use kairo_lib::p_allocator::{assign_in_file, with_lease_file, AllocError, Allocator, AllocatorConfig};
+use kairo_lib::registration::{AssignRequest, ChallengeStore, RegisterRequest, RevokeRequest};
+use kairo_lib::registry_store::{AgentRecord, AgentStatus, Registry, RegistryError, RegistryStore};
+use kairo_lib::registry_snapshot::Snapshot;
+use kairo_lib::revocation::RevocationList;
+use kairo_lib::seed_key;
+use ed25519_dalek::SigningKey;
 use std::fs;
 
 fn load_node_config(path: &str) -> Result<DaemonConfig, Box<dyn std::error::Error>> {
@@ -24,58 +30,75 @@ fn load_node_config(path: &str) -> Result<DaemonConfig, Box<dyn std::error::Erro
 }
 
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct RegisterRequest {
-    agent_id: String,
-}
-
+/// The new identity proves possession of its key like a registration does.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct ReissueRequest {
     old_agent_id: String,
     new_agent_id: String,
+    public_key: String,
+    nonce: String,
+    signature: String,
 }
 
-#[derive(Debug, Serialize)]
-struct RegisterResponse {
-    status: String,
-    message: String,
+#[derive(Debug, Deserialize)]
+struct RevocationQuery {
+    #[serde(default)]
+    since: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct AgentInfo {
-    agent_id: String,
+#[derive(Debug, Deserialize)]
+struct StatusQuery {
     public_key: String,
-    registered_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse {
+    agent_id: String,
+    status: AgentStatus,
+    p_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProofQuery {
+    p_address: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RegisterResponse {
     status: String,
-    replaces: Option<String>,
+    message: String,
 }
 
 const DB_FILE: &str = "registry.json";
+const LEASE_FILE: &str = "p_leases.json";
+const POOLS_CONFIG: &str = ".kairo/config/p_address_pools.json";
 
-fn read_registry() -> Result<Vec<AgentInfo>, std::io::Error> {
-    let file = File::open(DB_FILE).unwrap_or_else(|_| File::create(DB_FILE).unwrap());
-    let reader = BufReader::new(file);
-    let registry = serde_json::from_reader(reader).unwrap_or_else(|_| Vec::new());
-    Ok(registry)
+fn load_allocator(listen_address: &str) -> Allocator {
+    let config = match fs::read_to_string(POOLS_CONFIG) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| panic!("Invalid {}: {}", POOLS_CONFIG, e)),
+        Err(_) => AllocatorConfig::default(),
+    };
+    let mut allocator = Allocator::new(config).unwrap_or_else(|e| panic!("{}", e));
+    // Never lease out the seed's own address.
+    allocator.reserve(listen_address).ok();
+    allocator
 }
 
-fn write_registry(registry: &[AgentInfo]) -> std::io::Result<()> {
-    let file = OpenOptions::new().write(true).truncate(true).create(true).open(DB_FILE)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, registry)?;
-    Ok(())
+fn store() -> RegistryStore {
+    RegistryStore::open(DB_FILE)
 }
 
-fn verify_packet_signature(packet: &AiTcpPacket, registry: &[AgentInfo]) -> bool {
-    let source_agent = match registry.iter().find(|a| a.public_key == packet.source_public_key) {
-        Some(agent) => agent,
+fn verify_packet_signature(packet: &AiTcpPacket, registry: &Registry) -> bool {
+    let source_key = match registry.by_public_key(&packet.source_public_key).and_then(|a| a.public_key.as_deref()) {
+        Some(key) => key,
         None => {
             println!("Signature Fail: Source agent {} not found in registry.", packet.source_public_key);
             return false;
         }
     };
 
-    let public_key_bytes = match hex::decode(&source_agent.public_key) {
+    let public_key_bytes = match hex::decode(source_key) {
         Ok(bytes) => bytes,
         Err(_) => return false,
     };
@@ -99,11 +122,74 @@ fn verify_packet_signature(packet: &AiTcpPacket, registry: &[AgentInfo]) -> bool
 }
 
 static MESSAGE_QUEUE: Lazy<Arc<Mutex<HashMap<String, Vec<AiTcpPacket>>>>> = Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+static CHALLENGES: Lazy<Mutex<ChallengeStore>> = Lazy::new(|| Mutex::new(ChallengeStore::new()));
+/// Stamped as `origin` on registrations accepted here.
+static SEED_ID: once_cell::sync::OnceCell<String> = once_cell::sync::OnceCell::new();
+
+fn seed_id() -> Option<String> {
+    SEED_ID.get().cloned()
+}
+
+/// The P address currently leased to `public_key`, if any.
+fn leased_address(public_key: &str) -> Option<String> {
+    with_lease_file(LEASE_FILE, |leases| {
+        Ok(leases.iter().find(|l| l.public_key.eq_ignore_ascii_case(public_key)).map(|l| l.p_address.clone()))
+    })
+    .ok()
+    .flatten()
+}
+
+fn reply_status(status: &str, message: String, code: warp::http::StatusCode) -> warp::reply::WithStatus<warp::reply::Json> {
+    let res = RegisterResponse { status: status.to_string(), message };
+    warp::reply::with_status(warp::reply::json(&res), code)
+}
+
+/// Check a proof of possession and consume its nonce; returns the key (lowercase hex).
+async fn verify_proof(req: &RegisterRequest) -> Result<String, warp::reply::WithStatus<warp::reply::Json>> {
+    redeem_proof(req.verify(), &req.public_key, &req.nonce).await
+}
+
+async fn redeem_proof(
+    verified: Result<VerifyingKey, String>,
+    public_key: &str,
+    nonce: &str,
+) -> Result<String, warp::reply::WithStatus<warp::reply::Json>> {
+    if let Err(e) = verified {
+        return Err(reply_status("error", e, warp::http::StatusCode::UNAUTHORIZED));
+    }
+    if !CHALLENGES.lock().await.redeem(nonce, Utc::now().timestamp()) {
+        let message = "Unknown, used or expired nonce; request a new challenge".to_string();
+        return Err(reply_status("error", message, warp::http::StatusCode::UNAUTHORIZED));
+    }
+    Ok(public_key.to_ascii_lowercase())
+}
+
+fn registry_error(e: RegistryError) -> warp::reply::WithStatus<warp::reply::Json> {
+    match e {
+        RegistryError::Conflict(m) => reply_status("exists", m, warp::http::StatusCode::CONFLICT),
+        RegistryError::NotFound(m) => reply_status("not_found", m, warp::http::StatusCode::NOT_FOUND),
+        other => {
+            eprintln!("Registry error: {}", other);
+            reply_status("error", other.to_string(), warp::http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn handle_challenge() -> Result<impl Reply, Rejection> {
+    let challenge = CHALLENGES.lock().await.issue(Utc::now().timestamp());
+    Ok(warp::reply::json(&challenge))
+}
 
 async fn handle_send(packet: AiTcpPacket) -> Result<impl Reply, Rejection> {
     println!("Received packet to: {}, from: {}", packet.destination_p_address, packet.source_public_key);
     let mut queue = MESSAGE_QUEUE.lock().await;
-    let registry = read_registry().expect("Config read error during send");
+    let registry = match store().read() {
+        Ok(registry) => registry,
+        Err(e) => {
+            eprintln!("Registry read error during send: {}", e);
+            return Ok(warp::reply::json(&"registry_unavailable"));
+        }
+    };
     if verify_packet_signature(&packet, &registry) {
         println!("Signature VERIFIED for packet from {}", packet.source_public_key);
         let inbox = queue.entry(packet.destination_p_address.clone()).or_insert_with(Vec::new);
@@ -129,74 +215,244 @@ async fn handle_receive(p_address: String) -> Result<impl Reply, Rejection> {
 async fn handle_registration(req: RegisterRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
     let _lock = db_lock.lock().await;
     println!("Received registration for agent_id: {}", req.agent_id);
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if registry.iter().any(|agent| agent.agent_id == req.agent_id && agent.status == "active") {
-        let res = RegisterResponse { status: "exists".to_string(), message: "Active agent already registered".to_string() };
-        Ok(warp::reply::json(&res))
-    } else {
-        let new_agent = AgentInfo {
-            agent_id: req.agent_id.clone(),
-            public_key: req.agent_id.clone(), // agent_id を public_key として使用
-            registered_at: Utc::now().to_rfc3339(),
-            status: "active".to_string(),
-            replaces: None,
-        };
-        registry.push(new_agent);
-        write_registry(&registry).expect("Failed to write to DB");
-        let res = RegisterResponse { status: "success".to_string(), message: "Agent successfully registered".to_string() };
-        Ok(warp::reply::json(&res))
+    let public_key = match verify_proof(&req).await {
+        Ok(key) => key,
+        Err(reply) => {
+            println!("Registration REJECTED for {}: bad proof of possession", req.agent_id);
+            return Ok(reply);
+        }
+    };
+    let result = store().transaction(|r| {
+        if r.by_name(&req.agent_id).is_some() {
+            return Ok(reply_status("exists", "Active agent already registered".to_string(), warp::http::StatusCode::OK));
+        }
+        r.insert(AgentRecord {
+            p_address: leased_address(&public_key),
+            public_key: Some(public_key),
+            origin: seed_id(),
+            ..AgentRecord::new(&req.agent_id)
+        })?;
+        Ok(reply_status("success", "Agent successfully registered".to_string(), warp::http::StatusCode::OK))
+    });
+    Ok(result.unwrap_or_else(registry_error))
+}
+
+async fn handle_assign(req: AssignRequest, allocator: Arc<Allocator>) -> Result<impl Reply, Rejection> {
+    // Only the key holder may lease, so nobody can drain the pools with made-up keys.
+    let public_key = match redeem_proof(req.verify(), &req.public_key, &req.nonce).await {
+        Ok(key) => key,
+        Err(reply) => {
+            println!("Lease REJECTED for {}: bad proof of possession", req.public_key);
+            return Ok(reply);
+        }
+    };
+    // Addresses registered to other keys, possibly on a federated seed, are taken.
+    let mut allocator = (*allocator).clone();
+    match store().read() {
+        Ok(registry) => {
+            for a in registry.active().filter(|a| !a.public_key.as_deref().is_some_and(|k| k.eq_ignore_ascii_case(&public_key))) {
+                if let Some(p) = &a.p_address {
+                    allocator.reserve(p).ok();
+                }
+            }
+        }
+        Err(e) => return Ok(registry_error(e)),
+    }
+    match assign_in_file(LEASE_FILE, &allocator, &public_key, req.family, Utc::now().timestamp()) {
+        Ok(lease) => {
+            println!("Leased {} to {}", lease.p_address, lease.public_key);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "p_address": lease.p_address, "expires_at": lease.expires_at })),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            let code = match e {
+                AllocError::InvalidKey => warp::http::StatusCode::BAD_REQUEST,
+                AllocError::Exhausted => warp::http::StatusCode::CONFLICT,
+                AllocError::Config(_) | AllocError::Io(_) => warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Ok(reply_status("error", e.to_string(), code))
+        }
     }
 }
 
-async fn handle_revocation(req: RegisterRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
+/// Revoke an agent; only the seed's administrators may, since the result is
+/// signed and distributed to every node.
+async fn handle_revocation(
+    req: RevokeRequest,
+    db_lock: Arc<Mutex<()>>,
+    admins: Arc<Vec<VerifyingKey>>,
+) -> Result<impl Reply, Rejection> {
     let _lock = db_lock.lock().await;
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if let Some(agent) = registry.iter_mut().find(|a| a.agent_id == req.agent_id && a.status == "active") {
-        agent.status = "revoked".to_string();
-        write_registry(&registry).expect("Failed to write to DB");
-        let res = RegisterResponse { status: "success".to_string(), message: "Agent successfully revoked".to_string() };
-        Ok(warp::reply::json(&res))
-    } else {
-        let res = RegisterResponse { status: "not_found".to_string(), message: "Active agent not found".to_string() };
-        Ok(warp::reply::json(&res))
+    if let Err(reply) = redeem_proof(req.verify(&admins), &req.admin_key, &req.nonce).await {
+        return Ok(reply);
     }
+    let reply = match store().transaction(|r| r.set_status(&req.agent_id, AgentStatus::Revoked)) {
+        Ok(()) => reply_status("success", "Agent successfully revoked".to_string(), warp::http::StatusCode::OK),
+        Err(RegistryError::NotFound(_)) => {
+            reply_status("not_found", "Active agent not found".to_string(), warp::http::StatusCode::OK)
+        }
+        Err(e) => registry_error(e),
+    };
+    Ok(reply)
 }
 
-async fn handle_reissue(req: ReissueRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
-    let _lock = db_lock.lock().await;
-    let mut registry = read_registry().expect("Failed to read from DB");
-    if let Some(old_agent) = registry.iter().find(|a| a.agent_id == req.old_agent_id) {
-        if old_agent.status != "revoked" {
-            let res = RegisterResponse { status: "error".to_string(), message: "Old agent is not revoked".to_string() };
-            return Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::BAD_REQUEST));
+/// The signed revocation log, or only the entries after `since`.
+async fn handle_revocation_list(query: RevocationQuery, key: Arc<SigningKey>) -> Result<impl Reply, Rejection> {
+    let reply = match store().read() {
+        Ok(registry) => {
+            let list = RevocationList::issue(registry.revocations(), query.since, &key, Utc::now().timestamp());
+            warp::reply::with_status(warp::reply::json(&list), warp::http::StatusCode::OK)
         }
-    } else {
-        let res = RegisterResponse { status: "not_found".to_string(), message: "Old agent not found".to_string() };
-        return Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::NOT_FOUND));
-    }
-    if registry.iter().any(|a| a.agent_id == req.new_agent_id && a.status == "active") {
-        let res = RegisterResponse { status: "exists".to_string(), message: "New agent ID already exists as an active agent".to_string() };
-        return Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::CONFLICT));
+        Err(e) => registry_error(e),
+    };
+    Ok(reply)
+}
+
+/// This seed's whole registry, signed, for federated peers.
+async fn handle_federation_state(key: Arc<SigningKey>) -> Result<impl Reply, Rejection> {
+    let reply = match store().read() {
+        Ok(registry) => {
+            let state = FederationState::issue(&registry, &seed_id().unwrap_or_default(), &key, Utc::now().timestamp());
+            warp::reply::with_status(warp::reply::json(&state), warp::http::StatusCode::OK)
+        }
+        Err(e) => registry_error(e),
+    };
+    Ok(reply)
+}
+
+async fn fetch_peer_state(client: &reqwest::Client, peer: &PeerConfig) -> Result<FederationState, String> {
+    let url = format!("{}/federation/state", peer.url.trim_end_matches('/'));
+    let res = client.get(&url).send().await.map_err(|e| format!("{}: {}", url, e))?;
+    res.error_for_status().map_err(|e| e.to_string())?.json().await.map_err(|e| format!("{}: {}", url, e))
+}
+
+/// Pull every peer's state and merge the ones that changed since the last round.
+async fn sync_peers(config: FederationConfig, allocator: Arc<Allocator>) {
+    let client = reqwest::Client::builder().timeout(std::time::Duration::from_secs(10)).build().unwrap();
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    loop {
+        for peer in &config.peers {
+            let peer_key = match kairo_lib::seed_key::parse_public_key(&peer.public_key) {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("Federation peer {}: {}", peer.url, e);
+                    continue;
+                }
+            };
+            let state = match fetch_peer_state(&client, peer).await {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("Federation sync with {} failed: {}", peer.url, e);
+                    continue;
+                }
+            };
+            if seen.get(&peer.url) == Some(&state.generation) {
+                continue;
+            }
+            // Our agents that lose a conflict give up their address and lease;
+            // they see it on `/register/status` and must lease a new one.
+            let origin = seed_id();
+            match apply_state_and_release(&store(), &state, &peer_key, origin.as_deref(), LEASE_FILE, &allocator) {
+                Ok(report) => {
+                    seen.insert(peer.url.clone(), state.generation);
+                    if report.added + report.updated > 0 {
+                        println!("Merged {} (generation {}): {} added, {} updated", peer.url, state.generation, report.added, report.updated);
+                    }
+                    for lost in report.conflicted.iter().filter(|a| a.origin == origin) {
+                        println!("Agent {} conflicts with an earlier registration on another seed", lost.name);
+                    }
+                }
+                Err(e) => eprintln!("Federation state from {} rejected: {}", peer.url, e),
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(config.sync_secs.max(1))).await;
     }
-    let new_agent = AgentInfo {
+}
+
+/// Registration status of the agent holding `public_key`: its active record,
+/// else its newest one. A `conflicted` agent lost to an earlier registration
+/// on another seed; its P address was taken away.
+async fn handle_status(query: StatusQuery) -> Result<impl Reply, Rejection> {
+    let registry = match store().read() {
+        Ok(registry) => registry,
+        Err(e) => return Ok(registry_error(e)),
+    };
+    let key = query.public_key.to_ascii_lowercase();
+    let record = registry
+        .by_public_key(&key)
+        .or_else(|| registry.agents().iter().rev().find(|a| a.public_key.as_deref() == Some(key.as_str())));
+    let reply = match record {
+        Some(a) => {
+            let res = StatusResponse { agent_id: a.name.clone(), status: a.status, p_address: a.p_address.clone() };
+            warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::OK)
+        }
+        None => reply_status("not_found", "No agent holds this key".to_string(), warp::http::StatusCode::NOT_FOUND),
+    };
+    Ok(reply)
+}
+
+/// The signed head of the current registry snapshot.
+async fn handle_snapshot(key: Arc<SigningKey>) -> Result<impl Reply, Rejection> {
+    let reply = match store().read() {
+        Ok(registry) => {
+            let snapshot = Snapshot::build(&registry, &key, Utc::now().timestamp());
+            warp::reply::with_status(warp::reply::json(&snapshot.head), warp::http::StatusCode::OK)
+        }
+        Err(e) => registry_error(e),
+    };
+    Ok(reply)
+}
+
+/// Inclusion proof for the newest record with `p_address` (or `name`).
+async fn handle_snapshot_proof(query: ProofQuery, key: Arc<SigningKey>) -> Result<impl Reply, Rejection> {
+    let registry = match store().read() {
+        Ok(registry) => registry,
+        Err(e) => return Ok(registry_error(e)),
+    };
+    let snapshot = Snapshot::build(&registry, &key, Utc::now().timestamp());
+    let reply = match snapshot.prove_entry(query.p_address.as_deref(), query.name.as_deref()) {
+        Some(proof) => warp::reply::with_status(warp::reply::json(&proof), warp::http::StatusCode::OK),
+        None => reply_status("not_found", "No registry entry matches".to_string(), warp::http::StatusCode::NOT_FOUND),
+    };
+    Ok(reply)
+}
+
+async fn handle_reissue(req: ReissueRequest, db_lock: Arc<Mutex<()>>) -> Result<impl Reply, Rejection> {
+    let _lock = db_lock.lock().await;
+    let proof = RegisterRequest {
         agent_id: req.new_agent_id.clone(),
-        public_key: req.new_agent_id.clone(), // new_agent_id を public_key として使用
-        registered_at: Utc::now().to_rfc3339(),
-        status: "active".to_string(),
-        replaces: Some(req.old_agent_id.clone()),
+        public_key: req.public_key.clone(),
+        nonce: req.nonce.clone(),
+        signature: req.signature.clone(),
     };
-    registry.push(new_agent);
-    write_registry(&registry).expect("Failed to write to DB");
-    let res = RegisterResponse { status: "success".to_string(), message: "Agent ID successfully reissued".to_string() };
-    Ok(warp::reply::with_status(warp::reply::json(&res), warp::http::StatusCode::OK))
+    let public_key = match verify_proof(&proof).await {
+        Ok(key) => key,
+        Err(reply) => return Ok(reply),
+    };
+    let result = store().transaction(|r| {
+        r.reissue(
+            &req.old_agent_id,
+            AgentRecord {
+                p_address: leased_address(&public_key),
+                public_key: Some(public_key),
+                origin: seed_id(),
+                ..AgentRecord::new(&req.new_agent_id)
+            },
+        )?;
+        Ok(reply_status("success", "Agent ID successfully reissued".to_string(), warp::http::StatusCode::OK))
+    });
+    Ok(result.unwrap_or_else(registry_error))
 }
 
 async fn handle_emergency_reissue(req: OverridePackage, db_lock: Arc<Mutex<()>>) -> Result<impl warp::Reply, warp::Rejection> {
     let _lock = db_lock.lock().await;
     println!("Received emergency reissue request.");
 
-    let registry = read_registry().expect("Failed to read from DB");
-    let agent_configs = read_registry().expect("Failed to read agent configs");
+    let registry = store()
+        .read()
+        .map_err(|e| warp::reject::custom(BadRequest(format!("Registry unavailable: {}", e))))?;
 
     // 1. JSONで受け取ったOverridePackageをパース (warp::body::json() で既にパース済み)
     let payload_bytes = serde_json::to_vec(&req.payload)
@@ -208,10 +464,10 @@ async fn handle_emergency_reissue(req: OverridePackage, db_lock: Arc<Mutex<()>>)
         let signature_hex = &sig_package.signature;
 
         // 署名者IDが実在し、`agent_registry` に登録済であること
-        let signatory_agent = agent_configs.iter().find(|a| a.agent_id == *signatory_id)
+        let signatory_key = registry.by_name(signatory_id).and_then(|a| a.public_key.as_deref())
             .ok_or_else(|| warp::reject::custom(BadRequest(format!("Signatory ID not found: {}", signatory_id))))?;
 
-        let public_key_bytes: [u8; 32] = hex::decode(&signatory_agent.public_key)
+        let public_key_bytes: [u8; 32] = hex::decode(signatory_key)
             .map_err(|_| warp::reject::custom(BadRequest("Invalid public key format".to_string())))?
             .try_into()
             .map_err(|_| warp::reject::custom(BadRequest("Invalid public key length".to_string())))?;
@@ -233,13 +489,13 @@ async fn handle_emergency_reissue(req: OverridePackage, db_lock: Arc<Mutex<()>>)
     let new_agent_id = &req.payload.new_agent_id;
 
     // old_agent_id が `agent_registry` に存在し、`revoked` 状態であること
-    let old_agent_exists_and_revoked = registry.iter().any(|a| a.agent_id == *old_agent_id && a.status == "revoked");
+    let old_agent_exists_and_revoked = registry.agents().iter().any(|a| a.name == *old_agent_id && a.status == AgentStatus::Revoked);
     if !old_agent_exists_and_revoked {
         return Err(warp::reject::custom(BadRequest(format!("Old agent ID '{}' not found or not revoked.", old_agent_id))));
     }
 
     // new_agent_id が `agent_registry` に存在しない、または `active` 状態でないこと
-    let new_agent_exists_and_active = registry.iter().any(|a| a.agent_id == *new_agent_id && a.status == "active");
+    let new_agent_exists_and_active = registry.by_name(new_agent_id).is_some();
     if new_agent_exists_and_active {
         return Err(warp::reject::custom(BadRequest(format!("New agent ID '{}' already exists and is active.", new_agent_id))));
     }
@@ -281,10 +537,46 @@ async fn main() {
 
     println!("Listening on {}:{}", config.listen_address, config.listen_port);
 
+    let signing_key = seed_key::load_or_create(seed_key::DEFAULT_SEED_KEY_FILE).unwrap_or_else(|e| {
+        panic!("Failed to load seed key {}: {}", seed_key::DEFAULT_SEED_KEY_FILE, e)
+    });
+    println!("Revocation lists and registry snapshots signed by {}", hex::encode(signing_key.verifying_key().to_bytes()));
+    let federation_config: FederationConfig = match fs::read_to_string(federation::DEFAULT_FEDERATION_CONFIG) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| panic!("Invalid {}: {}", federation::DEFAULT_FEDERATION_CONFIG, e)),
+        Err(_) => FederationConfig::default(),
+    };
+    let id = federation_config.seed_id.clone().unwrap_or_else(|| federation::seed_id(&signing_key.verifying_key()));
+    println!("Seed id {}; federating with {} peers", id, federation_config.peers.len());
+    SEED_ID.set(id).ok();
+    let admins = seed_key::load_admin_keys(seed_key::DEFAULT_SEED_ADMINS_FILE, &signing_key.verifying_key())
+        .unwrap_or_else(|e| panic!("Invalid administrator keys: {}", e));
+    println!("{} administrator keys may revoke agents", admins.len());
+    let admins = Arc::new(admins);
+    let signing_key = Arc::new(signing_key);
+
     let db_lock = Arc::new(Mutex::new(()));
+    let allocator = Arc::new(load_allocator(&config.listen_address));
+    if !federation_config.peers.is_empty() {
+        tokio::spawn(sync_peers(federation_config, Arc::clone(&allocator)));
+    }
+
+    let assign = warp::post()
+        .and(warp::path!("assign_p_address"))
+        .and(warp::body::json())
+        .and(warp::any().map(move || Arc::clone(&allocator)))
+        .and_then(handle_assign);
+
+    let challenge = warp::post()
+        .and(warp::path!("register" / "challenge"))
+        .and_then(handle_challenge);
+
+    let status = warp::get()
+        .and(warp::path!("register" / "status"))
+        .and(warp::query::<StatusQuery>())
+        .and_then(handle_status);
 
     let register = warp::post()
-        .and(warp::path("register"))
+        .and(warp::path!("register"))
         .and(warp::body::json())
         .and(warp::any().map({
             let register_lock = Arc::clone(&db_lock);
@@ -299,8 +591,40 @@ async fn main() {
             let revoke_lock = Arc::clone(&db_lock);
             move || Arc::clone(&revoke_lock)
         }))
+        .and(warp::any().map(move || Arc::clone(&admins)))
         .and_then(handle_revocation);
 
+    let revocations = warp::get()
+        .and(warp::path!("revocations"))
+        .and(warp::query::<RevocationQuery>())
+        .and(warp::any().map({
+            let key = Arc::clone(&signing_key);
+            move || Arc::clone(&key)
+        }))
+        .and_then(handle_revocation_list);
+
+    let federation_state = warp::get()
+        .and(warp::path!("federation" / "state"))
+        .and(warp::any().map({
+            let key = Arc::clone(&signing_key);
+            move || Arc::clone(&key)
+        }))
+        .and_then(handle_federation_state);
+
+    let snapshot = warp::get()
+        .and(warp::path!("registry" / "snapshot"))
+        .and(warp::any().map({
+            let key = Arc::clone(&signing_key);
+            move || Arc::clone(&key)
+        }))
+        .and_then(handle_snapshot);
+
+    let snapshot_proof = warp::get()
+        .and(warp::path!("registry" / "proof"))
+        .and(warp::query::<ProofQuery>())
+        .and(warp::any().map(move || Arc::clone(&signing_key)))
+        .and_then(handle_snapshot_proof);
+
     let reissue = warp::post()
         .and(warp::path("reissue"))
         .and(warp::body::json())
@@ -329,7 +653,7 @@ async fn main() {
         .and(warp::path::param())
         .and_then(handle_receive);
 
-    let routes = register.or(revoke).or(reissue).or(emergency_reissue).or(send).or(receive).recover(handle_rejection);
+    let routes = challenge.or(status).or(register).or(assign).or(revoke).or(revocations).or(snapshot).or(snapshot_proof).or(federation_state).or(reissue).or(emergency_reissue).or(send).or(receive).recover(handle_rejection);
 
     warp::serve(routes).run((config.listen_address.parse::<std::net::IpAddr>().unwrap(), config.listen_port)).await;
 }